@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::api::auth::AdminToken;
+use crate::api::instagram::DEFAULT_BATCH_CONCURRENCY;
+use crate::api::ApiError;
+use crate::cache::{ImageCache, InstagramCache};
+use crate::config::AppConfig;
+use crate::proxy::{ProxyManager, ProxySnapshot};
+use crate::models::instagram::InstagramUser;
+use crate::scrapers::instagram::{InstagramScraper, ScraperError};
+use crate::scrapers::stats::ScrapeStatsSnapshot;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxiesResponse {
+    pub available: usize,
+    pub total: usize,
+    pub proxies: Vec<ProxySnapshot>,
+}
+
+#[get("/proxies")]
+pub async fn get_proxies(
+    admin: Result<AdminToken, ApiError>,
+    proxy_manager: &State<ProxyManager>,
+) -> Result<Json<ProxiesResponse>, ApiError> {
+    admin?;
+
+    let (available, total) = proxy_manager.get_proxy_count();
+
+    Ok(Json(ProxiesResponse {
+        available,
+        total,
+        proxies: proxy_manager.snapshot(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsResponse {
+    pub uptime_secs: u64,
+    pub cache: CacheStats,
+    pub proxies: ProxyStats,
+    pub scrapes: ScrapeStatsSnapshot,
+}
+
+// Cache sizes, unknown (null) with shared backends
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub users: Option<usize>,
+    pub images: Option<usize>,
+    pub image_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyStats {
+    pub available: usize,
+    pub total: usize,
+}
+
+// Time the server started, for the uptime
+pub struct StartedAt(pub std::time::Instant);
+
+#[get("/stats")]
+pub async fn get_stats(
+    admin: Result<AdminToken, ApiError>,
+    started_at: &State<StartedAt>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+    image_cache: &State<ImageCache>,
+    proxy_manager: &State<ProxyManager>,
+) -> Result<Json<StatsResponse>, ApiError> {
+    admin?;
+
+    let image_usage = image_cache.usage();
+    let (available, total) = proxy_manager.get_proxy_count();
+
+    Ok(Json(StatsResponse {
+        uptime_secs: started_at.0.elapsed().as_secs(),
+        cache: CacheStats {
+            users: cache.user_count(),
+            images: image_usage.map(|(count, _)| count),
+            image_bytes: image_usage.map(|(_, bytes)| bytes),
+        },
+        proxies: ProxyStats { available, total },
+        scrapes: scraper.stats(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreloadResponse {
+    pub data: HashMap<String, PreloadEntry>,
+}
+
+// Outcome of a single username of a preload
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status")]
+pub enum PreloadEntry {
+    // Already cached and not expired, left untouched
+    Cached,
+    Scraped,
+    Failed { error: String, code: String },
+}
+
+/// Scrape profiles into the cache ahead of a traffic spike, with the batch concurrency.
+/// Profiles already cached are skipped unless `fresh` is set.
+#[post("/preload?<fresh>", format = "json", data = "<usernames>")]
+pub async fn preload(
+    usernames: Json<Vec<String>>,
+    fresh: Option<bool>,
+    admin: Result<AdminToken, ApiError>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+    config: &State<AppConfig>,
+) -> Result<Json<PreloadResponse>, ApiError> {
+    admin?;
+
+    // De-duplicate usernames, preserving request order
+    let mut unique_usernames: Vec<String> = Vec::new();
+    for username in usernames.into_inner() {
+        if !unique_usernames.contains(&username) {
+            unique_usernames.push(username);
+        }
+    }
+
+    let semaphore = Semaphore::new(config.batch_concurrency.unwrap_or(DEFAULT_BATCH_CONCURRENCY).max(1));
+    let fresh = fresh.unwrap_or(false);
+
+    let entries = futures::future::join_all(unique_usernames.into_iter().map(|username| {
+        let semaphore = &semaphore;
+        async move {
+            let entry = match preload_user(&username, fresh, scraper, cache, config, semaphore).await {
+                Ok(entry) => entry,
+                Err(err) => PreloadEntry::Failed { error: err.to_string(), code: err.code().to_string() },
+            };
+            (username, entry)
+        }
+    })).await;
+
+    Ok(Json(PreloadResponse {
+        data: entries.into_iter().collect(),
+    }))
+}
+
+async fn preload_user(
+    username: &str,
+    fresh: bool,
+    scraper: &InstagramScraper,
+    cache: &InstagramCache,
+    config: &AppConfig,
+    semaphore: &Semaphore,
+) -> Result<PreloadEntry, ScraperError> {
+    if !config.username_allowed(username) {
+        return Err(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username)));
+    }
+
+    if !fresh && cache.get_user(username).is_some() {
+        return Ok(PreloadEntry::Cached);
+    }
+
+    let _permit = semaphore.acquire().await
+        .map_err(|e| ScraperError::ParsingError(format!("Preload semaphore closed: {}", e)))?;
+
+    let user = scraper.scrape_user_with_retry(username).await?;
+    cache.store_user(user);
+    Ok(PreloadEntry::Scraped)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyTestResponse {
+    pub proxy: String,
+    pub username: String,
+    pub duration_ms: u64,
+    #[serde(flatten)]
+    pub outcome: ProxyTestOutcome,
+}
+
+// Raw outcome of a scrape through a forced proxy
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase", tag = "status")]
+pub enum ProxyTestOutcome {
+    Succeeded { user: Box<InstagramUser> },
+    Failed { error: String, code: String },
+}
+
+/// Scrape a profile through exactly the given proxy (configured or not), bypassing rotation, to debug it.
+/// The profile is neither cached nor read from the cache, and the proxy state (health, protocol, client) is left untouched.
+#[get("/test-proxy?<url>&<username>")]
+pub async fn test_proxy(
+    url: &str,
+    username: &str,
+    admin: Result<AdminToken, ApiError>,
+    scraper: &State<InstagramScraper>,
+) -> Result<Json<ProxyTestResponse>, ApiError> {
+    admin?;
+
+    let started = std::time::Instant::now();
+    let result = scraper.scrape_user_through_proxy(username, url).await;
+    let outcome = match result {
+        Ok(user) => ProxyTestOutcome::Succeeded { user: Box::new(user) },
+        Err(err) => ProxyTestOutcome::Failed { error: err.to_string(), code: err.code().to_string() },
+    };
+
+    Ok(Json(ProxyTestResponse {
+        proxy: url.to_string(),
+        username: username.to_string(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        outcome,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::{Header, Status};
+    use rocket::local::blocking::Client;
+
+    fn admin_client() -> Client {
+        let config = AppConfig { admin_token: Some("secret".to_string()), ..AppConfig::default() };
+        let rocket = rocket::build()
+            .manage(StartedAt(std::time::Instant::now()))
+            .manage(InstagramScraper::new(config.clone(), ProxyManager::new(None, 4)))
+            .manage(InstagramCache::new(1))
+            .manage(ImageCache::new())
+            .manage(ProxyManager::new(Some(vec!["http://10.0.0.1:8080".to_string()]), 4))
+            .manage(config)
+            .mount("/admin", routes![get_stats, preload])
+            .mount("/instagram", routes![crate::api::instagram::get_user]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    fn user(username: &str) -> InstagramUser {
+        serde_json::from_value(serde_json::json!({
+            "username": username,
+            "fullName": null,
+            "biography": null,
+            "profilePicUrl": null,
+            "isPrivate": false,
+            "isVerified": false,
+            "externalUrl": null,
+            "stats": { "postsCount": 0, "followersCount": 1, "followingCount": 1 },
+            "posts": [],
+            "reels": [],
+            "scrapedAt": "2024-01-01T00:00:00Z",
+            "postsLimited": false
+        })).unwrap()
+    }
+
+    #[test]
+    fn stats_count_the_cached_users() {
+        let client = admin_client();
+        client.rocket().state::<InstagramCache>().unwrap().store_user(user("alice"));
+
+        let response = client.get("/admin/stats").header(Header::new("X-Admin-Token", "secret")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["cache"]["users"], 1);
+        assert_eq!(body["cache"]["images"], 0);
+        assert_eq!(body["proxies"]["total"], 1);
+        assert_eq!(body["scrapes"]["inFlight"], 0);
+
+        assert_eq!(client.get("/admin/stats").dispatch().status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn preload_reports_each_username() {
+        let client = admin_client();
+        client.rocket().state::<InstagramCache>().unwrap().store_user(user("alice"));
+
+        let preload = |usernames: &str| {
+            client
+                .post("/admin/preload")
+                .header(Header::new("X-Admin-Token", "secret"))
+                .header(rocket::http::ContentType::JSON)
+                .body(usernames)
+                .dispatch()
+        };
+
+        // Scraping without proxies fails fast offline
+        let body: serde_json::Value = preload(r#"["alice", "bob", "alice"]"#).into_json().unwrap();
+        assert_eq!(body["data"].as_object().unwrap().len(), 2);
+        assert_eq!(body["data"]["alice"]["status"], "cached");
+        assert_eq!(body["data"]["bob"]["status"], "failed");
+        assert!(body["data"]["bob"]["code"].is_string());
+
+        // Preloaded profiles are then read from the cache
+        let body: serde_json::Value = client.get("/instagram/alice").dispatch().into_json().unwrap();
+        assert_eq!(body["fromCache"], true);
+
+        let response = client.post("/admin/preload").header(rocket::http::ContentType::JSON).body("[]").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn test_proxy_scrapes_through_the_given_proxy_only() {
+        use crate::test_server::{response, TestServer};
+
+        let forced = TestServer::start(vec![response(502, &[], b"")]);
+        let configured = TestServer::start(vec![response(502, &[], b"")]);
+        let config = AppConfig { admin_token: Some("secret".to_string()), max_retries: 0, ..AppConfig::default() };
+        let proxy_manager = ProxyManager::new(Some(vec![configured.url.clone()]), 4);
+        let rocket = rocket::build()
+            .manage(InstagramScraper::new(config.clone(), proxy_manager.clone()))
+            .manage(proxy_manager.clone())
+            .manage(config)
+            .mount("/admin", routes![test_proxy]);
+        let client = Client::tracked(rocket).unwrap();
+
+        let uri = format!("/admin/test-proxy?url={}&username=jane", rocket::http::RawStr::new(&forced.url).percent_encode());
+        let response = client.get(uri.clone()).header(Header::new("X-Admin-Token", "secret")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["proxy"], forced.url.as_str());
+        assert_eq!(body["status"], "failed");
+
+        // Every endpoint went through the forced proxy, none through the configured one, whose health is untouched
+        let connects: Vec<String> = forced.requests().into_iter().map(|request| request.line).collect();
+        assert!(!connects.is_empty() && connects.iter().all(|line| line.starts_with("CONNECT ") && line.contains("instagram.com:443")));
+        assert!(configured.requests().is_empty());
+        assert_eq!(proxy_manager.snapshot()[0].consecutive_failures, 0);
+
+        assert_eq!(client.get(uri).dispatch().status(), Status::Unauthorized);
+    }
+}
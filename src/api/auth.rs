@@ -0,0 +1,203 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use subtle::ConstantTimeEq;
+
+use crate::api::rate_limit::RateLimiter;
+use crate::api::ApiError;
+use crate::config::AppConfig;
+
+// Header carrying the admin token
+const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+
+// Header and query parameter carrying the API key (the query parameter allows using proxied images in <img> tags)
+const API_KEY_HEADER: &str = "X-API-Key";
+const API_KEY_QUERY_PARAM: &str = "api_key";
+
+/// Request guard for admin routes: succeeds only when the `X-Admin-Token` header
+/// matches the configured `admin_token`. Admin routes are disabled when no token is configured.
+///
+/// Take it as `Result<AdminToken, ApiError>` in handlers so rejections are returned as JSON errors.
+pub struct AdminToken;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = request
+            .rocket()
+            .state::<AppConfig>()
+            .and_then(|config| config.admin_token.as_deref())
+            .filter(|token| !token.is_empty());
+
+        let expected = match expected {
+            Some(token) => token,
+            None => {
+                return Outcome::Error((
+                    Status::Unauthorized,
+                    ApiError::Unauthorized("Admin endpoints are disabled (no admin token configured)".to_string()),
+                ))
+            }
+        };
+
+        match request.headers().get_one(ADMIN_TOKEN_HEADER) {
+            Some(token) if secrets_match(token, expected) => Outcome::Success(AdminToken),
+            Some(_) => Outcome::Error((
+                Status::Unauthorized,
+                ApiError::Unauthorized("Invalid admin token".to_string()),
+            )),
+            None => Outcome::Error((
+                Status::Unauthorized,
+                ApiError::Unauthorized(format!("Missing {} header", ADMIN_TOKEN_HEADER)),
+            )),
+        }
+    }
+}
+
+// Compare a secret provided by a client in constant time, so that response timings don't reveal how much of it matches
+fn secrets_match(provided: &str, expected: &str) -> bool {
+    provided.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+// Whether a key is one of the configured ones, comparing it with every key so that timings don't reveal which one matched
+fn matches_any_key(provided: &str, keys: &[String]) -> bool {
+    keys.iter().fold(false, |found, key| secrets_match(provided, key) | found)
+}
+
+/// Request guard for API routes: when `api_keys` are configured, the request must provide one
+/// of them in the `X-API-Key` header (or `api_key` query parameter). Always succeeds when no key is configured.
+///
+/// Holds the key provided by the client, if any.
+pub struct ApiKey(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let keys = request
+            .rocket()
+            .state::<AppConfig>()
+            .and_then(|config| config.api_keys.as_ref())
+            .filter(|keys| !keys.is_empty());
+
+        // Authentication disabled
+        let keys = match keys {
+            Some(keys) => keys,
+            None => return Outcome::Success(ApiKey(None)),
+        };
+
+        let provided = request
+            .headers()
+            .get_one(API_KEY_HEADER)
+            .or_else(|| request.query_value::<&str>(API_KEY_QUERY_PARAM).and_then(|value| value.ok()));
+
+        match provided {
+            Some(key) if matches_any_key(key, keys) => Outcome::Success(ApiKey(Some(key.to_string()))),
+            Some(_) => Outcome::Error((
+                Status::Unauthorized,
+                ApiError::Unauthorized("Invalid API key".to_string()),
+            )),
+            None => Outcome::Error((
+                Status::Unauthorized,
+                ApiError::Unauthorized(format!("Missing {} header", API_KEY_HEADER)),
+            )),
+        }
+    }
+}
+
+/// Request guard for API routes: authenticates the client with `ApiKey` then applies the
+/// rate limit, keyed by API key when provided or by client IP otherwise.
+/// Take it as `Result<ApiClient, ApiError>` in handlers so rejections are returned as JSON errors.
+pub struct ApiClient;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiClient {
+    type Error = ApiError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let api_key = match request.guard::<ApiKey>().await {
+            Outcome::Success(api_key) => api_key,
+            Outcome::Error(error) => return Outcome::Error(error),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        let rate_limiter = match request.rocket().state::<RateLimiter>() {
+            Some(rate_limiter) => rate_limiter,
+            None => return Outcome::Success(ApiClient),
+        };
+
+        let client = match (api_key.0, request.client_ip()) {
+            (Some(key), _) => format!("key:{}", key),
+            (None, Some(ip)) => format!("ip:{}", ip),
+            (None, None) => "unknown".to_string(),
+        };
+
+        match rate_limiter.check(&client) {
+            Ok(()) => Outcome::Success(ApiClient),
+            Err(retry_after) => Outcome::Error((
+                Status::TooManyRequests,
+                ApiError::RateLimitExceeded(retry_after),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Header;
+    use rocket::local::blocking::Client;
+
+    #[rocket::get("/protected")]
+    fn protected(client: Result<ApiClient, ApiError>) -> Result<&'static str, ApiError> {
+        client?;
+        Ok("ok")
+    }
+
+    fn client(config: AppConfig) -> Client {
+        let rocket = rocket::build()
+            .manage(RateLimiter::new(config.rate_limit_per_minute))
+            .manage(config)
+            .mount("/", rocket::routes![protected]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    fn keys_config() -> AppConfig {
+        AppConfig { api_keys: Some(vec!["key-1".to_string(), "key-2".to_string()]), ..AppConfig::default() }
+    }
+
+    #[test]
+    fn allowed_api_key() {
+        let client = client(keys_config());
+        assert_eq!(client.get("/protected").header(Header::new(API_KEY_HEADER, "key-2")).dispatch().status(), Status::Ok);
+        assert_eq!(client.get("/protected?api_key=key-1").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn wrong_or_missing_api_key() {
+        let client = client(keys_config());
+        let response = client.get("/protected").header(Header::new(API_KEY_HEADER, "key-3")).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "UNAUTHORIZED");
+        assert_eq!(client.get("/protected").dispatch().status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn disabled_api_key_auth() {
+        let client = client(AppConfig::default());
+        assert_eq!(client.get("/protected").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn request_over_the_rate_limit_is_429() {
+        let client = client(AppConfig { rate_limit_per_minute: Some(2), ..AppConfig::default() });
+        assert_eq!(client.get("/protected").dispatch().status(), Status::Ok);
+        assert_eq!(client.get("/protected").dispatch().status(), Status::Ok);
+
+        let response = client.get("/protected").dispatch();
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("30"));
+    }
+}
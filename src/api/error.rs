@@ -7,6 +7,15 @@ use serde_json::json;
 pub enum ApiError {
     ScraperError(ScraperError),
     ImageError(ImageProxyError),
+    Unauthorized(String),
+    // Invalid request parameter
+    BadRequest(String),
+    // Feature requested while it isn't configured on the server
+    NotConfigured(String),
+    // Image URL signature missing, invalid or expired while signed image URLs are required
+    InvalidSignature(String),
+    // Client exceeded its request quota, with the number of seconds to wait before retrying
+    RateLimitExceeded(u64),
 }
 
 impl From<ScraperError> for ApiError {
@@ -21,17 +30,59 @@ impl From<ImageProxyError> for ApiError {
     }
 }
 
+impl ApiError {
+    /// Stable machine-readable code of the error, so that clients don't have to match messages
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::ScraperError(error) => error.code(),
+            ApiError::ImageError(ImageProxyError::NetworkError(_)) => "IMAGE_NETWORK_ERROR",
+            ApiError::ImageError(ImageProxyError::ProxyError(_)) => "IMAGE_PROXY_ERROR",
+            ApiError::ImageError(ImageProxyError::ImageError(_)) => "IMAGE_ERROR",
+            ApiError::ImageError(ImageProxyError::ConversionError(_)) => "IMAGE_CONVERSION_ERROR",
+            ApiError::Unauthorized(_) => "UNAUTHORIZED",
+            ApiError::BadRequest(_) => "INVALID_PARAMETER",
+            ApiError::NotConfigured(_) => "NOT_CONFIGURED",
+            ApiError::InvalidSignature(_) => "INVALID_SIGNATURE",
+            ApiError::RateLimitExceeded(_) => "QUOTA_EXCEEDED",
+        }
+    }
+}
+
 impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
     fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+        let code = self.code();
         match self {
-            ApiError::ScraperError(ScraperError::ProfileNotFound) => rocket::Response::build()
-                .status(Status::NotFound)
-                .sized_body(None, std::io::Cursor::new("Profile not found"))
-                .ok(),
+            ApiError::ScraperError(ScraperError::ProfileNotFound) => {
+                let body = json!({
+                    "error": "Profile not found",
+                    "message": "The requested profile does not exist",
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::NotFound)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::ScraperError(ScraperError::PostNotFound) => {
+                let body = json!({
+                    "error": "Post not found",
+                    "message": "The requested post does not exist or belongs to another user",
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::NotFound)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
             ApiError::ScraperError(ScraperError::PrivateProfile) => {
                 let body = json!({
                     "error": "Profile is private",
-                    "message": "The requested profile is private and cannot be accessed"
+                    "message": "The requested profile is private and cannot be accessed",
+                    "code": code
                 })
                 .to_string();
 
@@ -40,22 +91,46 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
                     .sized_body(None, std::io::Cursor::new(body))
                     .ok()
             }
-            ApiError::ScraperError(ScraperError::RateLimited) => {
+            ApiError::ScraperError(ScraperError::ChallengeRequired) => {
                 let body = json!({
-                    "error": "Rate limited",
-                    "message": "Too many requests, please try again later"
+                    "error": "Challenge required",
+                    "message": "Instagram flagged the account and requires a checkpoint, complete it in a browser or replace the cookies",
+                    "code": code
                 })
                 .to_string();
 
                 rocket::Response::build()
-                    .status(Status::TooManyRequests)
+                    .status(Status::Forbidden)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::ScraperError(ScraperError::RateLimited { retry_after }) => {
+                let message = match retry_after {
+                    Some(retry_after) => format!("Too many requests, please retry in {} seconds", retry_after),
+                    None => "Too many requests, please try again later".to_string(),
+                };
+                let body = json!({
+                    "error": "Rate limited",
+                    "message": message,
+                    "code": code
+                })
+                .to_string();
+
+                let mut response = rocket::Response::build();
+                response.status(Status::TooManyRequests);
+                // Pass on the delay Instagram asked for
+                if let Some(retry_after) = retry_after {
+                    response.raw_header("Retry-After", retry_after.to_string());
+                }
+                response
                     .sized_body(None, std::io::Cursor::new(body))
                     .ok()
             }
             ApiError::ScraperError(ScraperError::UnauthorizedAccess(message)) => {
                 let body = json!({
                     "error": "Unauthorized",
-                    "message": message
+                    "message": message,
+                    "code": code
                 })
                 .to_string();
 
@@ -67,7 +142,8 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
             ApiError::ScraperError(ScraperError::ProxyError(error)) => {
                 let body = json!({
                     "error": "Proxy error",
-                    "message": error
+                    "message": error,
+                    "code": code
                 })
                 .to_string();
 
@@ -79,7 +155,8 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
             ApiError::ScraperError(ScraperError::AllProxiesFailed) => {
                 let body = json!({
                     "error": "All proxies failed",
-                    "message": "All configured proxies failed to connect"
+                    "message": "All configured proxies failed to connect",
+                    "code": code
                 })
                 .to_string();
 
@@ -91,7 +168,8 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
             ApiError::ScraperError(ScraperError::NetworkError(error)) => {
                 let body = json!({
                     "error": "Network error",
-                    "message": error.to_string()
+                    "message": error.to_string(),
+                    "code": code
                 })
                 .to_string();
 
@@ -100,17 +178,50 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
                     .sized_body(None, std::io::Cursor::new(body))
                     .ok()
             }
-            ApiError::ScraperError(ScraperError::ParsingError(e)) => rocket::Response::build()
-                .status(Status::InternalServerError)
-                .sized_body(
-                    None,
-                    std::io::Cursor::new(format!("Error parsing Instagram page: {}", e)),
-                )
-                .ok(),
+            ApiError::ScraperError(ScraperError::Timeout(deadline)) => {
+                let body = json!({
+                    "error": "Timeout",
+                    "message": format!("Scraping took longer than {} seconds", deadline),
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::GatewayTimeout)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::ScraperError(ScraperError::Busy(timeout)) => {
+                let body = json!({
+                    "error": "Scraper busy",
+                    "message": format!("Too many concurrent scrapes, none finished within {} seconds", timeout),
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::ServiceUnavailable)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::ScraperError(ScraperError::ParsingError(e)) => {
+                let body = json!({
+                    "error": "Parsing error",
+                    "message": format!("Error parsing Instagram page: {}", e),
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::InternalServerError)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
             ApiError::ImageError(ImageProxyError::NetworkError(error)) => {
                 let body = json!({
                     "error": "Image network error",
-                    "message": error.to_string()
+                    "message": error.to_string(),
+                    "code": code
                 })
                 .to_string();
 
@@ -122,7 +233,8 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
             ApiError::ImageError(ImageProxyError::ProxyError(error)) => {
                 let body = json!({
                     "error": "Image proxy error",
-                    "message": error
+                    "message": error,
+                    "code": code
                 })
                 .to_string();
 
@@ -134,7 +246,8 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
             ApiError::ImageError(ImageProxyError::ImageError(error)) => {
                 let body = json!({
                     "error": "Image processing error",
-                    "message": error
+                    "message": error,
+                    "code": code
                 })
                 .to_string();
 
@@ -146,7 +259,8 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
             ApiError::ImageError(ImageProxyError::ConversionError(error)) => {
                 let body = json!({
                     "error": "Image conversion error",
-                    "message": error
+                    "message": error,
+                    "code": code
                 })
                 .to_string();
 
@@ -155,7 +269,140 @@ impl<'r> rocket::response::Responder<'r, 'static> for ApiError {
                     .sized_body(None, std::io::Cursor::new(body))
                     .ok()
             }
-           
+            ApiError::Unauthorized(message) => {
+                let body = json!({
+                    "error": "Unauthorized",
+                    "message": message,
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::Unauthorized)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::BadRequest(message) => {
+                let body = json!({
+                    "error": "Bad request",
+                    "message": message,
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::BadRequest)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::NotConfigured(message) => {
+                let body = json!({
+                    "error": "Not implemented",
+                    "message": message,
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::NotImplemented)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::InvalidSignature(message) => {
+                let body = json!({
+                    "error": "Invalid signature",
+                    "message": message,
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::Forbidden)
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+            ApiError::RateLimitExceeded(retry_after) => {
+                let body = json!({
+                    "error": "Rate limited",
+                    "message": format!("Too many requests, please retry in {} seconds", retry_after),
+                    "code": code
+                })
+                .to_string();
+
+                rocket::Response::build()
+                    .status(Status::TooManyRequests)
+                    .raw_header("Retry-After", retry_after.to_string())
+                    .sized_body(None, std::io::Cursor::new(body))
+                    .ok()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::asynchronous::Client;
+    use rocket::response::Responder;
+
+    // Error of a request to a port nothing listens on
+    async fn network_error() -> reqwest::Error {
+        reqwest::get("http://127.0.0.1:1/").await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn every_variant_has_its_code() {
+        let cases = vec![
+            (ApiError::ScraperError(ScraperError::NetworkError(network_error().await)), "NETWORK_ERROR", Status::ServiceUnavailable),
+            (ApiError::ScraperError(ScraperError::ParsingError("bad".into())), "PARSING_ERROR", Status::InternalServerError),
+            (ApiError::ScraperError(ScraperError::RateLimited { retry_after: Some(30) }), "RATE_LIMITED", Status::TooManyRequests),
+            (ApiError::ScraperError(ScraperError::ProfileNotFound), "PROFILE_NOT_FOUND", Status::NotFound),
+            (ApiError::ScraperError(ScraperError::PostNotFound), "POST_NOT_FOUND", Status::NotFound),
+            (ApiError::ScraperError(ScraperError::PrivateProfile), "PRIVATE_PROFILE", Status::Forbidden),
+            (ApiError::ScraperError(ScraperError::ProxyError("refused".into())), "PROXY_ERROR", Status::BadGateway),
+            (ApiError::ScraperError(ScraperError::AllProxiesFailed), "ALL_PROXIES_FAILED", Status::ServiceUnavailable),
+            (ApiError::ScraperError(ScraperError::UnauthorizedAccess("no".into())), "UNAUTHORIZED_ACCESS", Status::Unauthorized),
+            (ApiError::ScraperError(ScraperError::ChallengeRequired), "CHALLENGE_REQUIRED", Status::Forbidden),
+            (ApiError::ScraperError(ScraperError::Timeout(30)), "SCRAPE_TIMEOUT", Status::GatewayTimeout),
+            (ApiError::ScraperError(ScraperError::Busy(10)), "SCRAPER_BUSY", Status::ServiceUnavailable),
+            (ApiError::ImageError(ImageProxyError::NetworkError(network_error().await)), "IMAGE_NETWORK_ERROR", Status::ServiceUnavailable),
+            (ApiError::ImageError(ImageProxyError::ProxyError("refused".into())), "IMAGE_PROXY_ERROR", Status::BadGateway),
+            (ApiError::ImageError(ImageProxyError::ImageError("bad".into())), "IMAGE_ERROR", Status::InternalServerError),
+            (ApiError::ImageError(ImageProxyError::ConversionError("bad".into())), "IMAGE_CONVERSION_ERROR", Status::BadRequest),
+            (ApiError::Unauthorized("no".into()), "UNAUTHORIZED", Status::Unauthorized),
+            (ApiError::BadRequest("bad".into()), "INVALID_PARAMETER", Status::BadRequest),
+            (ApiError::NotConfigured("off".into()), "NOT_CONFIGURED", Status::NotImplemented),
+            (ApiError::InvalidSignature("bad".into()), "INVALID_SIGNATURE", Status::Forbidden),
+            (ApiError::RateLimitExceeded(30), "QUOTA_EXCEEDED", Status::TooManyRequests),
+        ];
+
+        let client = Client::tracked(rocket::build()).await.unwrap();
+        let request = client.get("/");
+        for (error, code, status) in cases {
+            assert_eq!(error.code(), code);
+
+            let mut response = error.respond_to(request.inner()).unwrap();
+            assert_eq!(response.status(), status, "{}", code);
+            let body: serde_json::Value = serde_json::from_str(&response.body_mut().to_string().await.unwrap()).unwrap();
+            assert_eq!(body["code"], code);
+            // The existing fields are kept
+            assert!(body["error"].is_string() && body["message"].is_string(), "{}", code);
         }
     }
+
+    #[tokio::test]
+    async fn rate_limit_delay_is_sent_as_retry_after() {
+        let client = Client::tracked(rocket::build()).await.unwrap();
+        let request = client.get("/");
+
+        let error = ApiError::ScraperError(ScraperError::RateLimited { retry_after: Some(30) });
+        let mut response = error.respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Retry-After"), Some("30"));
+        let body = response.body_mut().to_string().await.unwrap();
+        assert!(body.contains("retry in 30 seconds"));
+
+        let error = ApiError::ScraperError(ScraperError::RateLimited { retry_after: None });
+        let response = error.respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Retry-After"), None);
+    }
 }
@@ -1,39 +1,67 @@
 use rocket::State;
 use rocket::http::ContentType;
-use rocket::{request::Request, response::{self, Response, Responder}};
+use rocket::serde::json::Json;
+use rocket::{request::{self, FromRequest, Request}, response::{self, stream::ByteStream, Response, Responder}};
 use std::io::Cursor;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
 use md5;
 use rocket::http::Header;
+use rocket::http::uri::Origin;
+use rocket::Either;
 use serde;
+use tokio::sync::Semaphore;
+use chrono::{DateTime, Utc};
 
-use crate::models::instagram::{InstagramUserResponse, InstagramPostsResponse, InstagramReelsResponse};
+use crate::models::instagram::{
+    InstagramPost, InstagramUser, InstagramUserResponse, InstagramPostsResponse, InstagramReelsResponse,
+    InstagramPostResponse, InstagramHighlightsResponse,
+    InstagramBatchEntry, InstagramBatchResponse, InstagramCachePurgeResponse, InstagramImageVariantsResponse, InstagramSignedImageResponse,
+    InstagramImageDataUrlResponse,
+};
 use crate::scrapers::instagram::{InstagramScraper, ScraperError};
-use crate::cache::{InstagramCache, ImageCache};
+use crate::cache::{InstagramCache, ImageCache, image_etag};
 use crate::config::AppConfig;
-use crate::images::{ImageProxy, ImageConversionParams};
+use crate::images::{ImageProxy, ImageProxyError, ImageConversionParams, ImageConversionFormat, DecodedImage, ImageSigner, VideoStream};
 use crate::api::ApiError;
+use crate::api::auth::{AdminToken, ApiClient};
 
-#[get("/<username>")]
+#[allow(clippy::too_many_arguments)]
+#[get("/<username>?<fresh>&<media>")]
 pub async fn get_user(
     username: &str,
+    fresh: Option<bool>,
+    media: Option<bool>,
+    client: Result<ApiClient, ApiError>,
     scraper: &State<InstagramScraper>,
     cache: &State<InstagramCache>,
     config: &State<AppConfig>,
 ) -> Result<JsonWithCache<InstagramUserResponse>, ApiError> {
-    // Whitelist check
-    if let Some(whitelist) = &config.instagram_username_whitelist {
-        if !whitelist.contains(&username.to_string()) {
-            return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
-        }
+    client?;
+
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
     }
     
-    // Check cache first (non-expired data)
-    if let Some((user, age)) = cache.get_user(username) {
+    // Drop the posts and reels when only the profile and its counts are wanted
+    let without_media = |mut user: InstagramUser| {
+        if !media.unwrap_or(true) {
+            user.posts = None;
+            user.reels = None;
+        }
+        user
+    };
+    
+    // Check cache first (non-expired data), unless a fresh scrape is requested
+    if let Some((user, age)) = cache.get_user(username).filter(|_| !fresh.unwrap_or(false)) {
         return Ok(JsonWithCache {
             inner: InstagramUserResponse {
-                data: user,
+                data: without_media(user),
                 from_cache: true,
                 cache_age: Some(age),
+                scrape_duration_ms: None,
             },
             from_cache: true,
             cache_age: Some(age),
@@ -42,6 +70,7 @@ pub async fn get_user(
     }
     
     // Try to scrape fresh data with retry logic
+    let scrape_started = std::time::Instant::now();
     match scraper.scrape_user_with_retry(username).await {
         Ok(user) => {
             // Successfully retrieved fresh data, store in cache
@@ -49,9 +78,10 @@ pub async fn get_user(
             
             Ok(JsonWithCache {
                 inner: InstagramUserResponse {
-                    data: user,
+                    data: without_media(user),
                     from_cache: false,
                     cache_age: None,
+                    scrape_duration_ms: Some(scrape_started.elapsed().as_millis() as u64),
                 },
                 from_cache: false,
                 cache_age: None,
@@ -66,9 +96,10 @@ pub async fn get_user(
                 
                 Ok(JsonWithCache {
                     inner: InstagramUserResponse {
-                        data: user,
+                        data: without_media(user),
                         from_cache: true,
                         cache_age: Some(age),
+                        scrape_duration_ms: None,
                     },
                     from_cache: true,
                     cache_age: Some(age),
@@ -82,31 +113,230 @@ pub async fn get_user(
     }
 }
 
-#[get("/<username>/posts")]
+// Number of profiles scraped in parallel by a batch (or preload) request when not configured
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 4;
+
+#[post("/batch", format = "json", data = "<usernames>")]
+pub async fn get_users_batch(
+    usernames: Json<Vec<String>>,
+    client: Result<ApiClient, ApiError>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+    config: &State<AppConfig>,
+) -> Result<Json<InstagramBatchResponse>, ApiError> {
+    client?;
+
+    // De-duplicate usernames, preserving request order
+    let mut unique_usernames: Vec<String> = Vec::new();
+    for username in usernames.into_inner() {
+        if !unique_usernames.contains(&username) {
+            unique_usernames.push(username);
+        }
+    }
+    if unique_usernames.len() > MAX_BATCH_USERNAMES {
+        return Err(ApiError::BadRequest(format!("At most {} usernames can be requested at once", MAX_BATCH_USERNAMES)));
+    }
+    
+    // Bound the number of concurrent scrapes so a batch doesn't hammer the proxies
+    let semaphore = Semaphore::new(config.batch_concurrency.unwrap_or(DEFAULT_BATCH_CONCURRENCY).max(1));
+    
+    let entries = futures::future::join_all(unique_usernames.into_iter().map(|username| {
+        let semaphore = &semaphore;
+        async move {
+            let entry = match get_batch_user(&username, scraper, cache, config, semaphore).await {
+                Ok(response) => InstagramBatchEntry::Success(Box::new(response)),
+                Err(err) => InstagramBatchEntry::Error { error: err.to_string(), code: err.code().to_string() },
+            };
+            (username, entry)
+        }
+    })).await;
+    
+    Ok(Json(InstagramBatchResponse {
+        data: entries.into_iter().collect(),
+    }))
+}
+
+// Resolve a single batch entry: whitelist check, cache, then scrape with expired cache fallback
+async fn get_batch_user(
+    username: &str,
+    scraper: &InstagramScraper,
+    cache: &InstagramCache,
+    config: &AppConfig,
+    semaphore: &Semaphore,
+) -> Result<InstagramUserResponse, ScraperError> {
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username)));
+    }
+    
+    // Check cache first (non-expired data), without waiting for a scrape slot
+    if let Some((user, age)) = cache.get_user(username) {
+        return Ok(InstagramUserResponse {
+            data: user,
+            from_cache: true,
+            cache_age: Some(age),
+            scrape_duration_ms: None,
+        });
+    }
+    
+    let _permit = semaphore.acquire().await
+        .map_err(|e| ScraperError::ParsingError(format!("Batch semaphore closed: {}", e)))?;
+    
+    let scrape_started = std::time::Instant::now();
+    match scraper.scrape_user_with_retry(username).await {
+        Ok(user) => {
+            cache.store_user(user.clone());
+            Ok(InstagramUserResponse {
+                data: user,
+                from_cache: false,
+                cache_age: None,
+                scrape_duration_ms: Some(scrape_started.elapsed().as_millis() as u64),
+            })
+        },
+        Err(err) => {
+            if let Some((user, age)) = cache.get_user_even_expired(username) {
+                log::warn!("Using expired cache for {} in batch as fallback due to scraping error: {:?}", username, err);
+                Ok(InstagramUserResponse {
+                    data: user,
+                    from_cache: true,
+                    cache_age: Some(age),
+                    scrape_duration_ms: None,
+                })
+            } else {
+                Err(err)
+            }
+        }
+    }
+}
+
+#[derive(FromForm)]
+pub struct PostsQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+    pub exclude_undated: Option<bool>,
+    pub order: Option<String>,
+    pub after_shortcode: Option<String>,
+    pub fresh: Option<bool>,
+}
+
+// Parsed posts query, applied to the posts list whatever its source (fresh or cached)
+pub struct PostsFilter {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+    exclude_undated: bool,
+    ascending: bool,
+    after_shortcode: Option<String>,
+}
+
+impl PostsQuery {
+    fn to_posts_filter(&self) -> Result<PostsFilter, ApiError> {
+        let parse_date = |name: &str, value: &Option<String>| -> Result<Option<DateTime<Utc>>, ApiError> {
+            match value {
+                Some(value) => DateTime::parse_from_rfc3339(value)
+                    .map(|date| Some(date.with_timezone(&Utc)))
+                    .map_err(|e| ApiError::BadRequest(
+                        format!("Invalid {} date '{}' (RFC3339 expected): {}", name, value, e)
+                    )),
+                None => Ok(None),
+            }
+        };
+        
+        let ascending = match self.order.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("desc") => false,
+            Some("asc") => true,
+            Some(order) => return Err(ApiError::BadRequest(
+                format!("Invalid order '{}' (asc or desc expected)", order)
+            )),
+        };
+        
+        Ok(PostsFilter {
+            since: parse_date("since", &self.since)?,
+            until: parse_date("until", &self.until)?,
+            limit: self.limit,
+            exclude_undated: self.exclude_undated.unwrap_or(false),
+            ascending,
+            after_shortcode: self.after_shortcode.clone(),
+        })
+    }
+}
+
+impl PostsFilter {
+    // Keep the posts newer than after_shortcode, sort them by timestamp (undated posts last),
+    // then filter them by date range and count.
+    // Also returns whether after_shortcode wasn't found (a gap), in which case all the posts are kept.
+    fn apply(&self, mut posts: Vec<InstagramPost>) -> (Vec<InstagramPost>, bool) {
+        let mut gap = false;
+        if let Some(shortcode) = &self.after_shortcode {
+            match posts.iter().position(|post| &post.shortcode == shortcode) {
+                // Newer by timestamp when known, by position in the scraped list (newest first) otherwise
+                Some(index) => match posts[index].timestamp {
+                    Some(after) => posts.retain(|post| post.timestamp.is_some_and(|timestamp| timestamp > after)),
+                    None => posts.truncate(index),
+                },
+                None => gap = true,
+            }
+        }
+        
+        posts.sort_by(|a, b| match (a.timestamp, b.timestamp) {
+            (Some(a), Some(b)) if self.ascending => a.cmp(&b),
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        
+        let filtered = posts.into_iter().filter(|post| match post.timestamp {
+            Some(timestamp) => {
+                self.since.is_none_or(|since| timestamp >= since)
+                    && self.until.is_none_or(|until| timestamp <= until)
+            },
+            None => !self.exclude_undated,
+        });
+        
+        let posts = match self.limit {
+            Some(limit) => filtered.take(limit).collect(),
+            None => filtered.collect(),
+        };
+        (posts, gap)
+    }
+}
+
+#[get("/<username>/posts?<query..>")]
 pub async fn get_posts(
     username: &str,
+    client: Result<ApiClient, ApiError>,
+    query: PostsQuery,
     scraper: &State<InstagramScraper>,
     cache: &State<InstagramCache>,
     config: &State<AppConfig>,
 ) -> Result<JsonWithCache<InstagramPostsResponse>, ApiError> {
-    // Whitelist check
-    if let Some(whitelist) = &config.instagram_username_whitelist {
-        if !whitelist.contains(&username.to_string()) {
-            return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
-        }
+    client?;
+
+    let filter = query.to_posts_filter()?;
+    
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
     }
     
-    // Check cache first (non-expired data)
-    if let Some((posts, age)) = cache.get_posts(username) {
+    // Check cache first (non-expired data), unless a fresh scrape is requested
+    if let Some((posts, age)) = cache.get_posts(username).filter(|_| !query.fresh.unwrap_or(false)) {
+        let (limited, total_count) = cached_posts_completeness(cache, username);
+        let (posts, gap) = filter.apply(posts);
         return Ok(JsonWithCache {
             inner: InstagramPostsResponse {
                 data: posts,
                 from_cache: true,
                 cache_age: Some(age),
+                limited,
+                total_count,
+                gap,
             },
             from_cache: true,
             cache_age: Some(age),
-            cache_duration: cache.cache_duration.as_secs(),
+            cache_duration: cache.posts_cache_duration.as_secs(),
         });
     }
     
@@ -117,17 +347,20 @@ pub async fn get_posts(
             cache.store_user(user.clone());
             
             // Return posts
-            let posts = user.posts.unwrap_or_default();
+            let (posts, gap) = filter.apply(user.posts.unwrap_or_default());
             
             Ok(JsonWithCache {
                 inner: InstagramPostsResponse {
                     data: posts,
                     from_cache: false,
                     cache_age: None,
+                    limited: user.posts_limited,
+                    total_count: user.stats.posts_count,
+                    gap,
                 },
                 from_cache: false,
                 cache_age: None,
-                cache_duration: cache.cache_duration.as_secs(),
+                cache_duration: cache.posts_cache_duration.as_secs(),
             })
         },
         Err(err) => {
@@ -136,15 +369,20 @@ pub async fn get_posts(
                 // Log that we're using expired cache as fallback
                 log::warn!("Using expired cache for {}/posts as fallback due to scraping error: {:?}", username, err);
                 
+                let (limited, total_count) = cached_posts_completeness(cache, username);
+                let (posts, gap) = filter.apply(posts);
                 Ok(JsonWithCache {
                     inner: InstagramPostsResponse {
                         data: posts,
                         from_cache: true,
                         cache_age: Some(age),
+                        limited,
+                        total_count,
+                        gap,
                     },
                     from_cache: true,
                     cache_age: Some(age),
-                    cache_duration: cache.cache_duration.as_secs(),
+                    cache_duration: cache.posts_cache_duration.as_secs(),
                 })
             } else {
                 // No cache data available, return the error
@@ -154,27 +392,154 @@ pub async fn get_posts(
     }
 }
 
-#[get("/<username>/reels")]
+// Whether the posts of a cached user are incomplete, with their total count (unknown if the user isn't cached)
+fn cached_posts_completeness(cache: &InstagramCache, username: &str) -> (bool, Option<u64>) {
+    cache.get_user_even_expired(username)
+        .map(|(user, _)| (user.posts_limited, user.stats.posts_count))
+        .unwrap_or((false, None))
+}
+
+#[get("/<username>/posts/<shortcode>")]
+pub async fn get_post(
+    username: &str,
+    shortcode: &str,
+    client: Result<ApiClient, ApiError>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+    config: &State<AppConfig>,
+) -> Result<JsonWithCache<InstagramPostResponse>, ApiError> {
+    client?;
+
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    }
+    
+    // Look for the post in the cached timeline first
+    if let Some((posts, age)) = cache.get_posts(username) {
+        if let Some(post) = posts.into_iter().find(|post| post.shortcode == shortcode) {
+            return Ok(JsonWithCache {
+                inner: InstagramPostResponse {
+                    data: post,
+                    from_cache: true,
+                    cache_age: Some(age),
+                },
+                from_cache: true,
+                cache_age: Some(age),
+                cache_duration: cache.posts_cache_duration.as_secs(),
+            });
+        }
+    }
+    
+    // Older posts aren't in the timeline, scrape the post alone
+    let post = scraper.scrape_post(username, shortcode).await?;
+    
+    Ok(JsonWithCache {
+        inner: InstagramPostResponse {
+            data: post,
+            from_cache: false,
+            cache_age: None,
+        },
+        from_cache: false,
+        cache_age: None,
+        cache_duration: cache.posts_cache_duration.as_secs(),
+    })
+}
+
+// Largest page of reels a request can ask for, larger limits are capped
+const MAX_REELS_PAGE_SIZE: u64 = 50;
+
+#[allow(clippy::too_many_arguments)]
+#[get("/<username>/reels?<fresh>&<limit>&<cursor>")]
 pub async fn get_reels(
     username: &str,
+    fresh: Option<bool>,
+    limit: Option<u64>,
+    cursor: Option<String>,
+    client: Result<ApiClient, ApiError>,
     scraper: &State<InstagramScraper>,
     cache: &State<InstagramCache>,
     config: &State<AppConfig>,
 ) -> Result<JsonWithCache<InstagramReelsResponse>, ApiError> {
-    // Whitelist check
-    if let Some(whitelist) = &config.instagram_username_whitelist {
-        if !whitelist.contains(&username.to_string()) {
-            return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    client?;
+
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    }
+    
+    let fresh = fresh.unwrap_or(false);
+    
+    // Paged requests go straight to the reels tab, uncached: the reels derived from the posts can't be paged
+    if limit.is_some() || cursor.is_some() {
+        let page_size = match limit {
+            Some(0) => return Err(ApiError::BadRequest("limit must be greater than 0".to_string())),
+            Some(limit) => limit.min(MAX_REELS_PAGE_SIZE),
+            None => config.instagram_page_size,
+        };
+        let page = scraper.scrape_reels(username, cursor.as_deref(), page_size).await?;
+        
+        return Ok(JsonWithCache {
+            inner: InstagramReelsResponse {
+                data: page.reels,
+                from_cache: false,
+                cache_age: None,
+                limited: page.end_cursor.is_some(),
+                end_cursor: page.end_cursor,
+            },
+            from_cache: false,
+            cache_age: None,
+            cache_duration: cache.cache_duration.as_secs(),
+        });
+    }
+    
+    // First page of the reels tab first
+    if let Some((page, age)) = cache.get_clips(username).filter(|_| !fresh) {
+        return Ok(JsonWithCache {
+            inner: InstagramReelsResponse {
+                data: page.reels,
+                from_cache: true,
+                cache_age: Some(age),
+                limited: page.end_cursor.is_some(),
+                end_cursor: page.end_cursor,
+            },
+            from_cache: true,
+            cache_age: Some(age),
+            cache_duration: cache.cache_duration.as_secs(),
+        });
+    }
+    
+    match scraper.scrape_reels(username, None, config.instagram_page_size).await {
+        Ok(page) => {
+            cache.store_clips(username, page.clone());
+            
+            return Ok(JsonWithCache {
+                inner: InstagramReelsResponse {
+                    data: page.reels,
+                    from_cache: false,
+                    cache_age: None,
+                    limited: page.end_cursor.is_some(),
+                    end_cursor: page.end_cursor,
+                },
+                from_cache: false,
+                cache_age: None,
+                cache_duration: cache.cache_duration.as_secs(),
+            });
+        },
+        Err(err) => {
+            log::warn!("Failed to scrape the reels tab of {}, falling back to reels derived from posts: {:?}", username, err);
         }
     }
     
-    // Check cache first (non-expired data)
-    if let Some((reels, age)) = cache.get_reels(username) {
+    // Then the reels derived from the cached posts, before scraping the profile
+    if let Some((reels, age)) = cache.get_reels(username).filter(|_| !fresh) {
         return Ok(JsonWithCache {
             inner: InstagramReelsResponse {
                 data: reels,
                 from_cache: true,
                 cache_age: Some(age),
+                limited: cached_posts_completeness(cache, username).0,
+                end_cursor: None,
             },
             from_cache: true,
             cache_age: Some(age),
@@ -196,6 +561,8 @@ pub async fn get_reels(
                     data: reels,
                     from_cache: false,
                     cache_age: None,
+                    limited: user.posts_limited,
+                    end_cursor: None,
                 },
                 from_cache: false,
                 cache_age: None,
@@ -203,8 +570,12 @@ pub async fn get_reels(
             })
         },
         Err(err) => {
-            // Scraping failed, try to use expired cache data as fallback
-            if let Some((reels, age)) = cache.get_reels_even_expired(username) {
+            // Scraping failed, try to use expired cache data as fallback (reels tab first)
+            let expired = cache.get_clips_even_expired(username)
+                .map(|(page, age)| (page.end_cursor.is_some(), page.reels, page.end_cursor, age))
+                .or_else(|| cache.get_reels_even_expired(username)
+                    .map(|(reels, age)| (cached_posts_completeness(cache, username).0, reels, None, age)));
+            if let Some((limited, reels, end_cursor, age)) = expired {
                 // Log that we're using expired cache as fallback
                 log::warn!("Using expired cache for {}/reels as fallback due to scraping error: {:?}", username, err);
                 
@@ -213,6 +584,8 @@ pub async fn get_reels(
                         data: reels,
                         from_cache: true,
                         cache_age: Some(age),
+                        limited,
+                        end_cursor,
                     },
                     from_cache: true,
                     cache_age: Some(age),
@@ -226,70 +599,360 @@ pub async fn get_reels(
     }
 }
 
-// Responder for image data
-pub struct ImageResponse {
-    pub data: Vec<u8>,
-    pub content_type: String,
-}
+#[get("/<username>/highlights")]
+pub async fn get_highlights(
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+    config: &State<AppConfig>,
+) -> Result<JsonWithCache<InstagramHighlightsResponse>, ApiError> {
+    client?;
 
-impl<'r> Responder<'r, 'static> for ImageResponse {
-    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
-        // Convert content type string to ContentType
-        let content_type = match self.content_type.as_str() {
-            "image/jpeg" => ContentType::JPEG,
-            "image/png" => ContentType::PNG,
-            "image/gif" => ContentType::GIF,
-            "image/webp" => ContentType::new("image", "webp"),
-            "image/bmp" => ContentType::new("image", "bmp"),
-            "image/tiff" => ContentType::new("image", "tiff"),
-            "image/x-icon" => ContentType::new("image", "x-icon"),
-            _ => ContentType::JPEG, // Default if unknown
-        };
-        let etag = format!("\"{:x}\"", md5::compute(&self.data));
-        // Check If-None-Match header
-        if let Some(if_none_match) = req.headers().get_one("If-None-Match") {
-            if if_none_match == etag {
-                // ETag matches, return 304 Not Modified
-                return Response::build()
-                    .status(rocket::http::Status::NotModified)
-                    .header(Header::new("ETag", etag))
-                    .header(Header::new("Cache-Control", "public, max-age=86400"))
-                    .ok();
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    }
+    
+    // Check cache first (non-expired data)
+    if let Some((highlights, age)) = cache.get_highlights(username) {
+        return Ok(JsonWithCache {
+            inner: InstagramHighlightsResponse {
+                data: highlights,
+                from_cache: true,
+                cache_age: Some(age),
+            },
+            from_cache: true,
+            cache_age: Some(age),
+            cache_duration: cache.cache_duration.as_secs(),
+        });
+    }
+    
+    match scraper.scrape_highlights(username).await {
+        Ok(highlights) => {
+            cache.store_highlights(username, highlights.clone());
+            
+            Ok(JsonWithCache {
+                inner: InstagramHighlightsResponse {
+                    data: highlights,
+                    from_cache: false,
+                    cache_age: None,
+                },
+                from_cache: false,
+                cache_age: None,
+                cache_duration: cache.cache_duration.as_secs(),
+            })
+        },
+        Err(err) => {
+            // Scraping failed, try to use expired cache data as fallback
+            if let Some((highlights, age)) = cache.get_highlights_even_expired(username) {
+                log::warn!("Using expired cache for {}/highlights as fallback due to scraping error: {:?}", username, err);
+                
+                Ok(JsonWithCache {
+                    inner: InstagramHighlightsResponse {
+                        data: highlights,
+                        from_cache: true,
+                        cache_age: Some(age),
+                    },
+                    from_cache: true,
+                    cache_age: Some(age),
+                    cache_duration: cache.cache_duration.as_secs(),
+                })
+            } else {
+                Err(err.into())
             }
         }
-        Response::build()
-            .header(content_type)
-            .header(Header::new("Cache-Control", "public, max-age=86400"))
-            .header(Header::new("ETag", etag))
-            .sized_body(None, Cursor::new(self.data))
-            .ok()
     }
 }
 
-#[derive(FromForm)]
-pub struct ImageProxyQuery {
-    pub url: String,
-    pub width: Option<u32>,
-    pub height: Option<u32>,
-    pub format: Option<String>,
-    pub quality: Option<u8>,
-    pub fit: Option<String>,
-    pub focus: Option<String>,
-}
+#[get("/<username>/tagged")]
+pub async fn get_tagged(
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+    config: &State<AppConfig>,
+) -> Result<JsonWithCache<InstagramPostsResponse>, ApiError> {
+    client?;
 
-impl ImageProxyQuery {
-    fn to_conversion_params(&self) -> Result<ImageConversionParams, ApiError> {
-        let format = if let Some(ref fmt) = self.format {
-            Some(match fmt.as_str() {
-                "webp" => crate::images::ImageConversionFormat::Webp,
-                "jpg" | "jpeg" => crate::images::ImageConversionFormat::Jpg,
-                "png" => crate::images::ImageConversionFormat::Png,
-                "gif" => crate::images::ImageConversionFormat::Gif,
-                _ => return Err(ApiError::ScraperError(crate::scrapers::instagram::ScraperError::ParsingError(
-                    format!("Unsupported format: {}", fmt)
-                ))),
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    }
+    
+    // Check cache first (non-expired data)
+    if let Some((posts, age)) = cache.get_tagged(username) {
+        // Only the first page of the tagged feed is fetched, more posts likely exist when it is full
+        let limited = posts.len() as u64 >= config.instagram_page_size;
+        return Ok(JsonWithCache {
+            inner: InstagramPostsResponse {
+                data: posts,
+                from_cache: true,
+                cache_age: Some(age),
+                limited,
+                total_count: None,
+                gap: false,
+            },
+            from_cache: true,
+            cache_age: Some(age),
+            cache_duration: cache.cache_duration.as_secs(),
+        });
+    }
+    
+    match scraper.scrape_tagged(username).await {
+        Ok(posts) => {
+            cache.store_tagged(username, posts.clone());
+            
+            let limited = posts.len() as u64 >= config.instagram_page_size;
+            Ok(JsonWithCache {
+                inner: InstagramPostsResponse {
+                    data: posts,
+                    from_cache: false,
+                    cache_age: None,
+                    limited,
+                    total_count: None,
+                    gap: false,
+                },
+                from_cache: false,
+                cache_age: None,
+                cache_duration: cache.cache_duration.as_secs(),
             })
-        } else {
+        },
+        Err(err) => {
+            // Scraping failed, try to use expired cache data as fallback
+            if let Some((posts, age)) = cache.get_tagged_even_expired(username) {
+                log::warn!("Using expired cache for {}/tagged as fallback due to scraping error: {:?}", username, err);
+                
+                let limited = posts.len() as u64 >= config.instagram_page_size;
+                Ok(JsonWithCache {
+                    inner: InstagramPostsResponse {
+                        data: posts,
+                        from_cache: true,
+                        cache_age: Some(age),
+                        limited,
+                        total_count: None,
+                        gap: false,
+                    },
+                    from_cache: true,
+                    cache_age: Some(age),
+                    cache_duration: cache.cache_duration.as_secs(),
+                })
+            } else {
+                Err(err.into())
+            }
+        }
+    }
+}
+
+#[delete("/<username>/cache?<images>")]
+pub async fn purge_cache(
+    username: &str,
+    images: Option<bool>,
+    admin: Result<AdminToken, ApiError>,
+    cache: &State<InstagramCache>,
+    image_cache: &State<ImageCache>,
+) -> Result<Json<InstagramCachePurgeResponse>, ApiError> {
+    admin?;
+    
+    let removed_user = cache.invalidate(username);
+    
+    // Images are only associated to a user through its cached data
+    let images_purged = match (&removed_user, images.unwrap_or(false)) {
+        (Some(user), true) => image_cache.invalidate_where(|url| user.is_content_url(url)),
+        _ => 0,
+    };
+    
+    log::info!("Cache purged for {} (user: {}, images: {})", username, removed_user.is_some(), images_purged);
+    
+    Ok(Json(InstagramCachePurgeResponse {
+        username: username.to_string(),
+        user_purged: removed_user.is_some(),
+        images_purged,
+    }))
+}
+
+// Whether the If-None-Match header of a request matches an ETag: `*`, or a comma-separated list of tags
+// compared weakly (ignoring the `W/` prefix), as RFC 9110 requires for If-None-Match
+fn if_none_match(req: &Request<'_>, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    req.headers()
+        .get("If-None-Match")
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
+
+// Responder for image data
+// Browser cache lifetime of the fallback image, so that the real one is retried soon
+const FALLBACK_IMAGE_MAX_AGE: u64 = 60;
+
+pub struct ImageResponse {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    // ETag stored along with cached images, computed from the data when missing
+    pub etag: Option<String>,
+    pub max_age: u64,
+}
+
+impl<'r> Responder<'r, 'static> for ImageResponse {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        // Convert content type string to ContentType
+        let content_type = match self.content_type.as_str() {
+            "image/jpeg" => ContentType::JPEG,
+            "image/png" => ContentType::PNG,
+            "image/gif" => ContentType::GIF,
+            "image/webp" => ContentType::new("image", "webp"),
+            "image/bmp" => ContentType::new("image", "bmp"),
+            "image/tiff" => ContentType::new("image", "tiff"),
+            "image/x-icon" => ContentType::new("image", "x-icon"),
+            "image/avif" => ContentType::AVIF,
+            _ => ContentType::JPEG, // Default if unknown
+        };
+        let etag = self.etag.unwrap_or_else(|| image_etag(&self.data));
+        let cache_control = format!("public, max-age={}", self.max_age);
+        // Check If-None-Match header
+        if if_none_match(req, &etag) {
+            // ETag matches, return 304 Not Modified
+            return Response::build()
+                .status(rocket::http::Status::NotModified)
+                .header(Header::new("ETag", etag))
+                .header(Header::new("Cache-Control", cache_control))
+                .ok();
+        }
+        Response::build()
+            .header(content_type)
+            // The output format may be negotiated from the Accept header
+            .header(Header::new("Vary", "Accept"))
+            .header(Header::new("Cache-Control", cache_control))
+            .header(Header::new("ETag", etag))
+            // Known size, sent as the Content-Length of HEAD responses too
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+// Streamed video, partial when the client requested a range
+pub struct VideoResponse {
+    pub video: VideoStream,
+    pub max_age: u64,
+}
+
+impl<'r> Responder<'r, 'r> for VideoResponse {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'r> {
+        let VideoStream { partial, content_type, content_length, content_range, response } = self.video;
+        
+        // Forward the body chunk by chunk instead of buffering the whole video
+        let chunks = futures::stream::unfold(response, |mut response| async move {
+            match response.chunk().await {
+                Ok(Some(chunk)) => Some((chunk, response)),
+                Ok(None) => None,
+                Err(e) => {
+                    log::warn!("Video stream interrupted: {}", e);
+                    None
+                }
+            }
+        });
+        
+        let mut builder = Response::build_from(ByteStream(chunks).respond_to(req)?);
+        builder
+            .status(if partial { rocket::http::Status::PartialContent } else { rocket::http::Status::Ok })
+            .raw_header("Content-Type", content_type)
+            .raw_header("Accept-Ranges", "bytes")
+            .raw_header("Cache-Control", format!("public, max-age={}", self.max_age));
+        if let Some(content_length) = content_length {
+            builder.raw_header("Content-Length", content_length);
+        }
+        if let Some(content_range) = content_range {
+            builder.raw_header("Content-Range", content_range);
+        }
+        builder.ok()
+    }
+}
+
+#[derive(FromForm, serde::Deserialize)]
+pub struct ImageProxyQuery {
+    // Required by the image route, ignored by the avatar route
+    pub url: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub scale: Option<f32>,
+    pub format: Option<String>,
+    pub quality: Option<u8>,
+    pub lossless: Option<bool>,
+    pub fit: Option<String>,
+    pub focus: Option<String>,
+    pub grayscale: Option<bool>,
+    pub blur: Option<f32>,
+    pub sharpen: Option<f32>,
+    pub rotate: Option<u16>,
+    pub flip: Option<String>,
+    pub auto_orient: Option<bool>,
+    pub fallback: Option<bool>,
+    // `base64` to get a JSON data URL instead of the image bytes (image route only)
+    pub encoding: Option<String>,
+    // Signed image URLs: expiry (Unix timestamp) and hex signature
+    pub expires: Option<u64>,
+    pub sig: Option<String>,
+}
+
+// Raw `Accept` header of the request, used to negotiate the image format
+pub struct AcceptHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptHeader {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(AcceptHeader(request.headers().get_one("Accept").map(str::to_string)))
+    }
+}
+
+// Raw `Range` header of the request, forwarded to the CDN for videos
+pub struct RangeHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(RangeHeader(request.headers().get_one("Range").map(str::to_string)))
+    }
+}
+
+impl ImageProxyQuery {
+    // Conversion params, with the format negotiated from the Accept header when none is requested,
+    // and whether the conversion only comes from this negotiation (no conversion requested by the client)
+    fn to_negotiated_conversion_params(&self, accept: &AcceptHeader, config: &AppConfig) -> Result<(ImageConversionParams, bool), ApiError> {
+        let mut params = self.to_conversion_params(config)?;
+        let requested = params.needs_conversion();
+        if params.format.is_none() {
+            params.format = accept.0.as_deref()
+                .and_then(|accept| ImageConversionFormat::negotiate(accept, |format| {
+                    // AVIF can't be lossless
+                    config.image_format_allowed(format) && !(params.lossless && matches!(format, ImageConversionFormat::Avif))
+                }));
+        }
+        Ok((params, !requested))
+    }
+    
+    fn to_conversion_params(&self, config: &AppConfig) -> Result<ImageConversionParams, ApiError> {
+        let format = if let Some(ref fmt) = self.format {
+            let format = ImageConversionFormat::from_name(fmt).ok_or_else(|| ApiError::BadRequest(
+                format!("Unsupported format: {}", fmt)
+            ))?;
+            
+            // Formats the operator disallowed are replaced by the fallback one, or rejected
+            if config.image_format_allowed(&format) {
+                Some(format)
+            } else if let Some(fallback) = config.image_format_fallback() {
+                log::debug!("Format {} is not allowed, using {:?} instead", fmt, fallback);
+                Some(fallback)
+            } else {
+                return Err(ApiError::ImageError(ImageProxyError::ConversionError(
+                    format!("Format {} is not allowed", fmt)
+                )));
+            }
+        } else {
             None
         };
         
@@ -300,9 +963,11 @@ impl ImageProxyQuery {
                 "scale" => crate::images::ImageFit::Scale,
                 "crop" => crate::images::ImageFit::Crop,
                 "thumb" => crate::images::ImageFit::Thumb,
-                _ => return Err(ApiError::ScraperError(crate::scrapers::instagram::ScraperError::ParsingError(
+                "inside" => crate::images::ImageFit::Inside,
+                "outside" => crate::images::ImageFit::Outside,
+                _ => return Err(ApiError::BadRequest(
                     format!("Unsupported fit: {}", fit_str)
-                ))),
+                )),
             })
         } else {
             None
@@ -321,53 +986,392 @@ impl ImageProxyQuery {
                 "bottom_left" => crate::images::ImageFocus::BottomLeft,
                 "face" => crate::images::ImageFocus::Face,
                 "faces" => crate::images::ImageFocus::Faces,
-                _ => return Err(ApiError::ScraperError(crate::scrapers::instagram::ScraperError::ParsingError(
+                _ => return Err(ApiError::BadRequest(
                     format!("Unsupported focus: {}", focus_str)
-                ))),
+                )),
             })
         } else {
             None
         };
         
-        Ok(ImageConversionParams {
+        // Filter sigmas must be positive numbers
+        for (name, sigma) in [("blur", self.blur), ("sharpen", self.sharpen)] {
+            if let Some(sigma) = sigma {
+                if !sigma.is_finite() || sigma <= 0.0 {
+                    return Err(ApiError::BadRequest(
+                        format!("Invalid {} value: {}", name, sigma)
+                    ));
+                }
+            }
+        }
+        
+        let params = ImageConversionParams {
             width: self.width,
             height: self.height,
+            scale: self.scale,
             format,
             quality: self.quality,
+            lossless: self.lossless.unwrap_or(false),
             fit,
             focus,
-        })
+            grayscale: self.grayscale.unwrap_or(false),
+            blur: self.blur,
+            sharpen: self.sharpen,
+            rotate: self.rotate,
+            flip: self.flip.clone(),
+            auto_orient: self.auto_orient,
+        };
+        params.validate()?;
+        
+        Ok(params)
+    }
+}
+
+// Check the API key or signature of an image request, returning its image URL and whether it asks for a data URL
+fn check_image_request(
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    query: &ImageProxyQuery,
+    config: &AppConfig,
+) -> Result<(String, bool), ApiError> {
+    // Signatures are checked when required, or when one is given: a valid signature stands in for the API key,
+    // so that signed URLs can be embedded as is
+    let signer = config.image_signer().filter(|_| config.require_signed_images || query.sig.is_some());
+    match (&signer, client) {
+        (None, client) => { client?; },
+        (Some(_), Err(ApiError::RateLimitExceeded(retry_after))) => return Err(ApiError::RateLimitExceeded(retry_after)),
+        _ => {},
+    }
+
+    let url = query.url.clone().ok_or_else(|| ApiError::BadRequest("Missing url parameter".to_string()))?;
+    
+    let data_url = match query.encoding.as_deref() {
+        None | Some("binary") => false,
+        Some("base64") => true,
+        Some(other) => return Err(ApiError::BadRequest(format!("Unsupported encoding: {}", other))),
+    };
+    
+    if let Some(signer) = signer {
+        let params_key = query.to_conversion_params(config)?.to_cache_key();
+        signer.verify(username, &url, &params_key, query.expires, query.sig.as_deref())
+            .map_err(ApiError::InvalidSignature)?;
     }
+    
+    Ok((url, data_url))
 }
 
+#[allow(clippy::too_many_arguments)]
 #[get("/<username>/image?<query..>")]
 pub async fn proxy_image(
     username: &str,
+    client: Result<ApiClient, ApiError>,
     query: ImageProxyQuery,
+    accept: AcceptHeader,
     image_cache: &State<ImageCache>,
     config: &State<AppConfig>,
     image_proxy: &State<ImageProxy>,
     scraper: &State<InstagramScraper>,
     cache: &State<InstagramCache>,
-) -> Result<ImageResponse, ApiError> {
-    log::debug!("Proxying image for user '{}', URL: {}", username, query.url);
+) -> Result<Either<ImageResponse, Json<InstagramImageDataUrlResponse>>, ApiError> {
+    let (url, data_url) = check_image_request(username, client, &query, config)?;
+    log::debug!("Proxying image for user '{}', URL: {}", username, url);
+    
+    let (conversion_params, negotiated_only) = query.to_negotiated_conversion_params(&accept, config)?;
+    let user_data = get_image_owner(username, config, scraper, cache).await?;
+    
+    // Check if URL belongs to user's content using the new method
+    if !user_data.is_content_url(&url) {
+        log::warn!("URL '{}' does not belong to user '{}'", url, username);
+        log::debug!("User has {} posts and {} reels", 
+            user_data.posts.as_ref().map_or(0, |p| p.len()),
+            user_data.reels.as_ref().map_or(0, |r| r.len()));
+        
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(
+            format!("URL '{}' does not belong to user '{}'", url, username)
+        )));
+    }
+    
+    log::debug!("URL validation passed for '{}'", url);
+    
+    let image = serve_image(&url, &conversion_params, negotiated_only, query.fallback, image_cache, config, image_proxy).await?;
+    image_or_data_url(image, data_url)
+}
+
+// The image itself, or a JSON data URL of it when requested
+fn image_or_data_url(image: ImageResponse, data_url: bool) -> Result<Either<ImageResponse, Json<InstagramImageDataUrlResponse>>, ApiError> {
+    if !data_url {
+        return Ok(Either::Left(image));
+    }
+    
+    Ok(Either::Right(Json(InstagramImageDataUrlResponse {
+        data_url: to_bounded_data_url(&image.content_type, &image.data)?,
+    })))
+}
+
+// Largest image returned as a data URL by the image and variants routes, in bytes (before base64 encoding)
+const MAX_DATA_URL_IMAGE_SIZE: usize = 5 * 1024 * 1024;
+
+// Data URL of an image, refused when the image is too large.
+// Resized images are already bounded by max_output_width x max_output_height, others may be arbitrarily large.
+fn to_bounded_data_url(content_type: &str, data: &[u8]) -> Result<String, ImageProxyError> {
+    if data.len() > MAX_DATA_URL_IMAGE_SIZE {
+        return Err(ImageProxyError::ConversionError(format!(
+            "Image too large for a data URL ({} bytes), request a smaller size", data.len()
+        )));
+    }
+    Ok(to_data_url(content_type, data))
+}
+
+// Headers of the image route (Content-Type, Content-Length, ETag) without the body, which Rocket strips.
+// The image is served from the cache when there, or cached along the way for the GET that usually follows.
+#[allow(clippy::too_many_arguments)]
+#[head("/<username>/image?<query..>")]
+pub async fn proxy_image_head(
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    query: ImageProxyQuery,
+    accept: AcceptHeader,
+    image_cache: &State<ImageCache>,
+    config: &State<AppConfig>,
+    image_proxy: &State<ImageProxy>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+) -> Result<Either<ImageResponse, Json<InstagramImageDataUrlResponse>>, ApiError> {
+    proxy_image(username, client, query, accept, image_cache, config, image_proxy, scraper, cache).await
+}
+
+// Query params left out of signed URLs: the previous signature, and the API key that the signature replaces
+const UNSIGNED_IMAGE_PARAMS: [&str; 3] = ["expires", "sig", "api_key"];
+
+// Signed URL of the image route with the same params, valid for `signed_image_ttl` seconds
+#[get("/<username>/image/signed?<query..>")]
+pub fn sign_image_url(
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    query: ImageProxyQuery,
+    origin: &Origin<'_>,
+    config: &State<AppConfig>,
+) -> Result<Json<InstagramSignedImageResponse>, ApiError> {
+    client?;
+
+    let signer = config.image_signer().ok_or_else(|| ApiError::NotConfigured("Image signing is not configured".to_string()))?;
+    let url = query.url.as_deref().ok_or_else(|| ApiError::BadRequest("Missing url parameter".to_string()))?;
+    let params_key = query.to_conversion_params(config)?.to_cache_key();
+    let expires = ImageSigner::expires_in(config.signed_image_ttl);
+    let sig = signer.sign(username, url, &params_key, expires);
+
+    // Params are kept as sent (already encoded), the signature covers their decoded values
+    let mut params: Vec<&str> = origin.query()
+        .map(|query| query.as_str().split('&')
+            .filter(|param| !param.is_empty())
+            .filter(|param| !UNSIGNED_IMAGE_PARAMS.contains(&param.split('=').next().unwrap_or_default()))
+            .collect())
+        .unwrap_or_default();
+    let signature = format!("expires={}&sig={}", expires, sig);
+    params.push(&signature);
+
+    let path = origin.path().as_str().trim_end_matches("/signed");
+    Ok(Json(InstagramSignedImageResponse {
+        url: format!("{}?{}", path, params.join("&")),
+        expires,
+    }))
+}
+
+// Maximum number of variants generated by a single variants request
+const MAX_IMAGE_VARIANTS: usize = 10;
+
+// Maximum number of distinct usernames in a single batch request
+const MAX_BATCH_USERNAMES: usize = 50;
+
+#[derive(serde::Deserialize)]
+pub struct ImageVariantsRequest {
+    pub url: String,
+    // Same params as the image route (`url` and `fallback` are ignored)
+    pub variants: Vec<ImageProxyQuery>,
+}
+
+// Several variants of an image, fetched and decoded once, returned as data URLs and cached like proxied images
+#[allow(clippy::too_many_arguments)]
+#[post("/<username>/image/variants", format = "json", data = "<request>")]
+pub async fn get_image_variants(
+    username: &str,
+    request: Json<ImageVariantsRequest>,
+    client: Result<ApiClient, ApiError>,
+    image_cache: &State<ImageCache>,
+    config: &State<AppConfig>,
+    image_proxy: &State<ImageProxy>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+) -> Result<Json<InstagramImageVariantsResponse>, ApiError> {
+    client?;
+
+    let ImageVariantsRequest { url, variants } = request.into_inner();
+    if variants.is_empty() || variants.len() > MAX_IMAGE_VARIANTS {
+        return Err(ApiError::BadRequest(format!("Between 1 and {} variants must be requested", MAX_IMAGE_VARIANTS)));
+    }
+    let params_list = variants.iter()
+        .map(|variant| variant.to_conversion_params(config))
+        .collect::<Result<Vec<_>, _>>()?;
+    log::debug!("Generating {} image variants for user '{}', URL: {}", params_list.len(), username, url);
+    
+    let user_data = get_image_owner(username, config, scraper, cache).await?;
+    if !user_data.is_content_url(&url) {
+        log::warn!("URL '{}' does not belong to user '{}'", url, username);
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(
+            format!("URL '{}' does not belong to user '{}'", url, username)
+        )));
+    }
     
-    // Convert query parameters to conversion params
-    let conversion_params = query.to_conversion_params()?;
+    // Serve cached variants, keeping the others (once each) to be generated
+    let mut data_urls = HashMap::new();
+    let mut missing: Vec<(String, ImageConversionParams)> = Vec::new();
+    for params in params_list {
+        let key = params.to_cache_key();
+        if data_urls.contains_key(&key) || missing.iter().any(|(missing_key, _)| *missing_key == key) {
+            continue;
+        }
+        match image_cache.get_image(&url, &params) {
+            Some((data, content_type, _)) => {
+                data_urls.insert(key, to_bounded_data_url(&content_type, &data)?);
+            },
+            None => missing.push((key, params)),
+        }
+    }
     
-    // Whitelist check
-    if let Some(whitelist) = &config.instagram_username_whitelist {
-        if !whitelist.contains(&username.to_string()) {
-            log::warn!("Username '{}' not in whitelist", username);
-            return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    if !missing.is_empty() {
+        let (raw_data, raw_content_type) = fetch_raw_image(&url, image_cache, image_proxy).await?;
+        
+        // Decode the source once, and convert it concurrently for each variant
+        let max_output = (config.max_output_width, config.max_output_height);
+        let default_quality = config.default_image_quality;
+        let decoded = if missing.iter().any(|(_, params)| params.needs_conversion()) {
+            let raw_data = raw_data.clone();
+            Some(Arc::new(run_blocking(move || DecodedImage::decode(&raw_data)).await?))
+        } else {
+            None
+        };
+        
+        let results = futures::future::join_all(missing.into_iter().map(|(key, params)| {
+            let decoded = decoded.clone();
+            let (url, raw_data, raw_content_type) = (&url, &raw_data, &raw_content_type);
+            async move {
+                let decoded = match decoded.filter(|_| params.needs_conversion()) {
+                    Some(decoded) => decoded,
+                    // Pass-through variant, already cached as the raw image
+                    None => return Ok((key, to_bounded_data_url(raw_content_type, raw_data)?)),
+                };
+                let (params, (data, content_type)) = run_blocking(move || {
+                    let converted = decoded.convert(&params, max_output, default_quality)?;
+                    Ok((params, converted))
+                }).await?;
+                image_cache.store_image(url, &params, data.clone(), content_type.clone());
+                Ok::<_, ImageProxyError>((key, to_bounded_data_url(&content_type, &data)?))
+            }
+        })).await;
+        
+        for result in results {
+            let (key, data_url) = result?;
+            data_urls.insert(key, data_url);
         }
     }
     
-    // Verify URL belongs to the user by checking against cached user data
-    let user_data = match cache.get_user_even_expired(username) {
+    Ok(Json(InstagramImageVariantsResponse {
+        url,
+        variants: data_urls,
+    }))
+}
+
+// Run a CPU-bound image operation on the blocking thread pool
+async fn run_blocking<T: Send + 'static>(
+    operation: impl FnOnce() -> Result<T, ImageProxyError> + Send + 'static,
+) -> Result<T, ImageProxyError> {
+    tokio::task::spawn_blocking(operation)
+        .await
+        .map_err(|e| ImageProxyError::ConversionError(format!("Image task failed: {}", e)))?
+}
+
+fn to_data_url(content_type: &str, data: &[u8]) -> String {
+    use base64::Engine;
+    format!("data:{};base64,{}", content_type, base64::engine::general_purpose::STANDARD.encode(data))
+}
+
+// Profile picture of a user, served through the image proxy
+#[allow(clippy::too_many_arguments)]
+#[get("/<username>/avatar?<query..>")]
+pub async fn get_avatar(
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    query: ImageProxyQuery,
+    accept: AcceptHeader,
+    image_cache: &State<ImageCache>,
+    config: &State<AppConfig>,
+    image_proxy: &State<ImageProxy>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+) -> Result<ImageResponse, ApiError> {
+    client?;
+
+    let (conversion_params, negotiated_only) = query.to_negotiated_conversion_params(&accept, config)?;
+    let user_data = get_image_owner(username, config, scraper, cache).await?;
+    
+    let url = user_data.profile_pic_url.ok_or_else(|| ApiError::ScraperError(ScraperError::ParsingError(
+        format!("No profile picture found for '{}'", username)
+    )))?;
+    log::debug!("Serving avatar of '{}', URL: {}", username, url);
+    
+    serve_image(&url, &conversion_params, negotiated_only, query.fallback, image_cache, config, image_proxy).await
+}
+
+// Stream a video of a user, supporting range requests so that players can seek
+#[allow(clippy::too_many_arguments)]
+#[get("/<username>/video?<url>")]
+pub async fn proxy_video(
+    username: &str,
+    url: &str,
+    client: Result<ApiClient, ApiError>,
+    range: RangeHeader,
+    config: &State<AppConfig>,
+    image_proxy: &State<ImageProxy>,
+    scraper: &State<InstagramScraper>,
+    cache: &State<InstagramCache>,
+) -> Result<VideoResponse, ApiError> {
+    client?;
+
+    log::debug!("Proxying video for user '{}', URL: {}, range: {:?}", username, url, range.0);
+    
+    let user_data = get_image_owner(username, config, scraper, cache).await?;
+    if !user_data.is_content_url(url) {
+        log::warn!("URL '{}' does not belong to user '{}'", url, username);
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(
+            format!("URL '{}' does not belong to user '{}'", url, username)
+        )));
+    }
+    
+    let video = image_proxy.fetch_video(url, range.0.as_deref()).await?;
+    
+    Ok(VideoResponse {
+        video,
+        max_age: config.image_cache_control_max_age,
+    })
+}
+
+// Get the data of a whitelisted user (even expired, scraping it if not cached) to check image ownership
+async fn get_image_owner(
+    username: &str,
+    config: &AppConfig,
+    scraper: &InstagramScraper,
+    cache: &InstagramCache,
+) -> Result<InstagramUser, ApiError> {
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        log::warn!("Username '{}' not allowed", username);
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    }
+    
+    match cache.get_user_even_expired(username) {
         Some((user, _)) => {
             log::debug!("Found cached user data for '{}'", username);
-            user
+            Ok(user)
         },
         None => {
             // Try to fetch user data if not in cache
@@ -375,90 +1379,157 @@ pub async fn proxy_image(
             match scraper.scrape_user(username).await {
                 Ok(user) => {
                     cache.store_user(user.clone());
-                    user
+                    Ok(user)
                 },
                 Err(err) => {
                     log::error!("Failed to fetch user data for '{}': {:?}", username, err);
-                    return Err(ApiError::ScraperError(err))
+                    Err(ApiError::ScraperError(err))
                 }
             }
         }
-    };
-    
-    // Check if URL belongs to user's content using the new method
-    if !user_data.is_content_url(&query.url) {
-        log::warn!("URL '{}' does not belong to user '{}'", query.url, username);
-        log::debug!("User has {} posts and {} reels", 
-            user_data.posts.as_ref().map_or(0, |p| p.len()),
-            user_data.reels.as_ref().map_or(0, |r| r.len()));
-        
-        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(
-            format!("URL '{}' does not belong to user '{}'", query.url, username)
-        )));
     }
-    
-    log::debug!("URL validation passed for '{}'", query.url);
-    
+}
+
+// Serve an image (already checked to belong to the user) from the cache, or fetch, convert and cache it.
+// When the conversion was only negotiated (`negotiated_only`), a source that can't be converted is served as is.
+#[allow(clippy::too_many_arguments)]
+async fn serve_image(
+    url: &str,
+    conversion_params: &ImageConversionParams,
+    negotiated_only: bool,
+    fallback: Option<bool>,
+    image_cache: &ImageCache,
+    config: &AppConfig,
+    image_proxy: &ImageProxy,
+) -> Result<ImageResponse, ApiError> {
     // Step 1: Check if we already have the processed image with the exact conversion params
-    if let Some((image_data, content_type)) = image_cache.get_image(&query.url, &conversion_params) {
-        log::info!("Processed image found in cache: {} with params: {:?}", query.url, conversion_params);
+    if let Some((image_data, content_type, etag)) = image_cache.get_image(url, conversion_params) {
+        log::info!("Processed image found in cache: {} with params: {:?}", url, conversion_params);
         return Ok(ImageResponse {
-            data: image_data,
+            data: Arc::unwrap_or_clone(image_data),
             content_type,
+            etag,
+            max_age: config.image_cache_control_max_age,
         });
     }
     
-    // Step 2: Check if we have the raw image cached
-    let raw_params = ImageConversionParams::default(); // Empty params for raw image
-    let raw_image_data = if let Some((raw_data, original_content_type)) = image_cache.get_image(&query.url, &raw_params) {
-        log::debug!("Raw image found in cache: {}", query.url);
-        (raw_data, original_content_type)
-    } else {
-        // Step 3: Fetch and cache the raw image
-        log::debug!("Raw image not found in cache, fetching: {}", query.url);
-        match image_proxy.fetch_image(&query.url).await {
-            Ok((raw_data, original_content_type)) => {
-                // Store the raw image in cache
-                image_cache.store_image(&query.url, &raw_params, raw_data.clone(), original_content_type.clone());
-                log::info!("Raw image fetched and cached: {}", query.url);
-                (raw_data, original_content_type)
-            },
-            Err(err) => {
-                log::error!("Failed to fetch raw image '{}': {:?}", query.url, err);
-                return Err(err.into());
+    // Steps 2 and 3: Get the raw image from the cache, or fetch and cache it
+    let raw_image_data = match fetch_raw_image(url, image_cache, image_proxy).await {
+        Ok(raw_image_data) => raw_image_data,
+        Err(err) => {
+            // Serve the placeholder image instead of an error if requested
+            if fallback.unwrap_or(config.image_fallback_default) {
+                if let Some((data, content_type)) = image_proxy.fallback_image() {
+                    log::info!("Returning fallback image for '{}'", url);
+                    return Ok(ImageResponse {
+                        data,
+                        content_type,
+                        etag: None,
+                        max_age: FALLBACK_IMAGE_MAX_AGE,
+                    });
+                }
             }
+            return Err(err.into());
         }
     };
     
-    // Step 4: Convert the raw image if conversion is needed
-    let (processed_data, content_type) = if conversion_params.needs_conversion() {
-        log::debug!("Converting raw image with params: {:?}", conversion_params);
-        match crate::images::tools::convert_image(raw_image_data.0, &conversion_params) {
-            Ok((converted_data, converted_content_type)) => {
-                log::info!("Image converted successfully");
-                (converted_data, converted_content_type)
-            },
-            Err(err) => {
-                log::error!("Failed to convert image: {:?}", err);
-                return Err(err.into());
+    // Step 4: Pass the raw image through untouched (bytes and content type) when no conversion is needed,
+    // it is already cached under the raw image key
+    if !conversion_params.needs_conversion() {
+        log::debug!("No conversion needed, returning raw image");
+        return Ok(ImageResponse {
+            data: raw_image_data.0,
+            content_type: raw_image_data.1,
+            etag: None,
+            max_age: config.image_cache_control_max_age,
+        });
+    }
+    
+    // Step 5: Convert the raw image
+    log::debug!("Converting raw image with params: {:?}", conversion_params);
+    // Keep the source when nothing was requested, to fall back on it if the decoder doesn't support it
+    let source = negotiated_only.then(|| raw_image_data.clone());
+    let (processed_data, content_type) = match crate::images::tools::convert_image(
+        raw_image_data.0,
+        conversion_params,
+        (config.max_output_width, config.max_output_height),
+        config.default_image_quality,
+    ) {
+        Ok((converted_data, converted_content_type)) => {
+            log::info!("Image converted successfully");
+            (converted_data, converted_content_type)
+        },
+        Err(err) => {
+            if let Some((data, content_type)) = source {
+                // Cached as is under the negotiated params, so that the source isn't decoded again
+                log::warn!("Failed to convert image to the negotiated format, serving it as is: {:?}", err);
+                image_cache.store_image(url, conversion_params, data.clone(), content_type.clone());
+                return Ok(ImageResponse {
+                    data,
+                    content_type,
+                    etag: None,
+                    max_age: config.image_cache_control_max_age,
+                });
             }
+            log::error!("Failed to convert image: {:?}", err);
+            return Err(err.into());
         }
-    } else {
-        // No conversion needed, return the raw image
-        log::debug!("No conversion needed, returning raw image");
-        raw_image_data
     };
     
-    // Step 5: Cache the processed image
-    image_cache.store_image(&query.url, &conversion_params, processed_data.clone(), content_type.clone());
+    // Step 6: Cache the processed image
+    image_cache.store_image(url, conversion_params, processed_data.clone(), content_type.clone());
     log::info!("Processed image cached with params: {:?}", conversion_params);
     
     Ok(ImageResponse {
         data: processed_data,
         content_type,
+        etag: None,
+        max_age: config.image_cache_control_max_age,
     })
 }
 
+// Get a raw image (already checked to belong to the user) from the cache, or fetch and cache it
+async fn fetch_raw_image(
+    url: &str,
+    image_cache: &ImageCache,
+    image_proxy: &ImageProxy,
+) -> Result<(Vec<u8>, String), ImageProxyError> {
+    let raw_params = ImageConversionParams::default(); // Empty params for raw image
+    if let Some((raw_data, content_type, _)) = image_cache.get_image(url, &raw_params) {
+        log::debug!("Raw image found in cache: {}", url);
+        return Ok((Arc::unwrap_or_clone(raw_data), content_type));
+    }
+    
+    log::debug!("Raw image not found in cache, fetching: {}", url);
+    match image_proxy.fetch_image(url).await {
+        Ok((raw_data, original_content_type)) => {
+            // Store the raw image in cache
+            image_cache.store_image(url, &raw_params, raw_data.clone(), original_content_type.clone());
+            log::info!("Raw image fetched and cached: {}", url);
+            Ok((raw_data, original_content_type))
+        },
+        Err(err) => {
+            log::error!("Failed to fetch raw image '{}': {:?}", url, err);
+            Err(err)
+        }
+    }
+}
+
+// Fields of the JSON responses describing how they were produced rather than what they hold
+const VOLATILE_RESPONSE_FIELDS: [&str; 3] = ["fromCache", "cacheAge", "scrapeDurationMs"];
+
+// Weak ETag of a JSON response body, computed without its volatile fields so that cache metadata changing
+// between polls doesn't invalidate an otherwise identical response (pagination state does)
+fn json_etag(body: &serde_json::Value) -> String {
+    let mut body = body.clone();
+    if let Some(fields) = body.as_object_mut() {
+        for field in VOLATILE_RESPONSE_FIELDS {
+            fields.remove(field);
+        }
+    }
+    format!("W/\"{:x}\"", md5::compute(body.to_string()))
+}
+
 pub struct JsonWithCache<T> {
     pub inner: T,
     pub from_cache: bool,
@@ -467,19 +1538,834 @@ pub struct JsonWithCache<T> {
 }
 
 impl<'r, T: serde::Serialize> Responder<'r, 'static> for JsonWithCache<T> {
-    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
-        let mut response = Response::build();
-        response.header(ContentType::JSON);
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let body = serde_json::to_value(&self.inner).unwrap();
+        let etag = json_etag(&body);
         // Set cache headers
-        if self.from_cache {
+        let cache_control = if self.from_cache {
             // If from cache, set max-age to remaining cache duration
             let max_age = self.cache_age.map(|age| self.cache_duration.saturating_sub(age)).unwrap_or(self.cache_duration);
-            response.header(Header::new("Cache-Control", format!("public, max-age={}", max_age)));
+            format!("public, max-age={}", max_age)
         } else {
             // If fresh, set max-age to full cache duration
-            response.header(Header::new("Cache-Control", format!("public, max-age={}", self.cache_duration)));
+            format!("public, max-age={}", self.cache_duration)
+        };
+        // Check If-None-Match header
+        if if_none_match(req, &etag) {
+            // ETag matches, return 304 Not Modified
+            return Response::build()
+                .status(rocket::http::Status::NotModified)
+                .header(Header::new("ETag", etag))
+                .header(Header::new("Cache-Control", cache_control))
+                .ok();
+        }
+        Response::build()
+            .header(ContentType::JSON)
+            .header(Header::new("Cache-Control", cache_control))
+            .header(Header::new("ETag", etag))
+            .sized_body(None, Cursor::new(body.to_string()))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+    use crate::models::instagram::{InstagramReel, InstagramReelsPage, InstagramUserStats};
+    use crate::proxy::ProxyManager;
+
+    fn test_user(username: &str) -> InstagramUser {
+        InstagramUser {
+            username: username.to_string(),
+            full_name: None,
+            biography: None,
+            profile_pic_url: None,
+            is_private: false,
+            is_verified: false,
+            external_url: None,
+            category: None,
+            is_business: false,
+            account_type: None,
+            business_contact_method: None,
+            stats: InstagramUserStats { posts_count: Some(0), followers_count: Some(1), following_count: Some(1) },
+            posts: Some(Vec::new()),
+            reels: Some(Vec::new()),
+            scraped_at: Utc::now(),
+            posts_limited: false,
+        }
+    }
+
+    fn test_post(shortcode: &str, timestamp: Option<i64>) -> InstagramPost {
+        InstagramPost {
+            id: format!("id_{}", shortcode),
+            shortcode: shortcode.to_string(),
+            display_url: None,
+            thumbnail_url: None,
+            resolutions: Vec::new(),
+            caption: None,
+            alt_text: None,
+            hashtags: Vec::new(),
+            mentions: Vec::new(),
+            likes_count: None,
+            comments_count: None,
+            timestamp: timestamp.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+            timestamp_unix: timestamp,
+            is_video: false,
+            video_url: None,
+            video_view_count: None,
+            video_duration: None,
+            video_width: None,
+            video_height: None,
+            carousel: Vec::new(),
+        }
+    }
+
+    // Posts newest first, as scraped: c (day 3), b (day 2), a (day 1)
+    fn test_posts() -> Vec<InstagramPost> {
+        vec![test_post("c", Some(3 * 86400)), test_post("b", Some(2 * 86400)), test_post("a", Some(86400))]
+    }
+
+    fn posts_query(since: Option<&str>, limit: Option<usize>, order: Option<&str>, after_shortcode: Option<&str>) -> PostsQuery {
+        PostsQuery {
+            since: since.map(str::to_string),
+            until: None,
+            limit,
+            exclude_undated: None,
+            order: order.map(str::to_string),
+            after_shortcode: after_shortcode.map(str::to_string),
+            fresh: None,
+        }
+    }
+
+    fn shortcodes(posts: &[InstagramPost]) -> Vec<&str> {
+        posts.iter().map(|post| post.shortcode.as_str()).collect()
+    }
+
+    // Client of the Instagram routes, scraping without proxies so that every scrape fails fast offline
+    fn instagram_client(config: AppConfig) -> Client {
+        let image_proxy = image_proxy(&config);
+        instagram_client_with(config, image_proxy)
+    }
+
+    fn instagram_client_with(config: AppConfig, image_proxy: ImageProxy) -> Client {
+        let rocket = rocket::build()
+            .manage(InstagramScraper::new(config.clone(), ProxyManager::new(None, 4)))
+            .manage(InstagramCache::new(1))
+            .manage(ImageCache::new())
+            .manage(image_proxy)
+            .manage(config)
+            .mount("/instagram", routes![
+                get_user, get_users_batch, get_tagged, purge_cache, proxy_image, proxy_image_head,
+                sign_image_url, get_image_variants, get_avatar, get_post, get_posts, get_reels, proxy_video
+            ]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    fn image_proxy(config: &AppConfig) -> ImageProxy {
+        let image_proxy = ImageProxy::new(config.timeout, config.connect_timeout(), config.local_address());
+        match &config.image_fallback_path {
+            Some(path) => image_proxy.with_fallback(path).unwrap(),
+            None => image_proxy,
+        }
+    }
+
+    // Nothing listens on port 1, so fetching this CDN image fails whether the host resolves or not
+    const UNREACHABLE_IMAGE_URL: &str = "http://scontent.cdninstagram.com:1/v/t51/111_222_n.jpg?stp=dst-jpg";
+
+    // A user whose only post is the image at the URL
+    fn user_with_image(username: &str, url: &str) -> InstagramUser {
+        let mut post = test_post("img", Some(86400));
+        post.display_url = Some(url.to_string());
+        InstagramUser { posts: Some(vec![post]), ..test_user(username) }
+    }
+
+    fn image_uri(username: &str, url: &str) -> String {
+        format!("/instagram/{}/image?url={}", username, rocket::http::RawStr::new(url).percent_encode())
+    }
+
+    // CDN image whose source is already in the image cache, so that it is served without fetching
+    const CACHED_IMAGE_URL: &str = "https://scontent.cdninstagram.com/v/t51/333_444_n.jpg?stp=dst-jpg";
+
+    fn test_jpeg(width: u32, height: u32) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        let image = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, 128]));
+        image::DynamicImage::ImageRgb8(image).write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg).unwrap();
+        jpeg
+    }
+
+    // Client with alice owning the cached source image
+    fn image_client(config: AppConfig, source: Vec<u8>) -> Client {
+        let client = instagram_client(config);
+        instagram_cache(&client).store_user(user_with_image("alice", CACHED_IMAGE_URL));
+        image_cache(&client).store_image(CACHED_IMAGE_URL, &ImageConversionParams::default(), source, "image/jpeg".to_string());
+        client
+    }
+
+    fn image_cache(client: &Client) -> &ImageCache {
+        client.rocket().state::<ImageCache>().unwrap()
+    }
+
+    fn instagram_cache(client: &Client) -> &InstagramCache {
+        client.rocket().state::<InstagramCache>().unwrap()
+    }
+
+    #[get("/etag?<limited>&<age>")]
+    fn etag_route(limited: bool, age: u64) -> JsonWithCache<serde_json::Value> {
+        JsonWithCache {
+            inner: serde_json::json!({ "data": [1, 2], "fromCache": true, "cacheAge": age, "limited": limited }),
+            from_cache: true,
+            cache_age: Some(age),
+            cache_duration: 3600,
+        }
+    }
+
+    fn etag_client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![etag_route])).unwrap()
+    }
+
+    #[test]
+    fn second_request_with_etag_yields_304() {
+        let client = etag_client();
+        let response = client.get("/etag?limited=false&age=10").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+
+        // Cache metadata changes between the two requests
+        let response = client.get("/etag?limited=false&age=20").header(Header::new("If-None-Match", etag)).dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        assert!(response.into_bytes().unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn pagination_change_invalidates_etag() {
+        let client = etag_client();
+        let etag = client.get("/etag?limited=false&age=10").dispatch().headers().get_one("ETag").unwrap().to_string();
+
+        let response = client.get("/etag?limited=true&age=10").header(Header::new("If-None-Match", etag)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn json_etag_ignores_volatile_fields() {
+        let cached = serde_json::json!({ "data": [1], "fromCache": true, "cacheAge": 5, "totalCount": 3 });
+        let fresh = serde_json::json!({ "data": [1], "fromCache": false, "cacheAge": null, "scrapeDurationMs": 800, "totalCount": 3 });
+        let grown = serde_json::json!({ "data": [1], "fromCache": true, "cacheAge": 5, "totalCount": 4 });
+        assert_eq!(json_etag(&cached), json_etag(&fresh));
+        assert_ne!(json_etag(&cached), json_etag(&grown));
+        assert!(json_etag(&cached).starts_with("W/\""));
+    }
+
+    #[test]
+    fn if_none_match_accepts_lists_weak_tags_and_wildcard() {
+        let client = etag_client();
+        let matches = |header: Option<&str>, etag: &str| {
+            let mut request = client.get("/");
+            if let Some(header) = header {
+                request = request.header(Header::new("If-None-Match", header.to_string()));
+            }
+            if_none_match(request.inner(), etag)
+        };
+
+        assert!(matches(Some("\"abc\""), "\"abc\""));
+        assert!(matches(Some("W/\"abc\""), "\"abc\""));
+        assert!(matches(Some("\"abc\""), "W/\"abc\""));
+        assert!(matches(Some("\"x\", W/\"abc\""), "\"abc\""));
+        assert!(matches(Some("*"), "\"abc\""));
+        assert!(!matches(Some("\"abd\""), "\"abc\""));
+        assert!(!matches(None, "\"abc\""));
+    }
+
+    #[test]
+    fn batch_with_two_cached_and_one_missing_username() {
+        let client = instagram_client(AppConfig::default());
+        instagram_cache(&client).store_user(test_user("alice"));
+        instagram_cache(&client).store_user(test_user("bob"));
+
+        let response = client.post("/instagram/batch")
+            .header(ContentType::JSON)
+            .body(r#"["alice", "bob", "missing", "alice"]"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value = response.into_json().unwrap();
+        let data = body["data"].as_object().unwrap();
+        assert_eq!(data.len(), 3);
+        assert_eq!(data["alice"]["fromCache"], true);
+        assert_eq!(data["bob"]["data"]["username"], "bob");
+        assert_eq!(data["missing"]["code"], "PROXY_ERROR");
+    }
+
+    #[test]
+    fn batch_over_the_username_limit_is_rejected() {
+        let client = instagram_client(AppConfig::default());
+        let usernames: Vec<String> = (0..=MAX_BATCH_USERNAMES).map(|i| format!("user{}", i)).collect();
+
+        let response = client.post("/instagram/batch")
+            .header(ContentType::JSON)
+            .body(serde_json::to_string(&usernames).unwrap())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn since_filter_keeps_newer_posts() {
+        let filter = posts_query(Some("1970-01-02T12:00:00Z"), None, None, None).to_posts_filter().unwrap();
+        let (posts, gap) = filter.apply(test_posts());
+        assert_eq!(shortcodes(&posts), vec!["c", "b"]);
+        assert!(!gap);
+    }
+
+    #[test]
+    fn limit_keeps_the_newest_posts() {
+        let filter = posts_query(None, Some(2), None, None).to_posts_filter().unwrap();
+        let (posts, _) = filter.apply(test_posts());
+        assert_eq!(shortcodes(&posts), vec!["c", "b"]);
+    }
+
+    #[test]
+    fn invalid_posts_query_is_rejected() {
+        assert!(matches!(posts_query(Some("yesterday"), None, None, None).to_posts_filter(), Err(ApiError::BadRequest(_))));
+        assert!(matches!(posts_query(None, None, Some("up"), None).to_posts_filter(), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn purge_removes_the_cached_user() {
+        let client = instagram_client(AppConfig { admin_token: Some("secret".to_string()), ..AppConfig::default() });
+        instagram_cache(&client).store_user(test_user("alice"));
+
+        let response = client.delete("/instagram/alice/cache").header(Header::new("X-Admin-Token", "secret")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["userPurged"], true);
+        assert!(instagram_cache(&client).get_user("alice").is_none());
+    }
+
+    #[test]
+    fn purge_without_a_valid_admin_token_is_unauthorized() {
+        let client = instagram_client(AppConfig { admin_token: Some("secret".to_string()), ..AppConfig::default() });
+        instagram_cache(&client).store_user(test_user("alice"));
+
+        let response = client.delete("/instagram/alice/cache").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client.delete("/instagram/alice/cache").header(Header::new("X-Admin-Token", "wrong")).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert!(instagram_cache(&client).get_user("alice").is_some());
+    }
+
+    #[test]
+    fn tagged_route_returns_an_array() {
+        let client = instagram_client(AppConfig::default());
+        instagram_cache(&client).store_tagged("alice", vec![test_post("t1", Some(86400))]);
+
+        let response = client.get("/instagram/alice/tagged").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["data"].as_array().map(Vec::len), Some(1));
+        assert_eq!(body["data"][0]["shortcode"], "t1");
+        assert_eq!(body["fromCache"], true);
+    }
+
+    #[test]
+    fn tagged_route_applies_the_blocklist() {
+        let client = instagram_client(AppConfig { instagram_username_blocklist: Some(vec!["alice".to_string()]), ..AppConfig::default() });
+        instagram_cache(&client).store_tagged("alice", Vec::new());
+        assert_eq!(client.get("/instagram/alice/tagged").dispatch().status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn ascending_order_returns_the_earliest_post_first() {
+        let mut posts = test_posts();
+        posts.insert(1, test_post("undated", None));
+
+        let filter = posts_query(None, None, Some("asc"), None).to_posts_filter().unwrap();
+        let (sorted, _) = filter.apply(posts.clone());
+        assert_eq!(shortcodes(&sorted), vec!["a", "b", "c", "undated"]);
+
+        let filter = posts_query(None, None, None, None).to_posts_filter().unwrap();
+        let (sorted, _) = filter.apply(posts);
+        assert_eq!(shortcodes(&sorted), vec!["c", "b", "a", "undated"]);
+    }
+
+    #[test]
+    fn failing_fetch_serves_the_fallback_image() {
+        let path = std::env::temp_dir().join(format!("scrapn-fallback-{}.png", std::process::id()));
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(2, 2).write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        std::fs::write(&path, &png).unwrap();
+
+        let client = instagram_client(AppConfig { image_fallback_path: Some(path.to_string_lossy().to_string()), ..AppConfig::default() });
+        instagram_cache(&client).store_user(user_with_image("alice", UNREACHABLE_IMAGE_URL));
+        let uri = image_uri("alice", UNREACHABLE_IMAGE_URL);
+
+        let response = client.get(format!("{}&fallback=true", uri)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::PNG));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some(format!("public, max-age={}", FALLBACK_IMAGE_MAX_AGE).as_str()));
+        assert_eq!(response.into_bytes().unwrap(), png);
+
+        // Errors by default
+        assert_ne!(client.get(uri).dispatch().status(), Status::Ok);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn accept_header_negotiates_the_image_format() {
+        let client = image_client(AppConfig::default(), test_jpeg(8, 8));
+        let uri = image_uri("alice", CACHED_IMAGE_URL);
+
+        let response = client.get(uri.clone()).header(Header::new("Accept", "image/avif,image/webp,image/*,*/*;q=0.8")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let expected = if cfg!(feature = "avif") { "image/avif" } else { "image/webp" };
+        assert_eq!(response.headers().get_one("Content-Type"), Some(expected));
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept"));
+
+        let response = client.get(uri).header(Header::new("Accept", "image/*")).dispatch();
+        assert_eq!(response.headers().get_one("Content-Type"), Some("image/jpeg"));
+    }
+
+    #[test]
+    fn avatar_route_serves_the_profile_picture() {
+        let client = image_client(AppConfig::default(), test_jpeg(8, 8));
+        let user = InstagramUser { profile_pic_url: Some(CACHED_IMAGE_URL.to_string()), ..test_user("alice") };
+        instagram_cache(&client).store_user(user);
+
+        let response = client.get("/instagram/alice/avatar").header(Header::new("Accept", "image/*")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        let bytes = response.into_bytes().unwrap();
+        assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 8);
+    }
+
+    #[test]
+    fn known_shortcode_returns_its_post() {
+        let client = instagram_client(AppConfig::default());
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(test_posts()), ..test_user("alice") });
+
+        let response = client.get("/instagram/alice/posts/b").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["data"]["shortcode"], "b");
+        assert_eq!(body["fromCache"], true);
+    }
+
+    #[test]
+    fn unknown_shortcode_is_not_found() {
+        let client = instagram_client(AppConfig::default());
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(test_posts()), ..test_user("alice") });
+
+        // Not in the timeline, and not a valid shortcode so that no scrape is attempted
+        assert_eq!(client.get("/instagram/alice/posts/no.such.post").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn image_cache_control_uses_the_configured_max_age() {
+        let client = image_client(AppConfig { image_cache_control_max_age: 600, ..AppConfig::default() }, test_jpeg(8, 8));
+        let response = client.get(image_uri("alice", CACHED_IMAGE_URL)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=600"));
+    }
+
+    #[test]
+    fn ranged_video_request_is_partial() {
+        const VIDEO_URL: &str = "http://scontent.cdninstagram.com/v/t50/555_666_n.mp4?efg=1";
+        let server = crate::test_server::TestServer::start(vec![crate::test_server::response(
+            206,
+            &[("Content-Type", "video/mp4"), ("Content-Range", "bytes 0-3/10")],
+            b"\x00\x00\x00\x18",
+        )]);
+        let config = AppConfig::default();
+        let client = instagram_client_with(config.clone(), image_proxy(&config).with_proxy(&server.url));
+        let mut post = test_post("vid", Some(86400));
+        post.is_video = true;
+        post.video_url = Some(VIDEO_URL.to_string());
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(vec![post]), ..test_user("alice") });
+
+        let uri = format!("/instagram/alice/video?url={}", rocket::http::RawStr::new(VIDEO_URL).percent_encode());
+        let response = client.get(uri).header(Header::new("Range", "bytes=0-3")).dispatch();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes 0-3/10"));
+        assert_eq!(response.headers().get_one("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.into_bytes().unwrap(), b"\x00\x00\x00\x18");
+        assert_eq!(server.requests()[0].header("range"), Some("bytes=0-3"));
+    }
+
+    #[test]
+    fn fresh_flag_skips_a_warm_cache_entry() {
+        let client = instagram_client(AppConfig { max_retries: 0, ..AppConfig::default() });
+        instagram_cache(&client).store_user(test_user("alice"));
+        let scrapes = || {
+            let stats = client.rocket().state::<InstagramScraper>().unwrap().stats();
+            stats.succeeded + stats.errors.values().sum::<u64>()
+        };
+
+        let response = client.get("/instagram/alice").dispatch();
+        assert_eq!(response.into_json::<serde_json::Value>().unwrap()["fromCache"], true);
+        assert_eq!(scrapes(), 0);
+
+        // Instagram can't be reached here, so the scrape fails and the cached entry is served as a fallback
+        let response = client.get("/instagram/alice?fresh=true").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(scrapes(), 1);
+    }
+
+    #[test]
+    fn limited_profile_is_flagged_on_the_posts_route() {
+        let client = instagram_client(AppConfig::default());
+        let user = InstagramUser {
+            posts: Some(test_posts()),
+            posts_limited: true,
+            stats: InstagramUserStats { posts_count: Some(340), followers_count: Some(1), following_count: Some(1) },
+            ..test_user("alice")
+        };
+        instagram_cache(&client).store_user(user);
+
+        let body: serde_json::Value = client.get("/instagram/alice/posts").dispatch().into_json().unwrap();
+        assert_eq!(body["limited"], true);
+        assert_eq!(body["totalCount"], 340);
+        assert_eq!(body["data"].as_array().unwrap().len(), 3);
+
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(test_posts()), ..test_user("bob") });
+        let body: serde_json::Value = client.get("/instagram/bob/posts").dispatch().into_json().unwrap();
+        assert_eq!(body["limited"], false);
+    }
+
+    #[test]
+    fn unmodified_request_returns_the_source_bytes() {
+        let source = test_jpeg(8, 8);
+        let client = image_client(AppConfig::default(), source.clone());
+
+        let response = client.get(image_uri("alice", CACHED_IMAGE_URL)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        assert_eq!(response.into_bytes().unwrap(), source);
+    }
+
+    fn variants_body(url: &str, widths: &[u32]) -> String {
+        let variants: Vec<serde_json::Value> = widths.iter().map(|width| serde_json::json!({ "width": width })).collect();
+        serde_json::json!({ "url": url, "variants": variants }).to_string()
+    }
+
+    #[test]
+    fn three_variants_from_one_fetch() {
+        const SOURCE_URL: &str = "http://scontent.cdninstagram.com/v/t51/777_888_n.jpg?stp=dst-jpg";
+        let server = crate::test_server::TestServer::start(vec![crate::test_server::response(
+            200, &[("Content-Type", "image/jpeg")], &test_jpeg(16, 16),
+        )]);
+        let config = AppConfig::default();
+        let client = instagram_client_with(config.clone(), image_proxy(&config).with_proxy(&server.url));
+        instagram_cache(&client).store_user(user_with_image("alice", SOURCE_URL));
+
+        let response = client.post("/instagram/alice/image/variants")
+            .header(ContentType::JSON)
+            .body(variants_body(SOURCE_URL, &[4, 8, 12]))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["variants"].as_object().unwrap().len(), 3);
+
+        assert_eq!(server.requests().len(), 1);
+        for width in [4, 8, 12] {
+            let params = ImageConversionParams { width: Some(width), ..ImageConversionParams::default() };
+            let (data, _, _) = image_cache(&client).get_image(SOURCE_URL, &params).unwrap();
+            assert_eq!(image::load_from_memory(&data).unwrap().width(), width);
+        }
+    }
+
+    #[test]
+    fn oversized_variant_is_rejected() {
+        let client = image_client(AppConfig::default(), test_jpeg(8, 8));
+        let params = ImageConversionParams { width: Some(4), ..ImageConversionParams::default() };
+        image_cache(&client).store_image(CACHED_IMAGE_URL, &params, vec![0; MAX_DATA_URL_IMAGE_SIZE + 1], "image/jpeg".to_string());
+
+        let response = client.post("/instagram/alice/image/variants")
+            .header(ContentType::JSON)
+            .body(variants_body(CACHED_IMAGE_URL, &[2, 4]))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn disallowed_format_is_rejected() {
+        let config = AppConfig { allowed_image_formats: Some(vec!["webp".to_string()]), ..AppConfig::default() };
+        let client = image_client(config, test_jpeg(8, 8));
+        let uri = image_uri("alice", CACHED_IMAGE_URL);
+
+        let response = client.get(format!("{}&format=gif", uri)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "IMAGE_CONVERSION_ERROR");
+
+        let response = client.get(format!("{}&format=webp", uri)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Type"), Some("image/webp"));
+    }
+
+    #[test]
+    fn disallowed_format_is_coerced_to_the_fallback() {
+        let config = AppConfig {
+            allowed_image_formats: Some(vec!["webp".to_string()]),
+            image_format_fallback: Some("webp".to_string()),
+            ..AppConfig::default()
+        };
+        let client = image_client(config, test_jpeg(8, 8));
+
+        let response = client.get(format!("{}&format=gif", image_uri("alice", CACHED_IMAGE_URL))).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Type"), Some("image/webp"));
+    }
+
+    #[test]
+    fn media_false_omits_the_posts_and_reels() {
+        let client = instagram_client(AppConfig::default());
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(test_posts()), ..test_user("alice") });
+
+        let body: serde_json::Value = client.get("/instagram/alice?media=false").dispatch().into_json().unwrap();
+        assert!(body["data"]["posts"].is_null());
+        assert!(body["data"]["reels"].is_null());
+        assert_eq!(body["data"]["stats"]["followersCount"], 1);
+
+        let body: serde_json::Value = client.get("/instagram/alice").dispatch().into_json().unwrap();
+        assert_eq!(body["data"]["posts"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn after_shortcode_keeps_the_newer_posts() {
+        let filter = posts_query(None, None, None, Some("b")).to_posts_filter().unwrap();
+        let (posts, gap) = filter.apply(test_posts());
+        assert_eq!(shortcodes(&posts), vec!["c"]);
+        assert!(!gap);
+
+        // Undated posts are newer by their position in the scraped list
+        let posts = vec![test_post("z", None), test_post("y", None), test_post("x", None)];
+        let filter = posts_query(None, None, None, Some("y")).to_posts_filter().unwrap();
+        assert_eq!(shortcodes(&filter.apply(posts).0), vec!["z"]);
+
+        let filter = posts_query(None, None, None, Some("unknown")).to_posts_filter().unwrap();
+        let (posts, gap) = filter.apply(test_posts());
+        assert_eq!(shortcodes(&posts), vec!["c", "b", "a"]);
+        assert!(gap);
+    }
+
+    #[test]
+    fn after_shortcode_is_applied_on_the_posts_route() {
+        let client = instagram_client(AppConfig::default());
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(test_posts()), ..test_user("alice") });
+
+        let body: serde_json::Value = client.get("/instagram/alice/posts?after_shortcode=a").dispatch().into_json().unwrap();
+        let posts: Vec<&str> = body["data"].as_array().unwrap().iter().map(|post| post["shortcode"].as_str().unwrap()).collect();
+        assert_eq!(posts, vec!["c", "b"]);
+        assert_eq!(body["gap"], false);
+
+        let body: serde_json::Value = client.get("/instagram/alice/posts?after_shortcode=gone").dispatch().into_json().unwrap();
+        assert_eq!(body["data"].as_array().unwrap().len(), 3);
+        assert_eq!(body["gap"], true);
+    }
+
+    #[test]
+    fn head_returns_the_image_headers_without_body() {
+        let source = test_jpeg(8, 8);
+        let client = image_client(AppConfig::default(), source.clone());
+        let uri = image_uri("alice", CACHED_IMAGE_URL);
+
+        let get = client.get(uri.clone()).dispatch();
+        let response = client.head(uri).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        for header in ["ETag", "Cache-Control"] {
+            assert_eq!(response.headers().get_one(header), get.headers().get_one(header), "{}", header);
         }
-        response.sized_body(None, Cursor::new(serde_json::to_vec(&self.inner).unwrap()));
-        response.ok()
+        // Sent as the Content-Length by the server
+        assert_eq!(response.body().preset_size(), Some(source.len()));
+        assert!(response.into_bytes().unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn head_fetches_and_caches_an_image_not_in_cache() {
+        const SOURCE_URL: &str = "http://scontent.cdninstagram.com/v/t51/555_666_n.jpg?stp=dst-jpg";
+        let source = test_jpeg(8, 8);
+        let server = crate::test_server::TestServer::start(vec![crate::test_server::response(
+            200, &[("Content-Type", "image/jpeg")], &source,
+        )]);
+        let config = AppConfig::default();
+        let client = instagram_client_with(config.clone(), image_proxy(&config).with_proxy(&server.url));
+        instagram_cache(&client).store_user(user_with_image("alice", SOURCE_URL));
+
+        let response = client.head(image_uri("alice", SOURCE_URL)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        assert!(response.headers().get_one("ETag").is_some());
+        assert!(response.into_bytes().unwrap_or_default().is_empty());
+
+        // The following GET is served from the cache
+        let response = client.get(image_uri("alice", SOURCE_URL)).dispatch();
+        assert_eq!(response.into_bytes().unwrap(), source);
+        assert_eq!(server.requests().len(), 1);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn signed_image_urls_are_required_when_enabled() {
+        let config = AppConfig {
+            image_signing_secret: Some("secret".to_string()),
+            require_signed_images: true,
+            ..AppConfig::default()
+        };
+        let client = image_client(config, test_jpeg(8, 8));
+        let uri = format!("{}&width=4", image_uri("alice", CACHED_IMAGE_URL));
+
+        let signed = uri.replacen("/image?", "/image/signed?", 1);
+        let body: serde_json::Value = client.get(signed).dispatch().into_json().unwrap();
+        let signed_uri = body["url"].as_str().unwrap().to_string();
+        assert!(signed_uri.starts_with("/instagram/alice/image?") && signed_uri.contains("&sig="));
+
+        let response = client.get(signed_uri.clone()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(image::load_from_memory(&response.into_bytes().unwrap()).unwrap().width(), 4);
+
+        assert_eq!(client.get(uri).dispatch().status(), Status::Forbidden);
+        let tampered = signed_uri.replace("width=4", "width=6");
+        assert_eq!(client.get(tampered).dispatch().status(), Status::Forbidden);
+    }
+
+    #[test]
+    fn undecodable_source_is_served_as_is_without_conversion() {
+        let source = b"RIFF\x00\x00\x00\x00WEBPVP8X animated".to_vec();
+        let client = image_client(AppConfig::default(), source.clone());
+        let uri = image_uri("alice", CACHED_IMAGE_URL);
+
+        let response = client.get(uri.clone()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_bytes().unwrap(), source);
+
+        // Only a negotiated format was asked for
+        let response = client.get(uri.clone()).header(Header::new("Accept", "image/webp,*/*")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_bytes().unwrap(), source);
+
+        let response = client.get(format!("{}&width=4", uri)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["code"], "IMAGE_CONVERSION_ERROR");
+    }
+
+    #[test]
+    fn blocked_whitelisted_username_is_rejected() {
+        let config = AppConfig {
+            instagram_username_whitelist: Some(vec!["alice".to_string()]),
+            instagram_username_blocklist: Some(vec!["alice".to_string()]),
+            ..AppConfig::default()
+        };
+        let client = image_client(config, test_jpeg(8, 8));
+        instagram_cache(&client).store_user(InstagramUser { posts: Some(test_posts()), ..test_user("alice") });
+
+        for uri in ["/instagram/alice".to_string(), "/instagram/alice/posts".to_string(), image_uri("alice", CACHED_IMAGE_URL)] {
+            let response = client.get(uri.clone()).dispatch();
+            assert_eq!(response.status(), Status::Unauthorized, "{}", uri);
+            let body: serde_json::Value = response.into_json().unwrap();
+            assert_eq!(body["code"], "UNAUTHORIZED_ACCESS");
+        }
+    }
+
+    #[test]
+    fn scrape_duration_is_only_set_for_fresh_data() {
+        let client = instagram_client(AppConfig { max_retries: 0, ..AppConfig::default() });
+        instagram_cache(&client).store_user(test_user("alice"));
+
+        let body: serde_json::Value = client.get("/instagram/alice").dispatch().into_json().unwrap();
+        assert_eq!(body["fromCache"], true);
+        assert!(body["scrapeDurationMs"].is_null());
+
+        // Served from the cache after a failed fresh scrape
+        let body: serde_json::Value = client.get("/instagram/alice?fresh=true").dispatch().into_json().unwrap();
+        assert_eq!(body["fromCache"], true);
+        assert!(body["scrapeDurationMs"].is_null());
+
+        let response = InstagramUserResponse { data: test_user("alice"), from_cache: false, cache_age: None, scrape_duration_ms: Some(1250) };
+        assert_eq!(serde_json::to_value(response).unwrap()["scrapeDurationMs"], 1250);
+    }
+
+    #[test]
+    fn base64_encoding_returns_a_data_url() {
+        use base64::Engine;
+
+        let source = test_jpeg(8, 8);
+        let client = image_client(AppConfig::default(), source.clone());
+        let uri = image_uri("alice", CACHED_IMAGE_URL);
+        let data_url = |uri: String| -> (String, Vec<u8>) {
+            let body: serde_json::Value = client.get(uri).dispatch().into_json().unwrap();
+            let (header, data) = body["dataUrl"].as_str().unwrap().split_once(";base64,").unwrap();
+            (header.to_string(), base64::engine::general_purpose::STANDARD.decode(data).unwrap())
+        };
+
+        assert_eq!(data_url(format!("{}&encoding=base64", uri)), ("data:image/jpeg".to_string(), source));
+
+        // Same conversion params as the binary responses
+        let (header, data) = data_url(format!("{}&encoding=base64&width=4&format=png", uri));
+        assert_eq!(header, "data:image/png");
+        assert_eq!(image::load_from_memory(&data).unwrap().width(), 4);
+
+        assert_eq!(client.get(format!("{}&encoding=hex", uri)).dispatch().status(), Status::BadRequest);
+    }
+
+    fn test_reel(shortcode: &str) -> InstagramReel {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("id_{}", shortcode),
+            "shortcode": shortcode,
+            "displayUrl": null,
+            "videoUrl": null,
+            "caption": null,
+            "viewsCount": null,
+            "likesCount": null,
+            "commentsCount": null,
+            "timestamp": null
+        })).unwrap()
+    }
+
+    #[test]
+    fn reels_tab_is_preferred_over_derived_reels() {
+        use crate::test_server::{response, TestServer};
+
+        // Proxy refusing every tunnel, so that scraping the reels tab fails after reaching it
+        let server = TestServer::start(vec![response(502, &[], b"")]);
+        let config = AppConfig { max_retries: 0, ..AppConfig::default() };
+        let rocket = rocket::build()
+            .manage(InstagramScraper::new(config.clone(), ProxyManager::new(Some(vec![server.url.clone()]), 4)))
+            .manage(InstagramCache::new(1))
+            .manage(config)
+            .mount("/instagram", routes![get_reels]);
+        let client = Client::tracked(rocket).unwrap();
+        let reels = |client: &Client| -> Vec<String> {
+            let body: serde_json::Value = client.get("/instagram/alice/reels").dispatch().into_json().unwrap();
+            body["data"].as_array().unwrap().iter().map(|reel| reel["shortcode"].as_str().unwrap().to_string()).collect()
+        };
+
+        // The derived reels are only served once the reels tab failed
+        instagram_cache(&client).store_user(InstagramUser { reels: Some(vec![test_reel("derived")]), ..test_user("alice") });
+        assert_eq!(reels(&client), vec!["derived"]);
+        let attempts = server.requests().len();
+        assert!(attempts > 0 && server.requests().iter().all(|request| request.line.starts_with("CONNECT i.instagram.com:443")));
+
+        // Cached reels tab, including reels missing from the posts
+        let page = InstagramReelsPage { reels: vec![test_reel("tab_only"), test_reel("derived")], end_cursor: None };
+        instagram_cache(&client).store_clips("alice", page);
+        assert_eq!(reels(&client), vec!["tab_only", "derived"]);
+        assert_eq!(server.requests().len(), attempts);
+    }
+
+    #[test]
+    fn paged_reels_requests_are_always_scraped() {
+        let client = instagram_client(AppConfig::default());
+        let page = InstagramReelsPage { reels: vec![test_reel("cached")], end_cursor: Some("cursor1".to_string()) };
+        instagram_cache(&client).store_clips("alice", page);
+
+        let body: serde_json::Value = client.get("/instagram/alice/reels").dispatch().into_json().unwrap();
+        assert_eq!(body["endCursor"], "cursor1");
+        assert_eq!(body["limited"], true);
+
+        // Scraping fails offline, with no fallback on the cached first page
+        assert_ne!(client.get("/instagram/alice/reels?cursor=cursor1").dispatch().status(), Status::Ok);
+        assert_ne!(client.get("/instagram/alice/reels?limit=5").dispatch().status(), Status::Ok);
+        assert_eq!(client.get("/instagram/alice/reels?limit=0").dispatch().status(), Status::BadRequest);
+    }
+}
@@ -1,4 +1,9 @@
 pub mod instagram;
+pub mod profiles;
+pub mod admin;
+pub mod openapi;
 pub mod error;
+pub mod auth;
+pub mod rate_limit;
 
 pub use error::ApiError; 
\ No newline at end of file
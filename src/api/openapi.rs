@@ -0,0 +1,610 @@
+use rocket::serde::json::Json;
+use serde_json::{json, Value};
+
+// OpenAPI 3 description of the API, written by hand from the routes and serde models:
+// keep it in sync when changing query parameters or response fields.
+fn spec() -> Value {
+    let username = json!({
+        "name": "username",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" }
+    });
+    let error_responses = json!({
+        "400": { "description": "Invalid request parameter", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "401": { "description": "Missing or invalid API key, or username not allowed", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "403": { "description": "Private profile, or Instagram challenge required", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "404": { "description": "Profile not found", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "429": { "description": "Rate limited", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "503": { "description": "Scraping failed (network or proxies)", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } },
+        "504": { "description": "Scraping exceeded the configured deadline", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } }
+    });
+    let with_errors = |success: Value| -> Value {
+        let mut responses = error_responses.clone();
+        responses["200"] = success;
+        responses["304"] = json!({ "description": "Not modified (matching If-None-Match)" });
+        responses
+    };
+    let query = |name: &str, schema: Value, description: &str| -> Value {
+        json!({ "name": name, "in": "query", "required": false, "schema": schema, "description": description })
+    };
+
+    let fresh = query("fresh", json!({ "type": "boolean" }), "Skip the cache and scrape (expired cache still used if scraping fails)");
+
+    // Conversion parameters shared by the image and avatar routes
+    let image_conversion = vec![
+        query("width", json!({ "type": "integer", "minimum": 1 }), "Target width"),
+        query("height", json!({ "type": "integer", "minimum": 1 }), "Target height"),
+        query("scale", json!({ "type": "number", "exclusiveMinimum": 0 }), "Resize factor relative to the source size, e.g. 0.5 (exclusive with width and height)"),
+        query("format", json!({ "type": "string", "enum": ["webp", "jpg", "jpeg", "png", "gif", "avif"] }), "Output format (negotiated from the Accept header when unset, avif requires the `avif` feature)"),
+        query("quality", json!({ "type": "integer", "minimum": 0, "maximum": 100 }), "Output quality (JPEG, WebP, AVIF), or PNG compression effort (lower is smaller)"),
+        query("lossless", json!({ "type": "boolean" }), "Lossless WebP encoding (not supported with avif)"),
+        query("fit", json!({ "type": "string", "enum": ["pad", "fill", "scale", "crop", "thumb", "inside", "outside"] }), "Resize strategy (inside fits within the box, outside covers it, both keeping the aspect ratio)"),
+        query("focus", json!({ "type": "string", "enum": ["center", "top", "right", "left", "bottom", "top_right", "top_left", "bottom_right", "bottom_left", "face", "faces"] }), "Crop anchor"),
+        query("grayscale", json!({ "type": "boolean" }), "Convert to grayscale"),
+        query("blur", json!({ "type": "number", "exclusiveMinimum": 0 }), "Gaussian blur sigma"),
+        query("sharpen", json!({ "type": "number", "exclusiveMinimum": 0 }), "Unsharpen sigma"),
+        query("rotate", json!({ "type": "integer", "enum": [0, 90, 180, 270] }), "Clockwise rotation in degrees"),
+        query("flip", json!({ "type": "string", "enum": ["h", "v"] }), "Horizontal or vertical flip"),
+        query("auto_orient", json!({ "type": "boolean", "default": true }), "Apply the EXIF orientation"),
+        query("fallback", json!({ "type": "boolean" }), "Return the configured placeholder image instead of an error when the source can't be fetched")
+    ];
+
+    let mut image_parameters = vec![
+        username.clone(),
+        json!({ "name": "url", "in": "query", "required": true, "schema": { "type": "string" }, "description": "Instagram CDN URL of the image" }),
+    ];
+    image_parameters.extend(image_conversion.iter().cloned());
+    image_parameters.push(query("encoding", json!({ "type": "string", "enum": ["binary", "base64"], "default": "binary" }), "`base64` to get the image as a JSON data URL instead of its bytes (at most 5 MB)"));
+    let signed_image_parameters = image_parameters.clone();
+    image_parameters.push(query("expires", json!({ "type": "integer" }), "Expiry (Unix timestamp) of a signed URL"));
+    image_parameters.push(query("sig", json!({ "type": "string" }), "Signature of a signed URL, required when `require_signed_images` is enabled (stands in for the API key)"));
+    // Same conversion parameters, as properties of each variant of a variants request
+    let mut image_variant = serde_json::Map::new();
+    for parameter in image_conversion.iter().filter(|parameter| parameter["name"] != "fallback") {
+        let mut schema = parameter["schema"].clone();
+        schema["description"] = parameter["description"].clone();
+        image_variant.insert(parameter["name"].as_str().unwrap_or_default().to_string(), schema);
+    }
+    let mut avatar_parameters = vec![username.clone()];
+    avatar_parameters.extend(image_conversion);
+
+    let mut signed_image_responses = with_errors(json!({ "description": "Signed URL", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramSignedImageResponse" } } } }));
+    signed_image_responses["501"] = json!({ "description": "Image signing is not configured", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } } });
+
+    let mut image_variants_responses = error_responses.clone();
+    image_variants_responses["200"] = json!({ "description": "Variants", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramImageVariantsResponse" } } } });
+
+    let platform = json!({
+        "name": "platform",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string", "enum": ["instagram"] }
+    });
+    let mut profile_responses = error_responses.clone();
+    profile_responses["200"] = json!({ "description": "Profile", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Profile" } } } });
+    let mut profile_posts_responses = error_responses.clone();
+    profile_posts_responses["200"] = json!({ "description": "Posts", "content": { "application/json": { "schema": { "type": "array", "items": { "$ref": "#/components/schemas/Post" } } } } });
+
+    let mut video_responses = error_responses.clone();
+    video_responses["200"] = json!({ "description": "Full video", "content": { "video/*": { "schema": { "type": "string", "format": "binary" } } } });
+    video_responses["206"] = json!({ "description": "Requested range of the video", "content": { "video/*": { "schema": { "type": "string", "format": "binary" } } } });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Scrapn",
+            "description": "Instagram scraping API",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "components": {
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+                "adminToken": { "type": "apiKey", "in": "header", "name": "X-Admin-Token" }
+            },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": {
+                        "error": { "type": "string" },
+                        "message": { "type": "string" },
+                        "code": { "type": "string", "description": "Stable machine-readable error code, e.g. PROFILE_NOT_FOUND" }
+                    }
+                },
+                "ImageVersion": {
+                    "type": "object",
+                    "properties": {
+                        "width": { "type": "integer" },
+                        "height": { "type": "integer" },
+                        "url": { "type": "string" }
+                    }
+                },
+                "InstagramPost": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "shortcode": { "type": "string" },
+                        "displayUrl": { "type": "string", "nullable": true, "description": "Main image, null when Instagram returned no image URL" },
+                        "thumbnailUrl": { "type": "string", "nullable": true },
+                        "resolutions": { "type": "array", "items": { "$ref": "#/components/schemas/ImageVersion" } },
+                        "caption": { "type": "string", "nullable": true },
+                        "altText": { "type": "string", "nullable": true, "description": "Accessibility caption of the image, for alt attributes" },
+                        "hashtags": { "type": "array", "items": { "type": "string" } },
+                        "mentions": { "type": "array", "items": { "type": "string" } },
+                        "likesCount": { "type": "integer", "nullable": true },
+                        "commentsCount": { "type": "integer", "nullable": true },
+                        "timestamp": { "type": "string", "format": "date-time", "nullable": true },
+                        "timestampUnix": { "type": "integer", "nullable": true, "description": "Same as timestamp, in seconds since the Unix epoch" },
+                        "isVideo": { "type": "boolean" },
+                        "videoUrl": { "type": "string", "nullable": true },
+                        "videoViewCount": { "type": "integer", "nullable": true },
+                        "videoDuration": { "type": "number", "nullable": true },
+                        "videoWidth": { "type": "integer", "nullable": true },
+                        "videoHeight": { "type": "integer", "nullable": true },
+                        "carousel": { "type": "array", "items": { "$ref": "#/components/schemas/InstagramCarouselItem" } }
+                    }
+                },
+                "InstagramCarouselItem": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "displayUrl": { "type": "string" },
+                        "isVideo": { "type": "boolean" },
+                        "videoUrl": { "type": "string", "nullable": true },
+                        "width": { "type": "integer", "nullable": true },
+                        "height": { "type": "integer", "nullable": true }
+                    }
+                },
+                "InstagramReel": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "shortcode": { "type": "string" },
+                        "displayUrl": { "type": "string", "nullable": true },
+                        "videoUrl": { "type": "string", "nullable": true },
+                        "caption": { "type": "string", "nullable": true },
+                        "hashtags": { "type": "array", "items": { "type": "string" } },
+                        "mentions": { "type": "array", "items": { "type": "string" } },
+                        "viewsCount": { "type": "integer", "nullable": true },
+                        "likesCount": { "type": "integer", "nullable": true },
+                        "commentsCount": { "type": "integer", "nullable": true },
+                        "timestamp": { "type": "string", "format": "date-time", "nullable": true },
+                        "timestampUnix": { "type": "integer", "nullable": true, "description": "Same as timestamp, in seconds since the Unix epoch" },
+                        "duration": { "type": "number", "nullable": true },
+                        "width": { "type": "integer", "nullable": true },
+                        "height": { "type": "integer", "nullable": true }
+                    }
+                },
+                "InstagramUserStats": {
+                    "type": "object",
+                    "properties": {
+                        "postsCount": { "type": "integer", "nullable": true },
+                        "followersCount": { "type": "integer", "nullable": true },
+                        "followingCount": { "type": "integer", "nullable": true },
+                        "postsCountShort": { "type": "string", "nullable": true, "description": "Abbreviated count, e.g. 12.3K" },
+                        "followersCountShort": { "type": "string", "nullable": true, "description": "Abbreviated count, e.g. 1.2M" },
+                        "followingCountShort": { "type": "string", "nullable": true, "description": "Abbreviated count, e.g. 999" }
+                    }
+                },
+                "InstagramUser": {
+                    "type": "object",
+                    "properties": {
+                        "username": { "type": "string" },
+                        "fullName": { "type": "string", "nullable": true },
+                        "biography": { "type": "string", "nullable": true },
+                        "profilePicUrl": { "type": "string", "nullable": true },
+                        "isPrivate": { "type": "boolean" },
+                        "isVerified": { "type": "boolean" },
+                        "externalUrl": { "type": "string", "nullable": true },
+                        "category": { "type": "string", "nullable": true },
+                        "isBusiness": { "type": "boolean" },
+                        "accountType": { "type": "string", "nullable": true, "enum": ["personal", "business", "creator", null] },
+                        "businessContactMethod": { "type": "string", "nullable": true, "description": "Preferred contact method of a business account, lowercased (e.g. call, text)" },
+                        "stats": { "$ref": "#/components/schemas/InstagramUserStats" },
+                        "posts": { "type": "array", "nullable": true, "items": { "$ref": "#/components/schemas/InstagramPost" } },
+                        "reels": { "type": "array", "nullable": true, "items": { "$ref": "#/components/schemas/InstagramReel" } },
+                        "scrapedAt": { "type": "string", "format": "date-time" },
+                        "postsLimited": { "type": "boolean" }
+                    }
+                },
+                "InstagramUserResponse": {
+                    "type": "object",
+                    "properties": {
+                        "data": { "$ref": "#/components/schemas/InstagramUser" },
+                        "fromCache": { "type": "boolean" },
+                        "cacheAge": { "type": "integer", "nullable": true },
+                        "scrapeDurationMs": { "type": "integer", "nullable": true, "description": "Time taken by the scrape, retries included (null when served from the cache)" }
+                    }
+                },
+                "InstagramPostsResponse": {
+                    "type": "object",
+                    "properties": {
+                        "data": { "type": "array", "items": { "$ref": "#/components/schemas/InstagramPost" } },
+                        "fromCache": { "type": "boolean" },
+                        "cacheAge": { "type": "integer", "nullable": true },
+                        "limited": { "type": "boolean", "description": "More posts exist than the ones returned by Instagram" },
+                        "totalCount": { "type": "integer", "nullable": true, "description": "Total number of posts of the profile, when known" },
+                        "gap": { "type": "boolean", "description": "The after_shortcode post wasn't found, so all the posts are returned" }
+                    }
+                },
+                "InstagramPostResponse": {
+                    "type": "object",
+                    "properties": {
+                        "data": { "$ref": "#/components/schemas/InstagramPost" },
+                        "fromCache": { "type": "boolean" },
+                        "cacheAge": { "type": "integer", "nullable": true }
+                    }
+                },
+                "InstagramReelsResponse": {
+                    "type": "object",
+                    "properties": {
+                        "data": { "type": "array", "items": { "$ref": "#/components/schemas/InstagramReel" } },
+                        "fromCache": { "type": "boolean" },
+                        "cacheAge": { "type": "integer", "nullable": true },
+                        "limited": { "type": "boolean", "description": "More reels follow in the reels tab, or the reels come from an incomplete posts list" },
+                        "endCursor": { "type": "string", "nullable": true, "description": "Cursor of the next page of the reels tab (null on the last page, or for reels derived from the posts)" }
+                    }
+                },
+                "InstagramHighlight": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "title": { "type": "string" },
+                        "coverUrl": { "type": "string", "nullable": true },
+                        "mediaCount": { "type": "integer", "nullable": true }
+                    }
+                },
+                "InstagramHighlightsResponse": {
+                    "type": "object",
+                    "properties": {
+                        "data": { "type": "array", "items": { "$ref": "#/components/schemas/InstagramHighlight" } },
+                        "fromCache": { "type": "boolean" },
+                        "cacheAge": { "type": "integer", "nullable": true }
+                    }
+                },
+                "InstagramBatchResponse": {
+                    "type": "object",
+                    "properties": {
+                        "data": {
+                            "type": "object",
+                            "additionalProperties": {
+                                "oneOf": [
+                                    { "$ref": "#/components/schemas/InstagramUserResponse" },
+                                    { "type": "object", "properties": { "error": { "type": "string" }, "code": { "type": "string" } } }
+                                ]
+                            }
+                        }
+                    }
+                },
+                "InstagramImageVariantsResponse": {
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string" },
+                        "variants": {
+                            "type": "object",
+                            "description": "Data URL of each variant, keyed by its conversion params",
+                            "additionalProperties": { "type": "string" }
+                        }
+                    }
+                },
+                "InstagramSignedImageResponse": {
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string", "description": "Path and query of the signed image URL, relative to the server" },
+                        "expires": { "type": "integer", "description": "Unix timestamp after which the URL is rejected" }
+                    }
+                },
+                "InstagramCachePurgeResponse": {
+                    "type": "object",
+                    "properties": {
+                        "username": { "type": "string" },
+                        "userPurged": { "type": "boolean" },
+                        "imagesPurged": { "type": "integer" }
+                    }
+                },
+                "Profile": {
+                    "type": "object",
+                    "properties": {
+                        "platform": { "type": "string" },
+                        "username": { "type": "string" },
+                        "fullName": { "type": "string", "nullable": true },
+                        "biography": { "type": "string", "nullable": true },
+                        "avatarUrl": { "type": "string", "nullable": true },
+                        "isPrivate": { "type": "boolean" },
+                        "isVerified": { "type": "boolean" },
+                        "externalUrl": { "type": "string", "nullable": true },
+                        "postsCount": { "type": "integer", "nullable": true },
+                        "followersCount": { "type": "integer", "nullable": true },
+                        "followingCount": { "type": "integer", "nullable": true },
+                        "scrapedAt": { "type": "string", "format": "date-time" }
+                    }
+                },
+                "Post": {
+                    "type": "object",
+                    "properties": {
+                        "platform": { "type": "string" },
+                        "id": { "type": "string" },
+                        "code": { "type": "string" },
+                        "mediaUrl": { "type": "string", "nullable": true },
+                        "thumbnailUrl": { "type": "string", "nullable": true },
+                        "videoUrl": { "type": "string", "nullable": true },
+                        "caption": { "type": "string", "nullable": true },
+                        "likesCount": { "type": "integer", "nullable": true },
+                        "commentsCount": { "type": "integer", "nullable": true },
+                        "viewsCount": { "type": "integer", "nullable": true },
+                        "timestamp": { "type": "string", "format": "date-time", "nullable": true },
+                        "isVideo": { "type": "boolean" }
+                    }
+                }
+            }
+        },
+        "security": [{ "apiKey": [] }],
+        "paths": {
+            "/instagram/{username}": {
+                "get": {
+                    "summary": "Get full profile data for an Instagram user",
+                    "parameters": [
+                        username.clone(),
+                        fresh.clone(),
+                        query("media", json!({ "type": "boolean", "default": true }), "Include the posts and reels (null when false, stats are kept)")
+                    ],
+                    "responses": with_errors(json!({ "description": "Profile", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramUserResponse" } } } }))
+                }
+            },
+            "/instagram/{username}/posts": {
+                "get": {
+                    "summary": "Get the posts of an Instagram user",
+                    "parameters": [
+                        username,
+                        query("since", json!({ "type": "string", "format": "date-time" }), "Only posts published at or after this RFC3339 date"),
+                        query("until", json!({ "type": "string", "format": "date-time" }), "Only posts published at or before this RFC3339 date"),
+                        query("limit", json!({ "type": "integer", "minimum": 0 }), "Maximum number of posts"),
+                        query("exclude_undated", json!({ "type": "boolean" }), "Drop posts without a timestamp"),
+                        query("order", json!({ "type": "string", "enum": ["asc", "desc"], "default": "desc" }), "Sort by timestamp, undated posts last"),
+                        query("after_shortcode", json!({ "type": "string" }), "Only posts newer than the post with this shortcode (all posts, with gap = true, when it isn't found)"),
+                        fresh.clone()
+                    ],
+                    "responses": with_errors(json!({ "description": "Posts", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramPostsResponse" } } } }))
+                }
+            },
+            "/instagram/{username}/posts/{shortcode}": {
+                "get": {
+                    "summary": "Get a single post of an Instagram user, including its carousel media",
+                    "parameters": [
+                        username,
+                        { "name": "shortcode", "in": "path", "required": true, "schema": { "type": "string" } }
+                    ],
+                    "responses": with_errors(json!({ "description": "Post", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramPostResponse" } } } }))
+                }
+            },
+            "/instagram/{username}/reels": {
+                "get": {
+                    "summary": "Get the reels of an Instagram user (reels tab, or video posts when it can't be fetched)",
+                    "parameters": [
+                        username.clone(),
+                        fresh,
+                        query("limit", json!({ "type": "integer", "minimum": 1, "maximum": 50 }), "Number of reels per page of the reels tab (capped to 50), always scraped"),
+                        query("cursor", json!({ "type": "string" }), "endCursor of the previous page, to get the next one (always scraped)")
+                    ],
+                    "responses": with_errors(json!({ "description": "Reels", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramReelsResponse" } } } }))
+                }
+            },
+            "/instagram/{username}/highlights": {
+                "get": {
+                    "summary": "Get the highlights of an Instagram user (requires Instagram cookies)",
+                    "parameters": [username],
+                    "responses": with_errors(json!({ "description": "Highlights", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramHighlightsResponse" } } } }))
+                }
+            },
+            "/instagram/{username}/tagged": {
+                "get": {
+                    "summary": "Get the posts an Instagram user is tagged in",
+                    "parameters": [username],
+                    "responses": with_errors(json!({ "description": "Tagged posts", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramPostsResponse" } } } }))
+                }
+            },
+            "/instagram/{username}/image": {
+                "get": {
+                    "summary": "Proxy and optionally convert an image of an Instagram user",
+                    "parameters": image_parameters.clone(),
+                    "responses": with_errors(json!({ "description": "Image, or its data URL with encoding=base64", "content": {
+                        "image/*": { "schema": { "type": "string", "format": "binary" } },
+                        "application/json": { "schema": { "type": "object", "properties": { "dataUrl": { "type": "string", "description": "data:<type>;base64,<data>" } } } }
+                    } }))
+                },
+                "head": {
+                    "summary": "Get the headers (type, length, ETag) of a proxied image without its body, from the cache when available, otherwise fetched",
+                    "parameters": image_parameters,
+                    "responses": with_errors(json!({ "description": "Image headers" }))
+                }
+            },
+            "/instagram/{username}/image/signed": {
+                "get": {
+                    "summary": "Get a short-lived signed URL of the image route with the same parameters (requires an image signing secret)",
+                    "parameters": signed_image_parameters,
+                    "responses": signed_image_responses
+                }
+            },
+            "/instagram/{username}/image/variants": {
+                "post": {
+                    "summary": "Generate several variants of an image of an Instagram user, fetching and decoding it once",
+                    "parameters": [username.clone()],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": {
+                            "type": "object",
+                            "required": ["url", "variants"],
+                            "properties": {
+                                "url": { "type": "string", "description": "Instagram CDN URL of the image" },
+                                "variants": { "type": "array", "minItems": 1, "maxItems": 10, "items": { "type": "object", "properties": image_variant } }
+                            }
+                        } } }
+                    },
+                    "responses": image_variants_responses
+                }
+            },
+            "/instagram/{username}/avatar": {
+                "get": {
+                    "summary": "Get the profile picture of an Instagram user through the image proxy",
+                    "parameters": avatar_parameters,
+                    "responses": with_errors(json!({ "description": "Image", "content": { "image/*": { "schema": { "type": "string", "format": "binary" } } } }))
+                }
+            },
+            "/instagram/{username}/video": {
+                "get": {
+                    "summary": "Stream a video of an Instagram user, with range request support",
+                    "parameters": [
+                        username.clone(),
+                        json!({ "name": "url", "in": "query", "required": true, "schema": { "type": "string" }, "description": "Instagram CDN URL of the video (must belong to the user)" }),
+                        json!({ "name": "Range", "in": "header", "required": false, "schema": { "type": "string" }, "description": "Byte range to fetch, e.g. `bytes=0-1023`" })
+                    ],
+                    "responses": video_responses
+                }
+            },
+            "/instagram/batch": {
+                "post": {
+                    "summary": "Get full profile data for several users at once",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": { "type": "array", "items": { "type": "string" }, "maxItems": 50 } } }
+                    },
+                    "responses": {
+                        "200": { "description": "Profiles or errors by username", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramBatchResponse" } } } },
+                        "401": { "description": "Missing or invalid API key" },
+                        "429": { "description": "Rate limited" }
+                    }
+                }
+            },
+            "/instagram/{username}/cache": {
+                "delete": {
+                    "summary": "Purge the cached data of a user",
+                    "security": [{ "adminToken": [] }],
+                    "parameters": [
+                        username,
+                        query("images", json!({ "type": "boolean" }), "Also purge the cached images of the user")
+                    ],
+                    "responses": {
+                        "200": { "description": "Purge result", "content": { "application/json": { "schema": { "$ref": "#/components/schemas/InstagramCachePurgeResponse" } } } },
+                        "401": { "description": "Missing or invalid admin token" }
+                    }
+                }
+            },
+            "/profiles/{platform}/{username}": {
+                "get": {
+                    "summary": "Get the platform-agnostic profile of a user, scraped without cache",
+                    "parameters": [platform.clone(), username.clone()],
+                    "responses": profile_responses
+                }
+            },
+            "/profiles/{platform}/{username}/posts": {
+                "get": {
+                    "summary": "Get the platform-agnostic latest posts of a user, scraped without cache",
+                    "parameters": [platform, username.clone()],
+                    "responses": profile_posts_responses
+                }
+            },
+            "/admin/proxies": {
+                "get": {
+                    "summary": "Get the state of every proxy",
+                    "security": [{ "adminToken": [] }],
+                    "responses": {
+                        "200": { "description": "Proxies state" },
+                        "401": { "description": "Missing or invalid admin token" }
+                    }
+                }
+            },
+            "/admin/stats": {
+                "get": {
+                    "summary": "Get cache, proxy and scrape counters",
+                    "security": [{ "adminToken": [] }],
+                    "responses": {
+                        "200": { "description": "Service stats" },
+                        "401": { "description": "Missing or invalid admin token" }
+                    }
+                }
+            },
+            "/admin/preload": {
+                "post": {
+                    "summary": "Scrape profiles into the cache ahead of a traffic spike",
+                    "security": [{ "adminToken": [] }],
+                    "parameters": [
+                        query("fresh", json!({ "type": "boolean" }), "Scrape profiles even when they are already cached")
+                    ],
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/json": { "schema": { "type": "array", "items": { "type": "string" } } } }
+                    },
+                    "responses": {
+                        "200": { "description": "Outcome by username: status cached, scraped, or failed with an error and its code" },
+                        "401": { "description": "Missing or invalid admin token" }
+                    }
+                }
+            },
+            "/admin/test-proxy": {
+                "get": {
+                    "summary": "Scrape a profile through exactly the given proxy, bypassing rotation, to debug it",
+                    "security": [{ "adminToken": [] }],
+                    "parameters": [
+                        json!({ "name": "url", "in": "query", "required": true, "schema": { "type": "string" }, "description": "Proxy URL, configured or not" }),
+                        json!({ "name": "username", "in": "query", "required": true, "schema": { "type": "string" } })
+                    ],
+                    "responses": {
+                        "200": { "description": "Proxy, username, durationMs and status: succeeded with the scraped user, or failed with an error and its code" },
+                        "401": { "description": "Missing or invalid admin token" }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[get("/openapi.json")]
+pub fn openapi() -> Json<Value> {
+    Json(spec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+
+    // Every `$ref` in a value
+    fn refs(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::String(reference)) = object.get("$ref") {
+                    found.push(reference.clone());
+                }
+                object.values().for_each(|value| refs(value, found));
+            }
+            Value::Array(array) => array.iter().for_each(|value| refs(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn spec_lists_the_instagram_routes() {
+        let client = Client::tracked(rocket::build().mount("/", rocket::routes![openapi])).unwrap();
+        let response = client.get("/openapi.json").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let spec: Value = response.into_json().unwrap();
+
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        for path in ["/instagram/{username}", "/instagram/{username}/posts", "/instagram/{username}/reels", "/instagram/{username}/image"] {
+            assert!(spec["paths"][path]["get"].is_object(), "{} is missing", path);
+        }
+    }
+
+    #[test]
+    fn spec_references_resolve() {
+        let spec = spec();
+        let mut found = Vec::new();
+        refs(&spec, &mut found);
+        assert!(!found.is_empty());
+        for reference in found {
+            let name = reference.strip_prefix("#/components/schemas/").unwrap();
+            assert!(spec["components"]["schemas"][name].is_object(), "{} is not defined", reference);
+        }
+    }
+}
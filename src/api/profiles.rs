@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use rocket::serde::json::Json;
+use rocket::State;
+
+use crate::api::auth::ApiClient;
+use crate::api::ApiError;
+use crate::config::AppConfig;
+use crate::models::common::{Platform, Post, Profile};
+use crate::scrapers::{Scraper, ScraperError, ScraperRegistry};
+
+// Scraper of a platform given by name, after checking that the username may be scraped
+fn platform_scraper(
+    platform: &str,
+    username: &str,
+    registry: &ScraperRegistry,
+    config: &AppConfig,
+) -> Result<Arc<dyn Scraper>, ApiError> {
+    let platform = Platform::from_name(platform)
+        .ok_or_else(|| ApiError::BadRequest(format!("Unsupported platform: {}", platform)))?;
+    let scraper = registry.get(platform)
+        .ok_or_else(|| ApiError::NotConfigured(format!("No scraper registered for {:?}", platform)))?;
+
+    // Blocklist and whitelist check
+    if !config.username_allowed(username) {
+        return Err(ApiError::ScraperError(ScraperError::UnauthorizedAccess(format!("Username '{}' not allowed", username))));
+    }
+
+    Ok(scraper)
+}
+
+/// Platform-agnostic profile of a user, scraped through the scraper registered for the platform (uncached)
+#[get("/<platform>/<username>")]
+pub async fn get_profile(
+    platform: &str,
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    registry: &State<ScraperRegistry>,
+    config: &State<AppConfig>,
+) -> Result<Json<Profile>, ApiError> {
+    client?;
+
+    let scraper = platform_scraper(platform, username, registry, config)?;
+    Ok(Json(scraper.scrape_user(username).await?))
+}
+
+/// Platform-agnostic latest posts of a user, scraped through the scraper registered for the platform (uncached)
+#[get("/<platform>/<username>/posts")]
+pub async fn get_profile_posts(
+    platform: &str,
+    username: &str,
+    client: Result<ApiClient, ApiError>,
+    registry: &State<ScraperRegistry>,
+    config: &State<AppConfig>,
+) -> Result<Json<Vec<Post>>, ApiError> {
+    client?;
+
+    let scraper = platform_scraper(platform, username, registry, config)?;
+    Ok(Json(scraper.scrape_posts(username).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+
+    fn client(registry: ScraperRegistry, config: AppConfig) -> Client {
+        let rocket = rocket::build()
+            .manage(registry)
+            .manage(config)
+            .mount("/profiles", routes![get_profile, get_profile_posts]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn unsupported_platform_is_rejected() {
+        let client = client(ScraperRegistry::new(), AppConfig::default());
+        let response = client.get("/profiles/tiktok/someone").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn unregistered_platform_is_not_configured() {
+        let client = client(ScraperRegistry::new(), AppConfig::default());
+        let response = client.get("/profiles/instagram/someone/posts").dispatch();
+        assert_eq!(response.status(), Status::NotImplemented);
+    }
+}
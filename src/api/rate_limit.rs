@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+
+// Idle buckets are dropped after this period (a bucket idle for a full minute is back to full capacity anyway)
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+struct Buckets {
+    entries: HashMap<String, Bucket>,
+    last_cleanup: Instant,
+}
+
+/// Token-bucket rate limiter keyed by client identity (API key or IP address).
+/// Each client can burst up to `per_minute` requests, refilled continuously over a minute.
+pub struct RateLimiter {
+    per_minute: Option<u32>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter, disabled when `per_minute` is None
+    pub fn new(per_minute: Option<u32>) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(Buckets {
+                entries: HashMap::new(),
+                last_cleanup: Instant::now(),
+            }),
+        }
+    }
+
+    /// Consume a token for the client, or return the number of seconds to wait before retrying
+    pub fn check(&self, client: &str) -> Result<(), u64> {
+        let per_minute = match self.per_minute {
+            Some(per_minute) => per_minute as f64,
+            None => return Ok(()),
+        };
+        let refill_per_sec = per_minute / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock();
+
+        // Periodically drop buckets of clients that have been idle long enough to be full again
+        if now.duration_since(buckets.last_cleanup) >= CLEANUP_INTERVAL {
+            buckets
+                .entries
+                .retain(|_, bucket| now.duration_since(bucket.last_refill) < CLEANUP_INTERVAL);
+            buckets.last_cleanup = now;
+        }
+
+        let bucket = buckets.entries.entry(client.to_string()).or_insert(Bucket {
+            tokens: per_minute,
+            last_refill: now,
+        });
+
+        // Refill according to elapsed time
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(per_minute);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / refill_per_sec).ceil().max(1.0) as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_over_the_limit_is_rejected() {
+        let limiter = RateLimiter::new(Some(3));
+        for _ in 0..3 {
+            assert_eq!(limiter.check("ip:1.2.3.4"), Ok(()));
+        }
+        // One token comes back every 20 seconds
+        assert_eq!(limiter.check("ip:1.2.3.4"), Err(20));
+        // Buckets are per client
+        assert_eq!(limiter.check("ip:5.6.7.8"), Ok(()));
+    }
+
+    #[test]
+    fn disabled_limiter_accepts_everything() {
+        let limiter = RateLimiter::new(None);
+        for _ in 0..1000 {
+            assert_eq!(limiter.check("ip:1.2.3.4"), Ok(()));
+        }
+    }
+}
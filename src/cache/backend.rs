@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use parking_lot::RwLock;
+
+/// Storage used by the caches. Entries never expire at the storage level:
+/// freshness is decided by the caches from the entry age, so stale data stays
+/// available as a fallback when scraping fails.
+pub trait CacheBackend<V>: Send + Sync {
+    /// Get a value along with its age
+    fn get(&self, key: &str) -> Option<(V, Duration)>;
+
+    fn store(&self, key: &str, value: V);
+
+    /// Remove a value, returning it if it was present
+    fn remove(&self, key: &str) -> Option<V>;
+
+    /// Remove every value whose key matches the predicate, returning the number of removed entries
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) -> usize;
+
+    /// Number of entries and total size in bytes of their values (as measured by `size`),
+    /// None when the backend can't tell cheaply
+    fn usage(&self, _size: &dyn Fn(&V) -> u64) -> Option<(usize, u64)> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry<T> {
+    pub data: T,
+    pub inserted_at: Instant,
+}
+
+impl<T: Clone> CacheEntry<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            data,
+            inserted_at: Instant::now(),
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        Instant::now().saturating_duration_since(self.inserted_at)
+    }
+}
+
+// In-memory backend (default), local to the instance
+pub struct MemoryBackend<V> {
+    entries: RwLock<HashMap<String, CacheEntry<V>>>,
+}
+
+impl<V> MemoryBackend<V> {
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl<V: Clone + Send + Sync> CacheBackend<V> for MemoryBackend<V> {
+    fn get(&self, key: &str) -> Option<(V, Duration)> {
+        let entries = self.entries.read();
+        entries.get(key).map(|entry| (entry.data.clone(), entry.age()))
+    }
+
+    fn store(&self, key: &str, value: V) {
+        let mut entries = self.entries.write();
+        entries.insert(key.to_string(), CacheEntry::new(value));
+    }
+
+    fn remove(&self, key: &str) -> Option<V> {
+        let mut entries = self.entries.write();
+        entries.remove(key).map(|entry| entry.data)
+    }
+
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) -> usize {
+        let mut entries = self.entries.write();
+        let before = entries.len();
+        entries.retain(|key, _| !predicate(key));
+        before - entries.len()
+    }
+
+    fn usage(&self, size: &dyn Fn(&V) -> u64) -> Option<(usize, u64)> {
+        let entries = self.entries.read();
+        Some((entries.len(), entries.values().map(|entry| size(&entry.data)).sum()))
+    }
+}
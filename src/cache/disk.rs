@@ -0,0 +1,209 @@
+use std::fs::{self, File, FileTimes};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use crate::cache::backend::CacheBackend;
+use crate::cache::image::CachedImage;
+
+// Extensions of the files storing an image and its metadata
+const DATA_EXTENSION: &str = "bin";
+const META_EXTENSION: &str = "meta";
+
+// Metadata stored next to each image, the file name being a hash of the key
+#[derive(Serialize, Deserialize)]
+struct DiskMeta {
+    key: String,
+    content_type: String,
+    #[serde(default)]
+    etag: Option<String>,
+}
+
+/// Image backend storing each image as a file in a directory, so that images survive restarts.
+/// The data file modification time is the storage time and its access time is bumped on every
+/// read, so that the least recently used images can be evicted by `enforce_limit`.
+#[derive(Clone)]
+pub struct DiskBackend {
+    dir: PathBuf,
+}
+
+impl DiskBackend {
+    pub fn new(dir: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self { dir: PathBuf::from(dir) })
+    }
+
+    fn path(&self, key: &str, extension: &str) -> PathBuf {
+        self.dir.join(format!("{:x}.{}", md5::compute(key), extension))
+    }
+
+    fn read_meta(path: &Path) -> Option<DiskMeta> {
+        serde_json::from_slice(&fs::read(path).ok()?).ok()
+    }
+
+    fn remove_files(&self, key: &str) {
+        for extension in [DATA_EXTENSION, META_EXTENSION] {
+            if let Err(e) = fs::remove_file(self.path(key, extension)) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::warn!("Failed to remove cached image file: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Delete the least recently accessed images until the directory holds at most `max_bytes`,
+    /// returning the number of deleted images
+    pub fn enforce_limit(&self, max_bytes: u64) -> usize {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to list image cache directory {}: {}", self.dir.display(), e);
+                return 0;
+            }
+        };
+
+        // Size of each image (data and metadata) with its last access time
+        let mut files: Vec<(PathBuf, u64, SystemTime)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == DATA_EXTENSION))
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok()?;
+                let meta_size = fs::metadata(path.with_extension(META_EXTENSION)).map_or(0, |m| m.len());
+                let accessed = metadata.accessed().or_else(|_| metadata.modified()).ok()?;
+                Some((path, metadata.len() + meta_size, accessed))
+            })
+            .collect();
+
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        if total <= max_bytes {
+            return 0;
+        }
+
+        files.sort_by_key(|(_, _, accessed)| *accessed);
+
+        let mut deleted = 0;
+        for (path, size, _) in files {
+            if total <= max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                let _ = fs::remove_file(path.with_extension(META_EXTENSION));
+                total = total.saturating_sub(size);
+                deleted += 1;
+            }
+        }
+
+        log::info!("Image cache over its disk limit, deleted {} least recently used images", deleted);
+        deleted
+    }
+}
+
+impl CacheBackend<CachedImage> for DiskBackend {
+    fn get(&self, key: &str) -> Option<(CachedImage, Duration)> {
+        let meta = Self::read_meta(&self.path(key, META_EXTENSION))?;
+        // Guard against hash collisions
+        if meta.key != key {
+            return None;
+        }
+
+        let data_path = self.path(key, DATA_EXTENSION);
+        let data = fs::read(&data_path).ok()?;
+        let file = File::options().write(true).open(&data_path).ok()?;
+        let stored_at = file.metadata().and_then(|m| m.modified()).ok()?;
+        // Record the access for the eviction (most file systems don't update it on read)
+        let _ = file.set_times(FileTimes::new().set_accessed(SystemTime::now()));
+
+        let age = SystemTime::now().duration_since(stored_at).unwrap_or_default();
+        Some(((Arc::new(data), meta.content_type, meta.etag), age))
+    }
+
+    fn store(&self, key: &str, value: CachedImage) {
+        let (data, content_type, etag) = value;
+        let meta = DiskMeta { key: key.to_string(), content_type, etag };
+
+        let result = serde_json::to_vec(&meta)
+            .map_err(io::Error::other)
+            .and_then(|meta| fs::write(self.path(key, META_EXTENSION), meta))
+            .and_then(|_| fs::write(self.path(key, DATA_EXTENSION), data.as_slice()));
+        if let Err(e) = result {
+            log::warn!("Failed to write cached image to {}: {}", self.dir.display(), e);
+        }
+    }
+
+    fn remove(&self, key: &str) -> Option<CachedImage> {
+        let removed = self.get(key).map(|(image, _)| image);
+        self.remove_files(key);
+        removed
+    }
+
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) -> usize {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+
+        let keys: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == META_EXTENSION))
+            .filter_map(|path| Self::read_meta(&path))
+            .map(|meta| meta.key)
+            .filter(|key| predicate(key))
+            .collect();
+
+        for key in &keys {
+            self.remove_files(key);
+        }
+        keys.len()
+    }
+
+    // Measured from the data files, without reading them
+    fn usage(&self, _size: &dyn Fn(&CachedImage) -> u64) -> Option<(usize, u64)> {
+        let entries = fs::read_dir(&self.dir).ok()?;
+        let sizes: Vec<u64> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == DATA_EXTENSION))
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .collect();
+        Some((sizes.len(), sizes.iter().sum()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(size: usize) -> CachedImage {
+        (Arc::new(vec![0; size]), "image/jpeg".to_string(), None)
+    }
+
+    // Pretend that the image was last read the given number of seconds ago
+    fn set_accessed(backend: &DiskBackend, key: &str, secs_ago: u64) {
+        let file = File::options().write(true).open(backend.path(key, DATA_EXTENSION)).unwrap();
+        file.set_times(FileTimes::new().set_accessed(SystemTime::now() - Duration::from_secs(secs_ago))).unwrap();
+    }
+
+    #[test]
+    fn exceeding_the_limit_deletes_the_least_recently_accessed_image() {
+        let dir = std::env::temp_dir().join(format!("scrapn-disk-cache-{}", std::process::id()));
+        let backend = DiskBackend::new(dir.to_str().unwrap()).unwrap();
+        for (key, secs_ago) in [("old", 300), ("recent", 100), ("new", 0)] {
+            backend.store(key, image(1000));
+            set_accessed(&backend, key, secs_ago);
+        }
+        let (_, total) = backend.usage(&|_| 0).unwrap();
+
+        assert_eq!(backend.enforce_limit(u64::MAX), 0);
+        assert_eq!(backend.enforce_limit(total + 500), 0);
+        assert_eq!(backend.enforce_limit(total), 1);
+        assert!(backend.get("old").is_none());
+        assert!(backend.get("recent").is_some());
+        assert!(backend.get("new").is_some());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
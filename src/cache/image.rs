@@ -1,32 +1,150 @@
-use std::collections::HashMap;
-use parking_lot::RwLock;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+use parking_lot::Mutex;
+use crate::cache::backend::{CacheBackend, MemoryBackend};
 use crate::images::ImageConversionParams;
 
-// Image cache for proxied images - stored in memory forever
+// Image data, its content type and its ETag (computed when stored, missing from entries stored by older versions).
+// The data is shared between the entries holding identical bytes.
+pub type CachedImage = (Arc<Vec<u8>>, String, Option<String>);
+
+/// ETag of an image, a hash of its data
+pub fn image_etag(data: &[u8]) -> String {
+    format!("\"{:x}\"", md5::compute(data))
+}
+
+// Image cache for proxied images - stored forever
 pub struct ImageCache {
-    images: RwLock<HashMap<String, (Vec<u8>, String)>>,
+    images: Box<dyn CacheBackend<CachedImage>>,
+    // Data of the stored images by ETag (a hash of the content), so that variants with identical bytes
+    // (e.g. a small source requested at two larger sizes) are held once in memory
+    buffers: Mutex<SharedBuffers>,
+}
+
+// Buffers indexed by ETag, whose entries of removed images are pruned once the index doubled since the last pruning
+#[derive(Default)]
+struct SharedBuffers {
+    by_etag: HashMap<String, Weak<Vec<u8>>>,
+    prune_at: usize,
 }
 
+// Size of the buffer index under which it isn't pruned
+const MIN_BUFFERS_PRUNE_SIZE: usize = 1024;
+
 impl ImageCache {
     pub fn new() -> Self {
-        Self {
-            images: RwLock::new(HashMap::new()),
-        }
+        Self::with_backend(Box::new(MemoryBackend::new()))
+    }
+
+    pub fn with_backend(images: Box<dyn CacheBackend<CachedImage>>) -> Self {
+        Self { images, buffers: Mutex::new(SharedBuffers::default()) }
     }
 
-    pub fn get_image(&self, url: &str, params: &ImageConversionParams) -> Option<(Vec<u8>, String)> {
+    pub fn get_image(&self, url: &str, params: &ImageConversionParams) -> Option<CachedImage> {
         let cache_key = self.generate_cache_key(url, params);
-        let images = self.images.read();
-        images.get(&cache_key).cloned()
+        self.images.get(&cache_key).map(|(image, _)| image)
     }
 
     pub fn store_image(&self, url: &str, params: &ImageConversionParams, data: Vec<u8>, content_type: String) {
         let cache_key = self.generate_cache_key(url, params);
-        let mut images = self.images.write();
-        images.insert(cache_key, (data, content_type));
+        // Hash once here rather than on every cache hit
+        let etag = image_etag(&data);
+        let data = self.shared_buffer(&etag, data);
+        self.images.store(&cache_key, (data, content_type, Some(etag)));
+    }
+    
+    // Buffer of an already stored image with the same bytes if any, or the data itself (indexed for the next ones)
+    fn shared_buffer(&self, etag: &str, data: Vec<u8>) -> Arc<Vec<u8>> {
+        let mut buffers = self.buffers.lock();
+        if let Some(buffer) = buffers.by_etag.get(etag).and_then(Weak::upgrade) {
+            // Guard against hash collisions
+            if *buffer == data {
+                return buffer;
+            }
+        }
+        
+        // Replaces the entry of a removed image with the same bytes, if any
+        let buffer = Arc::new(data);
+        buffers.by_etag.insert(etag.to_string(), Arc::downgrade(&buffer));
+        
+        // Forget the buffers of the other images removed since, from time to time rather than on every store
+        if buffers.by_etag.len() > buffers.prune_at.max(MIN_BUFFERS_PRUNE_SIZE) {
+            buffers.by_etag.retain(|_, buffer| buffer.strong_count() > 0);
+            buffers.prune_at = buffers.by_etag.len() * 2;
+        }
+        buffer
+    }
+    
+    // Remove every variant of the images whose source URL matches the predicate, returning the number of removed entries
+    pub fn invalidate_where<F: Fn(&str) -> bool>(&self, predicate: F) -> usize {
+        self.images.remove_where(&|cache_key: &str| {
+            let url = cache_key.rsplit_once('#').map_or(cache_key, |(url, _)| url);
+            predicate(url)
+        })
+    }
+    
+    // Number of cached images (variants included) and their total size in bytes, None for shared backends.
+    // Buffers shared by several images are counted once.
+    pub fn usage(&self) -> Option<(usize, u64)> {
+        let counted = RefCell::new(HashSet::new());
+        self.images.usage(&|(data, _, _)| {
+            if counted.borrow_mut().insert(Arc::as_ptr(data)) { data.len() as u64 } else { 0 }
+        })
     }
     
     fn generate_cache_key(&self, url: &str, params: &ImageConversionParams) -> String {
         format!("{}#{}", url, params.to_cache_key())
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_etag_matches_the_data() {
+        let cache = ImageCache::new();
+        let params = ImageConversionParams::default();
+        cache.store_image("https://cdn/a.jpg", &params, b"jpeg bytes".to_vec(), "image/jpeg".to_string());
+
+        let (data, content_type, etag) = cache.get_image("https://cdn/a.jpg", &params).unwrap();
+        assert_eq!(content_type, "image/jpeg");
+        assert_eq!(etag, Some(image_etag(&data)));
+        assert_eq!(etag.as_deref(), Some("\"2ccd799f3a5130350478899447b6aa06\""));
+    }
+
+    #[test]
+    fn identical_bytes_are_held_once() {
+        let cache = ImageCache::new();
+        let data = vec![7u8; 1000];
+        let small = ImageConversionParams { width: Some(2000), ..Default::default() };
+        let large = ImageConversionParams { width: Some(4000), ..Default::default() };
+        cache.store_image("https://cdn/a.jpg", &small, data.clone(), "image/jpeg".to_string());
+        cache.store_image("https://cdn/a.jpg", &large, data.clone(), "image/jpeg".to_string());
+        assert_eq!(cache.usage(), Some((2, 1000)));
+
+        let (first, _, _) = cache.get_image("https://cdn/a.jpg", &small).unwrap();
+        let (second, _, _) = cache.get_image("https://cdn/a.jpg", &large).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        cache.store_image("https://cdn/b.jpg", &small, vec![8u8; 500], "image/jpeg".to_string());
+        assert_eq!(cache.usage(), Some((3, 1500)));
+    }
+
+    #[test]
+    fn buffers_of_removed_images_are_pruned() {
+        let cache = ImageCache::new();
+        let params = ImageConversionParams::default();
+        for index in 0..MIN_BUFFERS_PRUNE_SIZE {
+            cache.store_image(&format!("https://cdn/{}.jpg", index), &params, index.to_be_bytes().to_vec(), "image/jpeg".to_string());
+        }
+        assert_eq!(cache.invalidate_where(|_| true), MIN_BUFFERS_PRUNE_SIZE);
+        assert_eq!(cache.buffers.lock().by_etag.len(), MIN_BUFFERS_PRUNE_SIZE);
+
+        // The next store goes over the pruning size, only its own buffer is left
+        cache.store_image("https://cdn/new.jpg", &params, b"new".to_vec(), "image/jpeg".to_string());
+        assert_eq!(cache.buffers.lock().by_etag.len(), 1);
+        assert_eq!(cache.usage(), Some((1, 3)));
+    }
+}
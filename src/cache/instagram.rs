@@ -1,86 +1,129 @@
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
-use parking_lot::RwLock;
-use crate::models::instagram::{InstagramUser, InstagramPost, InstagramReel};
-
-#[derive(Debug, Clone)]
-pub struct CacheEntry<T> {
-    pub data: T,
-    pub inserted_at: Instant,
-    pub expires_at: Instant,
-}
-
-impl<T: Clone> CacheEntry<T> {
-    pub fn new(data: T, ttl: Duration) -> Self {
-        let now = Instant::now();
-        Self {
-            data,
-            inserted_at: now,
-            expires_at: now + ttl,
-        }
-    }
-
-    pub fn is_expired(&self) -> bool {
-        Instant::now() > self.expires_at
-    }
-
-    pub fn age(&self) -> Duration {
-        Instant::now().saturating_duration_since(self.inserted_at)
-    }
-}
+use std::time::Duration;
+use crate::cache::backend::{CacheBackend, MemoryBackend};
+use crate::models::instagram::{InstagramUser, InstagramPost, InstagramReel, InstagramReelsPage, InstagramHighlight};
+use crate::webhook::{ProfileDiff, WebhookNotifier};
 
 pub struct InstagramCache {
-    users: RwLock<HashMap<String, CacheEntry<InstagramUser>>>,
+    users: Box<dyn CacheBackend<InstagramUser>>,
+    // Posts of the users, stored apart so that they can expire before the profile
+    posts: Box<dyn CacheBackend<Vec<InstagramPost>>>,
+    highlights: Box<dyn CacheBackend<Vec<InstagramHighlight>>>,
+    tagged: Box<dyn CacheBackend<Vec<InstagramPost>>>,
+    // First page of the reels tab, which holds reels missing from the posts the user reels are derived from
+    clips: Box<dyn CacheBackend<InstagramReelsPage>>,
     pub cache_duration: Duration,
+    pub posts_cache_duration: Duration,
+    notifier: Option<WebhookNotifier>,
 }
 
 impl InstagramCache {
     pub fn new(cache_days: u64) -> Self {
+        Self::with_backends(
+            Box::new(MemoryBackend::new()),
+            Box::new(MemoryBackend::new()),
+            Box::new(MemoryBackend::new()),
+            Box::new(MemoryBackend::new()),
+            Box::new(MemoryBackend::new()),
+            cache_days,
+        )
+    }
+
+    pub fn with_backends(
+        users: Box<dyn CacheBackend<InstagramUser>>,
+        posts: Box<dyn CacheBackend<Vec<InstagramPost>>>,
+        highlights: Box<dyn CacheBackend<Vec<InstagramHighlight>>>,
+        tagged: Box<dyn CacheBackend<Vec<InstagramPost>>>,
+        clips: Box<dyn CacheBackend<InstagramReelsPage>>,
+        cache_days: u64,
+    ) -> Self {
+        let cache_duration = Duration::from_secs(cache_days * 24 * 60 * 60);
         Self {
-            users: RwLock::new(HashMap::new()),
-            cache_duration: Duration::from_secs(cache_days * 24 * 60 * 60),
+            users,
+            posts,
+            highlights,
+            tagged,
+            clips,
+            cache_duration,
+            posts_cache_duration: cache_duration,
+            notifier: None,
         }
     }
 
+    // Expire posts after their own duration instead of the profile one
+    pub fn with_posts_cache_hours(mut self, hours: u64) -> Self {
+        self.posts_cache_duration = Duration::from_secs(hours * 60 * 60);
+        self
+    }
+
+    // Notify profile changes detected when storing users
+    pub fn with_notifier(mut self, notifier: WebhookNotifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     pub fn get_user(&self, username: &str) -> Option<(InstagramUser, u64)> {
-        let users = self.users.read();
+        let (user, age) = self.users.get(username)?;
         
-        if let Some(entry) = users.get(username) {
-            if !entry.is_expired() {
-                return Some((entry.data.clone(), entry.age().as_secs()));
-            }
+        if age > self.cache_duration {
+            return None;
         }
         
-        None
+        Some((user, age.as_secs()))
     }
 
     pub fn get_user_even_expired(&self, username: &str) -> Option<(InstagramUser, u64)> {
-        let users = self.users.read();
+        self.users.get(username).map(|(user, age)| (user, age.as_secs()))
+    }
+
+    pub fn store_user(&self, user: InstagramUser) {
+        let username = user.username.clone();
         
-        if let Some(entry) = users.get(username) {
-            return Some((entry.data.clone(), entry.age().as_secs()));
+        // Compare with the previous data (even expired) to notify changes
+        if let Some(notifier) = &self.notifier {
+            if let Some((previous, _)) = self.users.get(&username) {
+                if let Some(diff) = ProfileDiff::between(&previous, &user) {
+                    notifier.notify(diff);
+                }
+            }
         }
         
-        None
+        if let Some(posts) = &user.posts {
+            self.posts.store(&username, posts.clone());
+        }
+        self.users.store(&username, user);
     }
 
-    pub fn store_user(&self, user: InstagramUser) {
-        let mut users = self.users.write();
-        users.insert(
-            user.username.clone(),
-            CacheEntry::new(user, self.cache_duration),
-        );
+    // Remove a user (and their posts, highlights, tagged posts and reels) from the cache, returning the removed user data if any
+    pub fn invalidate(&self, username: &str) -> Option<InstagramUser> {
+        self.posts.remove(username);
+        self.highlights.remove(username);
+        self.tagged.remove(username);
+        self.clips.remove(username);
+        self.users.remove(username)
+    }
+
+    // Number of cached users (expired ones included), None for shared backends
+    pub fn user_count(&self) -> Option<usize> {
+        self.users.usage(&|_| 0).map(|(count, _)| count)
     }
 
     pub fn get_posts(&self, username: &str) -> Option<(Vec<InstagramPost>, u64)> {
-        let (user, age) = self.get_user(username)?;
+        let (posts, age) = self.posts.get(username)?;
         
-        user.posts.map(|posts| (posts, age))
+        if age > self.posts_cache_duration {
+            return None;
+        }
+        
+        Some((posts, age.as_secs()))
     }
 
     pub fn get_posts_even_expired(&self, username: &str) -> Option<(Vec<InstagramPost>, u64)> {
-        let (user, age) = self.get_user_even_expired(username)?;
+        if let Some((posts, age)) = self.posts.get(username) {
+            return Some((posts, age.as_secs()));
+        }
         
+        // Users cached before posts had their own entry
+        let (user, age) = self.get_user_even_expired(username)?;
         user.posts.map(|posts| (posts, age))
     }
 
@@ -95,4 +138,172 @@ impl InstagramCache {
         
         user.reels.map(|reels| (reels, age))
     }
-} 
\ No newline at end of file
+
+    pub fn get_clips(&self, username: &str) -> Option<(InstagramReelsPage, u64)> {
+        let (page, age) = self.clips.get(username)?;
+        
+        if age > self.cache_duration {
+            return None;
+        }
+        
+        Some((page, age.as_secs()))
+    }
+
+    pub fn get_clips_even_expired(&self, username: &str) -> Option<(InstagramReelsPage, u64)> {
+        self.clips.get(username).map(|(page, age)| (page, age.as_secs()))
+    }
+
+    pub fn store_clips(&self, username: &str, page: InstagramReelsPage) {
+        self.clips.store(username, page);
+    }
+
+    pub fn get_highlights(&self, username: &str) -> Option<(Vec<InstagramHighlight>, u64)> {
+        let (highlights, age) = self.highlights.get(username)?;
+        
+        if age > self.cache_duration {
+            return None;
+        }
+        
+        Some((highlights, age.as_secs()))
+    }
+
+    pub fn get_highlights_even_expired(&self, username: &str) -> Option<(Vec<InstagramHighlight>, u64)> {
+        self.highlights.get(username).map(|(highlights, age)| (highlights, age.as_secs()))
+    }
+
+    pub fn store_highlights(&self, username: &str, highlights: Vec<InstagramHighlight>) {
+        self.highlights.store(username, highlights);
+    }
+
+    pub fn get_tagged(&self, username: &str) -> Option<(Vec<InstagramPost>, u64)> {
+        let (posts, age) = self.tagged.get(username)?;
+        
+        if age > self.cache_duration {
+            return None;
+        }
+        
+        Some((posts, age.as_secs()))
+    }
+
+    pub fn get_tagged_even_expired(&self, username: &str) -> Option<(Vec<InstagramPost>, u64)> {
+        self.tagged.get(username).map(|(posts, age)| (posts, age.as_secs()))
+    }
+
+    pub fn store_tagged(&self, username: &str, posts: Vec<InstagramPost>) {
+        self.tagged.store(username, posts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::test_server::{response, TestServer};
+
+    fn user(username: &str, shortcodes: &[&str]) -> InstagramUser {
+        let posts: Vec<serde_json::Value> = shortcodes.iter().map(|shortcode| json!({
+            "id": format!("id_{}", shortcode),
+            "shortcode": shortcode,
+            "displayUrl": null,
+            "thumbnailUrl": null,
+            "caption": null,
+            "likesCount": null,
+            "commentsCount": null,
+            "timestamp": null,
+            "isVideo": false,
+            "videoUrl": null,
+            "videoViewCount": null
+        })).collect();
+        serde_json::from_value(json!({
+            "username": username,
+            "fullName": null,
+            "biography": null,
+            "profilePicUrl": null,
+            "isPrivate": false,
+            "isVerified": false,
+            "externalUrl": null,
+            "stats": { "postsCount": shortcodes.len(), "followersCount": 10, "followingCount": 1 },
+            "posts": posts,
+            "reels": [],
+            "scrapedAt": "2024-01-01T00:00:00Z",
+            "postsLimited": false
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn new_post_triggers_one_webhook_call() {
+        let server = TestServer::start(vec![response(200, &[], b"")]);
+        let notifier = WebhookNotifier::new(format!("{}/hook", server.url), Some("secret".to_string()), 5);
+        let cache = InstagramCache::new(1).with_notifier(notifier);
+
+        // First scrape: nothing to compare with
+        cache.store_user(user("alice", &["a"]));
+        // Same data: no change
+        cache.store_user(user("alice", &["a"]));
+        cache.store_user(user("alice", &["b", "a"]));
+
+        for _ in 0..50 {
+            if !server.requests().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].line, "POST /hook HTTP/1.1");
+        assert!(requests[0].header("X-Scrapn-Signature").is_some_and(|signature| signature.starts_with("sha256=")));
+        let diff: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(diff["newPosts"][0]["shortcode"], "b");
+        assert!(diff["followersCount"].is_null());
+    }
+
+    // Memory backend whose entries all report the same age
+    struct AgedBackend<V> {
+        inner: MemoryBackend<V>,
+        age: Duration,
+    }
+
+    impl<V: Clone + Send + Sync> CacheBackend<V> for AgedBackend<V> {
+        fn get(&self, key: &str) -> Option<(V, Duration)> {
+            self.inner.get(key).map(|(value, _)| (value, self.age))
+        }
+
+        fn store(&self, key: &str, value: V) {
+            self.inner.store(key, value)
+        }
+
+        fn remove(&self, key: &str) -> Option<V> {
+            self.inner.remove(key)
+        }
+
+        fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) -> usize {
+            self.inner.remove_where(predicate)
+        }
+    }
+
+    fn aged<V>(age: Duration) -> Box<AgedBackend<V>> {
+        Box::new(AgedBackend { inner: MemoryBackend::new(), age })
+    }
+
+    #[test]
+    fn posts_expire_before_the_user() {
+        let two_hours = Duration::from_secs(2 * 60 * 60);
+        let cache = InstagramCache::with_backends(
+            aged(two_hours),
+            aged(two_hours),
+            Box::new(MemoryBackend::new()),
+            Box::new(MemoryBackend::new()),
+            Box::new(MemoryBackend::new()),
+            1,
+        )
+        .with_posts_cache_hours(1);
+        cache.store_user(user("alice", &["a"]));
+
+        assert!(cache.get_user("alice").is_some());
+        assert!(cache.get_posts("alice").is_none());
+        // Still available as a fallback
+        assert_eq!(cache.get_posts_even_expired("alice").unwrap().0.len(), 1);
+    }
+}
@@ -1,5 +1,9 @@
 mod instagram;
 mod image;
+pub mod backend;
+pub mod disk;
+#[cfg(feature = "redis")]
+pub mod redis;
 
 pub use instagram::InstagramCache;
-pub use image::ImageCache; 
\ No newline at end of file
+pub use image::{ImageCache, image_etag};
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use parking_lot::Mutex;
+use redis::{Client, Commands, Connection};
+use crate::cache::backend::CacheBackend;
+use crate::cache::image::CachedImage;
+use crate::models::instagram::{InstagramHighlight, InstagramPost, InstagramReelsPage, InstagramUser};
+
+// Fields of the companion key stored next to each value
+const STORED_AT_FIELD: &str = "stored_at";
+const CONTENT_TYPE_FIELD: &str = "content_type";
+const ETAG_FIELD: &str = "etag";
+
+/// Conversion of cached values to Redis payloads: the value itself is stored as binary
+/// under the key, along with optional fields (content type, ETag) kept in the companion key.
+pub trait RedisValue: Sized {
+    fn to_payload(&self) -> (Vec<u8>, Vec<(&'static str, String)>);
+    fn from_payload(payload: Vec<u8>, meta: &HashMap<String, String>) -> Option<Self>;
+}
+
+// Values stored as JSON
+macro_rules! json_redis_value {
+    ($($type:ty),*) => {
+        $(
+            impl RedisValue for $type {
+                fn to_payload(&self) -> (Vec<u8>, Vec<(&'static str, String)>) {
+                    (serde_json::to_vec(self).unwrap_or_default(), Vec::new())
+                }
+
+                fn from_payload(payload: Vec<u8>, _: &HashMap<String, String>) -> Option<Self> {
+                    serde_json::from_slice(&payload).ok()
+                }
+            }
+        )*
+    };
+}
+
+json_redis_value!(InstagramUser, Vec<InstagramHighlight>, Vec<InstagramPost>, InstagramReelsPage);
+
+impl RedisValue for CachedImage {
+    fn to_payload(&self) -> (Vec<u8>, Vec<(&'static str, String)>) {
+        let mut fields = vec![(CONTENT_TYPE_FIELD, self.1.clone())];
+        if let Some(etag) = &self.2 {
+            fields.push((ETAG_FIELD, etag.clone()));
+        }
+        (self.0.to_vec(), fields)
+    }
+
+    fn from_payload(payload: Vec<u8>, meta: &HashMap<String, String>) -> Option<Self> {
+        Some((Arc::new(payload), meta.get(CONTENT_TYPE_FIELD)?.clone(), meta.get(ETAG_FIELD).cloned()))
+    }
+}
+
+/// Redis backend, shared between instances. Values are stored under `<prefix><key>`
+/// with their metadata (storage time, content type) in the `<prefix><key>:meta` hash.
+/// Redis failures are logged and handled as cache misses.
+pub struct RedisBackend {
+    client: Client,
+    connection: Mutex<Option<Connection>>,
+    prefix: String,
+}
+
+impl RedisBackend {
+    pub fn new(url: &str, prefix: &str) -> Result<Self, redis::RedisError> {
+        let client = Client::open(url)?;
+        let connection = client.get_connection()?;
+
+        Ok(Self {
+            client,
+            connection: Mutex::new(Some(connection)),
+            prefix: prefix.to_string(),
+        })
+    }
+
+    // Run a command on the shared connection, reconnecting if the previous one failed
+    fn run<T, F>(&self, command: F) -> Option<T>
+    where
+        F: FnOnce(&mut Connection) -> redis::RedisResult<T>,
+    {
+        let mut connection = self.connection.lock();
+
+        if connection.is_none() {
+            match self.client.get_connection() {
+                Ok(new_connection) => *connection = Some(new_connection),
+                Err(e) => {
+                    log::warn!("Failed to connect to Redis: {}", e);
+                    return None;
+                }
+            }
+        }
+
+        match command(connection.as_mut()?) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                log::warn!("Redis command failed: {}", e);
+                // Drop the connection so the next command reconnects
+                *connection = None;
+                None
+            }
+        }
+    }
+
+    fn value_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    fn meta_key(&self, key: &str) -> String {
+        format!("{}{}:meta", self.prefix, key)
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl<V: RedisValue> CacheBackend<V> for RedisBackend {
+    fn get(&self, key: &str) -> Option<(V, Duration)> {
+        let (payload, meta): (Option<Vec<u8>>, HashMap<String, String>) = self.run(|connection| {
+            redis::pipe()
+                .get(self.value_key(key))
+                .hgetall(self.meta_key(key))
+                .query(connection)
+        })?;
+
+        let stored_at = meta.get(STORED_AT_FIELD).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+        let age = Duration::from_millis(now_millis().saturating_sub(stored_at));
+        let value = V::from_payload(payload?, &meta)?;
+
+        Some((value, age))
+    }
+
+    fn store(&self, key: &str, value: V) {
+        let (payload, fields) = value.to_payload();
+
+        let mut meta = vec![(STORED_AT_FIELD, now_millis().to_string())];
+        meta.extend(fields);
+
+        self.run(|connection| {
+            redis::pipe()
+                .atomic()
+                .set(self.value_key(key), payload)
+                .ignore()
+                .del(self.meta_key(key))
+                .ignore()
+                .hset_multiple(self.meta_key(key), &meta)
+                .ignore()
+                .query::<()>(connection)
+        });
+    }
+
+    fn remove(&self, key: &str) -> Option<V> {
+        let removed = CacheBackend::<V>::get(self, key).map(|(value, _)| value);
+
+        self.run(|connection| {
+            connection.del::<_, ()>(&[self.value_key(key), self.meta_key(key)])
+        });
+
+        removed
+    }
+
+    fn remove_where(&self, predicate: &dyn Fn(&str) -> bool) -> usize {
+        let pattern = format!("{}*", self.prefix);
+        let keys: Vec<String> = self
+            .run(|connection| {
+                connection
+                    .scan_match::<_, String>(&pattern)
+                    .map(|keys| keys.collect())
+            })
+            .unwrap_or_default();
+
+        let matching: Vec<&str> = keys
+            .iter()
+            .filter(|key| !key.ends_with(":meta"))
+            .map(|key| &key[self.prefix.len()..])
+            .filter(|key| predicate(key))
+            .collect();
+
+        if matching.is_empty() {
+            return 0;
+        }
+
+        let to_delete: Vec<String> = matching
+            .iter()
+            .flat_map(|key| [self.value_key(key), self.meta_key(key)])
+            .collect();
+
+        self.run(|connection| connection.del::<_, ()>(&to_delete));
+
+        matching.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run with a Redis server: `REDIS_URL=redis://127.0.0.1/ cargo test --features redis -- --ignored`
+    fn backend(prefix: &str) -> RedisBackend {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+        RedisBackend::new(&url, &format!("scrapn:test:{}:{}:", prefix, now_millis())).unwrap()
+    }
+
+    #[test]
+    #[ignore = "requires a running Redis server"]
+    fn image_round_trip() {
+        let backend = backend("image");
+        let image: CachedImage = (Arc::new(vec![1, 2, 3]), "image/webp".to_string(), Some("\"abc\"".to_string()));
+        backend.store("key", image);
+
+        let ((data, content_type, etag), _): (CachedImage, Duration) = backend.get("key").unwrap();
+        assert_eq!(*data, vec![1, 2, 3]);
+        assert_eq!(content_type, "image/webp");
+        assert_eq!(etag.as_deref(), Some("\"abc\""));
+
+        assert!(CacheBackend::<CachedImage>::remove(&backend, "key").is_some());
+        assert!(CacheBackend::<CachedImage>::get(&backend, "key").is_none());
+    }
+
+    #[test]
+    #[ignore = "requires a running Redis server"]
+    fn remove_where_only_matches_the_prefix() {
+        let backend = backend("highlights");
+        for key in ["alice", "bob"] {
+            CacheBackend::<Vec<InstagramHighlight>>::store(&backend, key, Vec::new());
+        }
+        assert_eq!(CacheBackend::<Vec<InstagramHighlight>>::remove_where(&backend, &|key| key == "alice"), 1);
+        assert!(CacheBackend::<Vec<InstagramHighlight>>::get(&backend, "bob").is_some());
+        CacheBackend::<Vec<InstagramHighlight>>::remove(&backend, "bob");
+    }
+}
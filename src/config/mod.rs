@@ -1,15 +1,468 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use rocket::figment::Figment;
 use serde::Deserialize;
+use thiserror::Error;
+use crate::images::{ImageConversionFormat, ImageSigner};
+use crate::proxy::ProxyManager;
+
+// Defaults for optional settings
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 2000;
+const DEFAULT_RETRY_JITTER_MS: u64 = 500;
+
+// GraphQL query fetching the first page of posts of a user (legacy endpoint, replaced by doc_id queries)
+const DEFAULT_POSTS_QUERY_HASH: &str = "8c2a529969ee035a5063f2fc8602a0fd";
+
+// Query string of the web API profile endpoint
+const DEFAULT_WEB_API_QUERY_PARAMS: &str = "__a=1&__d=dis";
+
+pub const DEFAULT_PROXY_HEALTHCHECK_URL: &str = "https://www.instagram.com/robots.txt";
+
+// Formats of the log lines (plain text, or one JSON object per line)
+const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+// IP families outbound requests can be restricted to
+const IP_FAMILIES: [&str; 3] = ["auto", "v4", "v6"];
+
+// Endpoints tried in turn to scrape a profile
+const SCRAPE_ENDPOINTS: [&str; 3] = ["mobile", "web", "html"];
+
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to load configuration: {0}")]
+    Load(#[from] Box<rocket::figment::Error>),
+    
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+    
+    #[error("Failed to read proxies file {0}: {1}")]
+    ProxiesFile(String, std::io::Error),
+}
 
 #[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
 pub struct AppConfig {
     pub port: u16,
     pub address: String,
+    pub log_format: String,
     pub instagram_cache_duration: u64,
+    pub instagram_posts_cache_duration: Option<u64>, // In hours
+    pub instagram_posts_query_hash: String,
+    pub instagram_posts_doc_id: Option<String>,
+    pub web_api_query_params: String,
+    pub instagram_page_size: u64,
+    pub html_min_length: usize,
     pub timeout: u64,
+    pub connect_timeout: Option<u64>,
+    pub ip_family: String,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_jitter_ms: Option<u64>,
     pub max_retries: u32,
     pub user_agent: String,
+    pub user_agents: Option<Vec<String>>,
+    pub scrape_accept_language: Option<String>,
     pub instagram_username_whitelist: Option<Vec<String>>,
+    pub instagram_username_blocklist: Option<Vec<String>>,
     pub instagram_cookies: Option<String>,
+    pub instagram_cookies_pool: Option<Vec<String>>,
     pub proxies: Option<Vec<String>>,
+    pub proxies_file: Option<String>,
+    pub proxy_state_file: Option<String>,
+    pub proxy_unavailable_hours: u64,
+    pub proxy_attempt_delay_ms: u64,
+    pub proxy_healthcheck_on_start: bool,
+    pub proxy_validation_strict: bool,
+    pub proxy_healthcheck_url: Option<String>,
+    pub proxy_healthcheck_interval: Option<u64>, // In minutes
+    pub batch_concurrency: Option<usize>,
+    pub admin_token: Option<String>,
+    pub api_keys: Option<Vec<String>>,
+    pub cors_allowed_origins: Option<Vec<String>>,
+    pub cors_allowed_methods: Option<Vec<String>>,
+    pub cors_allowed_headers: Option<Vec<String>>,
+    pub rate_limit_per_minute: Option<u32>,
+    pub redis_url: Option<String>,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<String>,
+    pub image_fallback_path: Option<String>,
+    pub image_fallback_default: bool,
+    pub image_cache_dir: Option<String>,
+    pub image_cache_disk_limit: Option<u64>, // In bytes
+    pub image_cache_control_max_age: u64, // In seconds
+    pub image_fetch_retries: u32,
+    pub image_referer: String, // Empty to send none
+    pub image_signing_secret: Option<String>,
+    pub require_signed_images: bool,
+    pub signed_image_ttl: u64, // In seconds
+    pub default_image_quality: u8, // Of JPEG, WebP and AVIF outputs, when the request has no quality
+    pub max_output_width: u32,
+    pub max_output_height: u32,
+    pub allowed_image_formats: Option<Vec<String>>,
+    pub image_format_fallback: Option<String>,
+    pub scrape_endpoint_order: Option<Vec<String>>,
+    pub scrape_deadline: Option<u64>, // In seconds
+    pub max_concurrent_scrapes: Option<usize>,
+    pub scrape_queue_timeout: u64, // In seconds
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            port: 8000,
+            address: "0.0.0.0".to_string(),
+            log_format: "text".to_string(),
+            instagram_cache_duration: 1,
+            instagram_posts_cache_duration: None,
+            instagram_posts_query_hash: DEFAULT_POSTS_QUERY_HASH.to_string(),
+            instagram_posts_doc_id: None,
+            web_api_query_params: DEFAULT_WEB_API_QUERY_PARAMS.to_string(),
+            instagram_page_size: 12,
+            html_min_length: 1000,
+            timeout: 30,
+            connect_timeout: None,
+            ip_family: "auto".to_string(),
+            retry_base_delay_ms: None,
+            retry_jitter_ms: None,
+            max_retries: 3,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            user_agents: None,
+            scrape_accept_language: None,
+            instagram_username_whitelist: None,
+            instagram_username_blocklist: None,
+            instagram_cookies: None,
+            instagram_cookies_pool: None,
+            proxies: None,
+            proxies_file: None,
+            proxy_state_file: None,
+            proxy_unavailable_hours: 4,
+            proxy_attempt_delay_ms: 0,
+            proxy_healthcheck_on_start: false,
+            proxy_validation_strict: false,
+            proxy_healthcheck_url: None,
+            proxy_healthcheck_interval: None,
+            batch_concurrency: None,
+            admin_token: None,
+            api_keys: None,
+            cors_allowed_origins: None,
+            cors_allowed_methods: None,
+            cors_allowed_headers: None,
+            rate_limit_per_minute: None,
+            redis_url: None,
+            webhook_url: None,
+            webhook_secret: None,
+            image_fallback_path: None,
+            image_fallback_default: false,
+            image_cache_dir: None,
+            image_cache_disk_limit: None,
+            image_cache_control_max_age: 86400,
+            image_fetch_retries: 2,
+            image_referer: "https://www.instagram.com/".to_string(),
+            image_signing_secret: None,
+            require_signed_images: false,
+            signed_image_ttl: 3600,
+            default_image_quality: 85,
+            max_output_width: 4096,
+            max_output_height: 4096,
+            allowed_image_formats: None,
+            image_format_fallback: None,
+            scrape_endpoint_order: None,
+            scrape_deadline: None,
+            max_concurrent_scrapes: None,
+            scrape_queue_timeout: 30,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Extract the configuration from figment (missing fields fall back to defaults) and validate it
+    pub fn load(figment: &Figment) -> Result<Self, ConfigError> {
+        let mut config = figment.extract::<AppConfig>().map_err(Box::new)?;
+        
+        // Proxies from the file are added to the configured ones
+        if let Some(path) = &config.proxies_file {
+            let list = std::fs::read_to_string(path).map_err(|e| ConfigError::ProxiesFile(path.clone(), e))?;
+            config.proxies.get_or_insert_with(Vec::new).extend(ProxyManager::parse_list(&list));
+        }
+        
+        config.validate()?;
+        Ok(config)
+    }
+    
+    /// Reject nonsensical settings
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.address.trim().is_empty() {
+            return Err(ConfigError::Invalid("address must not be empty".to_string()));
+        }
+        if self.instagram_cache_duration == 0 {
+            return Err(ConfigError::Invalid("instagram_cache_duration must be at least 1 day".to_string()));
+        }
+        if self.instagram_posts_query_hash.trim().is_empty() {
+            return Err(ConfigError::Invalid("instagram_posts_query_hash must not be empty".to_string()));
+        }
+        if self.instagram_posts_doc_id.as_ref().is_some_and(|doc_id| doc_id.trim().is_empty()) {
+            return Err(ConfigError::Invalid("instagram_posts_doc_id must not be empty".to_string()));
+        }
+        if self.web_api_query_params.trim().trim_start_matches('?').is_empty() {
+            return Err(ConfigError::Invalid("web_api_query_params must not be empty".to_string()));
+        }
+        if self.instagram_posts_cache_duration == Some(0) {
+            return Err(ConfigError::Invalid("instagram_posts_cache_duration must be at least 1 hour".to_string()));
+        }
+        if self.instagram_page_size == 0 {
+            return Err(ConfigError::Invalid("instagram_page_size must be greater than 0".to_string()));
+        }
+        if self.timeout == 0 {
+            return Err(ConfigError::Invalid("timeout must be greater than 0".to_string()));
+        }
+        if self.connect_timeout == Some(0) {
+            return Err(ConfigError::Invalid("connect_timeout must be greater than 0".to_string()));
+        }
+        if !LOG_FORMATS.contains(&self.log_format.as_str()) {
+            return Err(ConfigError::Invalid(format!(
+                "Unknown log_format '{}' (expected one of {})", self.log_format, LOG_FORMATS.join(", ")
+            )));
+        }
+        if !IP_FAMILIES.contains(&self.ip_family.as_str()) {
+            return Err(ConfigError::Invalid(format!(
+                "Unknown ip_family '{}' (expected one of {})", self.ip_family, IP_FAMILIES.join(", ")
+            )));
+        }
+        if self.scrape_accept_language.as_ref().is_some_and(|language| language.trim().is_empty()) {
+            return Err(ConfigError::Invalid("scrape_accept_language must not be empty".to_string()));
+        }
+        if self.user_agent.trim().is_empty() {
+            return Err(ConfigError::Invalid("user_agent must not be empty".to_string()));
+        }
+        if self.proxy_unavailable_hours == 0 {
+            return Err(ConfigError::Invalid("proxy_unavailable_hours must be at least 1 hour".to_string()));
+        }
+        if self.proxy_healthcheck_interval == Some(0) {
+            return Err(ConfigError::Invalid("proxy_healthcheck_interval must be greater than 0".to_string()));
+        }
+        if self.batch_concurrency == Some(0) {
+            return Err(ConfigError::Invalid("batch_concurrency must be greater than 0".to_string()));
+        }
+        if self.rate_limit_per_minute == Some(0) {
+            return Err(ConfigError::Invalid("rate_limit_per_minute must be greater than 0".to_string()));
+        }
+        if self.image_fallback_default && self.image_fallback_path.is_none() {
+            return Err(ConfigError::Invalid("image_fallback_default requires image_fallback_path".to_string()));
+        }
+        if let Some(referer) = self.image_referer() {
+            if reqwest::Url::parse(referer).is_err() {
+                return Err(ConfigError::Invalid(format!("image_referer is not a valid URL: {}", referer)));
+            }
+        }
+        if self.require_signed_images && self.image_signing_secret.is_none() {
+            return Err(ConfigError::Invalid("require_signed_images requires image_signing_secret".to_string()));
+        }
+        if self.image_signing_secret.as_deref().is_some_and(|secret| secret.is_empty()) {
+            return Err(ConfigError::Invalid("image_signing_secret must not be empty".to_string()));
+        }
+        if self.signed_image_ttl == 0 {
+            return Err(ConfigError::Invalid("signed_image_ttl must be greater than 0".to_string()));
+        }
+        if self.image_cache_dir.is_some() && self.redis_url.is_some() {
+            return Err(ConfigError::Invalid("image_cache_dir and redis_url can't be used together".to_string()));
+        }
+        if self.image_cache_disk_limit.is_some() && self.image_cache_dir.is_none() {
+            return Err(ConfigError::Invalid("image_cache_disk_limit requires image_cache_dir".to_string()));
+        }
+        if self.image_cache_disk_limit == Some(0) {
+            return Err(ConfigError::Invalid("image_cache_disk_limit must be greater than 0".to_string()));
+        }
+        if self.scrape_deadline == Some(0) {
+            return Err(ConfigError::Invalid("scrape_deadline must be greater than 0".to_string()));
+        }
+        if self.default_image_quality == 0 || self.default_image_quality > 100 {
+            return Err(ConfigError::Invalid("default_image_quality must be between 1 and 100".to_string()));
+        }
+        if self.max_output_width == 0 || self.max_output_height == 0 {
+            return Err(ConfigError::Invalid("max_output_width and max_output_height must be greater than 0".to_string()));
+        }
+        if let Some(formats) = &self.allowed_image_formats {
+            if formats.is_empty() {
+                return Err(ConfigError::Invalid("allowed_image_formats must not be empty".to_string()));
+            }
+            if let Some(format) = formats.iter().find(|format| ImageConversionFormat::from_name(format).is_none()) {
+                return Err(ConfigError::Invalid(format!("Unknown image format '{}' in allowed_image_formats", format)));
+            }
+        }
+        if let Some(fallback) = &self.image_format_fallback {
+            match ImageConversionFormat::from_name(fallback) {
+                None => return Err(ConfigError::Invalid(format!("Unknown image format '{}' in image_format_fallback", fallback))),
+                Some(format) if !self.image_format_allowed(&format) => {
+                    return Err(ConfigError::Invalid("image_format_fallback must be one of allowed_image_formats".to_string()));
+                },
+                Some(_) => {},
+            }
+        }
+        if self.max_concurrent_scrapes == Some(0) {
+            return Err(ConfigError::Invalid("max_concurrent_scrapes must be greater than 0".to_string()));
+        }
+        if let Some(order) = &self.scrape_endpoint_order {
+            if order.is_empty() {
+                return Err(ConfigError::Invalid("scrape_endpoint_order must not be empty".to_string()));
+            }
+            for (index, endpoint) in order.iter().enumerate() {
+                if !SCRAPE_ENDPOINTS.contains(&endpoint.as_str()) {
+                    return Err(ConfigError::Invalid(format!(
+                        "Unknown scrape endpoint '{}' (expected one of {})", endpoint, SCRAPE_ENDPOINTS.join(", ")
+                    )));
+                }
+                if order[..index].contains(endpoint) {
+                    return Err(ConfigError::Invalid(format!("Scrape endpoint '{}' listed twice", endpoint)));
+                }
+            }
+        }
+        if self.redis_url.is_some() && !cfg!(feature = "redis") {
+            return Err(ConfigError::Invalid("redis_url requires building with the `redis` feature".to_string()));
+        }
+        Ok(())
+    }
+    
+    // Timeout for establishing outbound connections, never longer than the overall request timeout
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS).min(self.timeout))
+    }
+    
+    // Local address bound by outbound connections: the unspecified address of the chosen family
+    // restricts them to it, None lets the system pick (both families)
+    pub fn local_address(&self) -> Option<IpAddr> {
+        match self.ip_family.as_str() {
+            "v4" => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            "v6" => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            _ => None,
+        }
+    }
+    
+    // Endpoints tried in turn to scrape a profile, mobile API first by default
+    pub fn scrape_endpoints(&self) -> Vec<String> {
+        self.scrape_endpoint_order
+            .clone()
+            .unwrap_or_else(|| SCRAPE_ENDPOINTS.iter().map(|s| s.to_string()).collect())
+    }
+    
+    // Whether the image proxy may produce a format (any when allowed_image_formats is unset)
+    pub fn image_format_allowed(&self, format: &ImageConversionFormat) -> bool {
+        self.allowed_image_formats.as_ref().is_none_or(|formats| {
+            formats.iter().any(|name| ImageConversionFormat::from_name(name).as_ref() == Some(format))
+        })
+    }
+    
+    // Format produced instead of a disallowed one, if any
+    pub fn image_format_fallback(&self) -> Option<ImageConversionFormat> {
+        self.image_format_fallback.as_deref().and_then(ImageConversionFormat::from_name)
+    }
+    
+    // Whether a username may be scraped: not blocked (case insensitively, the blocklist winning over the whitelist),
+    // and whitelisted when there is a whitelist
+    pub fn username_allowed(&self, username: &str) -> bool {
+        let blocked = self.instagram_username_blocklist.as_ref()
+            .is_some_and(|blocklist| blocklist.iter().any(|blocked| blocked.eq_ignore_ascii_case(username)));
+        let whitelisted = self.instagram_username_whitelist.as_ref()
+            .is_none_or(|whitelist| whitelist.contains(&username.to_string()));
+        !blocked && whitelisted
+    }
+    
+    // Referer of the requests to the CDN, None when disabled
+    pub fn image_referer(&self) -> Option<&str> {
+        Some(self.image_referer.trim()).filter(|referer| !referer.is_empty())
+    }
+    
+    // Signer of image URLs, when a signing secret is configured
+    pub fn image_signer(&self) -> Option<ImageSigner> {
+        self.image_signing_secret.as_deref().map(ImageSigner::new)
+    }
+    
+    // Base delay and maximum random jitter (in milliseconds) between scrape retries
+    pub fn retry_delay_ms(&self) -> (u64, u64) {
+        (
+            self.retry_base_delay_ms.unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS),
+            self.retry_jitter_ms.unwrap_or(DEFAULT_RETRY_JITTER_MS),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::figment::providers::Serialized;
+
+    #[test]
+    fn connect_timeout_is_capped_by_the_timeout() {
+        let config = AppConfig { timeout: 5, connect_timeout: Some(20), ..AppConfig::default() };
+        assert_eq!(config.connect_timeout(), Duration::from_secs(5));
+
+        let config = AppConfig { timeout: 30, connect_timeout: None, ..AppConfig::default() };
+        assert_eq!(config.connect_timeout(), Duration::from_secs(DEFAULT_CONNECT_TIMEOUT_SECS));
+    }
+
+    #[test]
+    fn retry_delay_defaults() {
+        assert_eq!(AppConfig::default().retry_delay_ms(), (DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_JITTER_MS));
+    }
+
+    #[test]
+    fn missing_settings_fall_back_to_defaults() {
+        let figment = Figment::new().merge(Serialized::default("port", 9000));
+        let config = AppConfig::load(&figment).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.address, "0.0.0.0");
+        assert_eq!(config.timeout, 30);
+        assert_eq!(config.max_retries, 3);
+        assert!(!config.user_agent.is_empty());
+    }
+
+    #[test]
+    fn nonsensical_settings_are_rejected() {
+        let figment = Figment::new().merge(Serialized::default("instagram_cache_duration", 0));
+        assert!(matches!(AppConfig::load(&figment), Err(ConfigError::Invalid(_))));
+
+        let config = AppConfig { address: " ".to_string(), ..AppConfig::default() };
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn ip_family_selects_the_local_address() {
+        let config = AppConfig { ip_family: "v4".to_string(), ..AppConfig::default() };
+        assert_eq!(config.local_address(), Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+
+        let config = AppConfig { ip_family: "v6".to_string(), ..AppConfig::default() };
+        assert_eq!(config.local_address(), Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)));
+
+        assert_eq!(AppConfig::default().local_address(), None);
+        let config = AppConfig { ip_family: "v5".to_string(), ..AppConfig::default() };
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn blocklist_wins_over_whitelist() {
+        let config = AppConfig {
+            instagram_username_whitelist: Some(vec!["alice".to_string(), "bob".to_string()]),
+            instagram_username_blocklist: Some(vec!["Bob".to_string()]),
+            ..AppConfig::default()
+        };
+        assert!(config.username_allowed("alice"));
+        assert!(!config.username_allowed("bob"));
+        assert!(!config.username_allowed("carol"));
+
+        let config = AppConfig { instagram_username_blocklist: Some(vec!["bob".to_string()]), ..AppConfig::default() };
+        assert!(config.username_allowed("carol"));
+        assert!(!config.username_allowed("BOB"));
+    }
+
+    #[test]
+    fn image_referer_defaults_to_instagram() {
+        assert_eq!(AppConfig::default().image_referer(), Some("https://www.instagram.com/"));
+        let config = AppConfig { image_referer: " ".to_string(), ..AppConfig::default() };
+        assert_eq!(config.image_referer(), None);
+        assert!(config.validate().is_ok());
+
+        let config = AppConfig { image_referer: "not a url".to_string(), ..AppConfig::default() };
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
 }
- 
\ No newline at end of file
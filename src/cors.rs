@@ -2,7 +2,42 @@ use rocket::fairing::{Fairing, Info, Kind};
 use rocket::http::{Header, Method, Status};
 use rocket::{Request, Response};
 
-pub struct CORS;
+const DEFAULT_ALLOWED_METHODS: &str = "POST, GET, PATCH, PUT, DELETE, OPTIONS";
+
+/// CORS fairing. Allows any origin by default; with a concrete origins allowlist,
+/// the request origin is reflected only when allowed.
+pub struct CORS {
+    allowed_origins: Option<Vec<String>>,
+    allowed_methods: String,
+    allowed_headers: String,
+}
+
+impl Default for CORS {
+    fn default() -> Self {
+        Self::new(None, None, None)
+    }
+}
+
+impl CORS {
+    pub fn new(
+        allowed_origins: Option<Vec<String>>,
+        allowed_methods: Option<Vec<String>>,
+        allowed_headers: Option<Vec<String>>,
+    ) -> Self {
+        // A wildcard in the allowlist means any origin
+        let allowed_origins = allowed_origins.filter(|origins| !origins.iter().any(|origin| origin == "*"));
+
+        Self {
+            allowed_origins,
+            allowed_methods: allowed_methods
+                .map(|methods| methods.join(", "))
+                .unwrap_or_else(|| DEFAULT_ALLOWED_METHODS.to_string()),
+            allowed_headers: allowed_headers
+                .map(|headers| headers.join(", "))
+                .unwrap_or_else(|| "*".to_string()),
+        }
+    }
+}
 
 #[rocket::async_trait]
 impl Fairing for CORS {
@@ -14,12 +49,26 @@ impl Fairing for CORS {
     }
 
     async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        match &self.allowed_origins {
+            None => {
+                response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+            }
+            Some(origins) => {
+                // The allowed origin depends on the request
+                response.adjoin_header(Header::new("Vary", "Origin"));
+                match request.headers().get_one("Origin") {
+                    Some(origin) if origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)) => {
+                        response.set_header(Header::new("Access-Control-Allow-Origin", origin.to_string()));
+                    }
+                    _ => return,
+                }
+            }
+        }
         response.set_header(Header::new(
             "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, PUT, DELETE, OPTIONS",
+            self.allowed_methods.clone(),
         ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
+        response.set_header(Header::new("Access-Control-Allow-Headers", self.allowed_headers.clone()));
         response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
 
         // Handle preflight requests
@@ -28,3 +77,41 @@ impl Fairing for CORS {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[rocket::get("/")]
+    fn index() -> &'static str {
+        "ok"
+    }
+
+    fn client(cors: CORS) -> Client {
+        Client::tracked(rocket::build().attach(cors).mount("/", rocket::routes![index])).unwrap()
+    }
+
+    #[test]
+    fn any_origin_by_default() {
+        let client = client(CORS::default());
+        let response = client.get("/").header(Header::new("Origin", "https://example.com")).dispatch();
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), Some("*"));
+    }
+
+    #[test]
+    fn allowed_origin_is_reflected() {
+        let client = client(CORS::new(Some(vec!["https://app.example.com".to_string()]), None, None));
+        let response = client.get("/").header(Header::new("Origin", "https://app.example.com")).dispatch();
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), Some("https://app.example.com"));
+        assert_eq!(response.headers().get_one("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn disallowed_origin_gets_no_cors_headers() {
+        let client = client(CORS::new(Some(vec!["https://app.example.com".to_string()]), None, None));
+        let response = client.get("/").header(Header::new("Origin", "https://evil.com")).dispatch();
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), None);
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Credentials"), None);
+    }
+}
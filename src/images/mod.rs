@@ -1,12 +1,15 @@
 pub mod proxy;
+pub mod signing;
 pub mod tools;
 
 // Re-export commonly used items for convenience
-pub use proxy::ImageProxy;
+pub use proxy::{ImageProxy, VideoStream};
+pub use signing::ImageSigner;
 pub use tools::{
     ImageProxyError,
     ImageConversionParams,
     ImageConversionFormat,
     ImageFit,
     ImageFocus,
+    DecodedImage,
 };
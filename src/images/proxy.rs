@@ -1,24 +1,95 @@
+use std::net::IpAddr;
 use std::time::Duration;
 use crate::images::tools::ImageProxyError;
 use reqwest::Client;
+use crate::models::instagram::is_instagram_cdn_url;
+
+// Content types accepted from the CDN, anything else is rejected
+const ALLOWED_CONTENT_TYPES: [&str; 9] = [
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/tiff",
+    "image/x-icon",
+    "image/avif",
+    "image/heic",
+];
+
+// Delay before retrying an image request, multiplied by the attempt number
+const IMAGE_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+// A video being streamed from the CDN, with the headers needed to serve range requests
+pub struct VideoStream {
+    pub partial: bool,
+    pub content_type: String,
+    pub content_length: Option<String>,
+    pub content_range: Option<String>,
+    pub response: reqwest::Response,
+}
 
 pub struct ImageProxy {
     timeout: Duration,
+    connect_timeout: Duration,
+    // Local address bound by outbound connections, to force IPv4 or IPv6
+    local_address: Option<IpAddr>,
     client: Client,
+    fallback: Option<(Vec<u8>, String)>,
+    // Extra attempts of an image request answered with a transient status (403, 429, 5xx)
+    retries: u32,
+    // Referer sent to the CDN, which rejects some requests without an instagram.com one
+    referer: Option<String>,
 }
 
 impl ImageProxy {
-    pub fn new(timeout: u64) -> Self {
+    pub fn new(timeout: u64, connect_timeout: Duration, local_address: Option<IpAddr>) -> Self {
         let timeout = Duration::from_secs(timeout);
         let client = reqwest::Client::builder()
             .timeout(timeout)
+            .connect_timeout(connect_timeout)
+            .local_address(local_address)
             .pool_max_idle_per_host(100)
             .pool_idle_timeout(Duration::from_secs(90))
             .tcp_keepalive(Some(Duration::from_secs(60)))
             .build()
             .unwrap_or_else(|_| reqwest::Client::new());
 
-        Self { timeout, client }
+        Self { timeout, connect_timeout, local_address, client, fallback: None, retries: 0, referer: None }
+    }
+
+    // Retry image requests answered with a transient status up to `retries` times
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    // Send every request through an HTTP proxy, so that tests can stand in for the CDN
+    #[cfg(test)]
+    pub fn with_proxy(mut self, proxy_url: &str) -> Self {
+        self.client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::all(proxy_url).unwrap())
+            .build()
+            .unwrap();
+        self
+    }
+
+    // Send this Referer with the requests to the CDN (none when None)
+    pub fn with_referer(mut self, referer: Option<String>) -> Self {
+        self.referer = referer;
+        self
+    }
+
+    // Load the placeholder image returned when a source image can't be fetched
+    pub fn with_fallback(mut self, path: &str) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+        let content_type = self.detect_image_type(&data);
+        self.fallback = Some((data, content_type));
+        Ok(self)
+    }
+
+    pub fn fallback_image(&self) -> Option<(Vec<u8>, String)> {
+        self.fallback.clone()
     }
 
     
@@ -27,12 +98,60 @@ impl ImageProxy {
         self.make_request(url, None).await
     }
 
+    // Start fetching a video, forwarding the client range if any, without buffering its body
+    pub async fn fetch_video(&self, url: &str, range: Option<&str>) -> Result<VideoStream, ImageProxyError> {
+        if !is_instagram_cdn_url(url) {
+            log::warn!("Refusing to fetch video from non-CDN URL: {}", url);
+            return Err(ImageProxyError::ImageError("URL is not an Instagram CDN URL".to_string()));
+        }
+        
+        let mut request = self.client.get(url)
+            .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.4 Safari/605.1.15")
+            .header("Accept", "video/*,*/*;q=0.8");
+        if let Some(range) = range {
+            request = request.header("Range", range);
+        }
+        if let Some(referer) = &self.referer {
+            request = request.header("Referer", referer);
+        }
+        
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            log::error!("Video request failed with status: {}", status);
+            return Err(ImageProxyError::ImageError(format!("Video request failed with status: {}", status)));
+        }
+        
+        let header = |name: &str| response.headers().get(name).and_then(|h| h.to_str().ok()).map(str::to_string);
+        let content_type = header("content-type").unwrap_or_else(|| "video/mp4".to_string());
+        if !content_type.starts_with("video/") {
+            log::warn!("Rejected non-video content type '{}' for {}", content_type, url);
+            return Err(ImageProxyError::ImageError(format!("Unsupported content type: {}", content_type)));
+        }
+        
+        Ok(VideoStream {
+            partial: status == reqwest::StatusCode::PARTIAL_CONTENT,
+            content_type,
+            content_length: header("content-length"),
+            content_range: header("content-range"),
+            response,
+        })
+    }
+
     // Make actual HTTP request with or without proxy
     async fn make_request(&self, url: &str, proxy_url: Option<&str>) -> Result<(Vec<u8>, String), ImageProxyError> {
+        // Only fetch from Instagram CDNs
+        if !is_instagram_cdn_url(url) {
+            log::warn!("Refusing to fetch image from non-CDN URL: {}", url);
+            return Err(ImageProxyError::ImageError("URL is not an Instagram CDN URL".to_string()));
+        }
+        
         // Use the shared client unless a proxy is required (proxies are client-wide in reqwest)
         let client = if let Some(proxy) = proxy_url {
             let builder = reqwest::Client::builder()
                 .timeout(self.timeout)
+                .connect_timeout(self.connect_timeout)
+                .local_address(self.local_address)
                 .pool_max_idle_per_host(100)
                 .pool_idle_timeout(Duration::from_secs(90))
                 .tcp_keepalive(Some(Duration::from_secs(60)));
@@ -48,23 +167,40 @@ impl ImageProxy {
             self.client.clone()
         };
         
-        // Build request with headers matching browser request
-        let request = client.get(url)
-            .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.4 Safari/605.1.15")
-            .header("Accept", "image/avif,image/webp,image/apng,image/*,*/*;q=0.8")
-            .header("Accept-Language", "fr-FR,fr;q=0.9")
-            .header("Accept-Encoding", "gzip, deflate, br");
+        let mut attempt = 0;
+        let response = loop {
+            // Build request with headers matching browser request
+            let mut request = client.get(url)
+                .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.4 Safari/605.1.15")
+                .header("Accept", "image/avif,image/webp,image/apng,image/*,*/*;q=0.8")
+                .header("Accept-Language", "fr-FR,fr;q=0.9")
+                .header("Accept-Encoding", "gzip, deflate, br");
+            if let Some(referer) = &self.referer {
+                request = request.header("Referer", referer);
+            }
+            
+            let response = request.send().await;
+            let status = match &response {
+                Ok(response) if !response.status().is_success() => response.status(),
+                _ => break response,
+            };
+            
+            // The CDN often answers 403 or 429 to a request that succeeds when sent again shortly after
+            if attempt < self.retries && is_transient_status(status) {
+                attempt += 1;
+                log::warn!("Image request failed with status: {}, retrying ({}/{})", status, attempt, self.retries);
+                tokio::time::sleep(IMAGE_RETRY_DELAY * attempt).await;
+                continue;
+            }
+            
+            log::error!("Image request failed with status: {}", status);
+            return Err(ImageProxyError::ImageError(
+                format!("Image request failed with status: {}", status)
+            ));
+        };
         
-        match request.send().await {
+        match response {
             Ok(response) => {
-                let status = response.status();
-                if !status.is_success() {
-                    log::error!("Image request failed with status: {}", status);
-                    return Err(ImageProxyError::ImageError(
-                        format!("Image request failed with status: {}", status)
-                    ));
-                }
-
                 log::info!("Image request successful");
                 
                 // Get the content-type from headers or default to octet-stream
@@ -85,7 +221,16 @@ impl ImageProxy {
                             content_type
                         };
                         
-                        Ok((image_data, content_type))
+                        // Reject anything that isn't an image (ignoring content type parameters)
+                        let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+                        if !ALLOWED_CONTENT_TYPES.contains(&mime.as_str()) {
+                            log::warn!("Rejected non-image content type '{}' for {}", content_type, url);
+                            return Err(ImageProxyError::ImageError(
+                                format!("Unsupported content type: {}", content_type)
+                            ));
+                        }
+                        
+                        Ok((image_data, mime))
                     },
                     Err(e) => Err(ImageProxyError::NetworkError(e)),
                 }
@@ -115,4 +260,84 @@ impl ImageProxy {
         // Default to JPEG if we can't detect
         "image/jpeg".to_string()
     }
-} 
\ No newline at end of file
+}
+
+// Statuses worth retrying an image request for
+fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::FORBIDDEN
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{response, TestServer};
+
+    const CDN_URL: &str = "http://scontent.cdninstagram.com/v/image.jpg";
+
+    fn image_proxy() -> ImageProxy {
+        ImageProxy::new(5, Duration::from_secs(2), None)
+    }
+
+    // Fetch the CDN URL through a test server acting as an HTTP proxy
+    async fn fetch_through(image_proxy: &ImageProxy, server: &TestServer) -> Result<(Vec<u8>, String), ImageProxyError> {
+        image_proxy.make_request(CDN_URL, Some(&server.url)).await
+    }
+
+    #[tokio::test]
+    async fn non_cdn_host_is_rejected() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "image/jpeg")], b"\xFF\xD8\xFF\xE0")]);
+        let result = image_proxy().make_request("http://evil.com/v/image.jpg", Some(&server.url)).await;
+        assert!(matches!(result, Err(ImageProxyError::ImageError(_))));
+        // Rejected before any request is sent
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn html_response_is_rejected() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "text/html; charset=utf-8")], b"<html></html>")]);
+        let result = fetch_through(&image_proxy(), &server).await;
+        assert!(matches!(result, Err(ImageProxyError::ImageError(message)) if message.contains("text/html")));
+    }
+
+    #[tokio::test]
+    async fn image_response_is_accepted() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "image/jpeg; charset=binary")], b"\xFF\xD8\xFF\xE0")]);
+        let (data, content_type) = fetch_through(&image_proxy(), &server).await.unwrap();
+        assert_eq!(data, b"\xFF\xD8\xFF\xE0");
+        assert_eq!(content_type, "image/jpeg");
+        assert_eq!(server.requests()[0].line, format!("GET {} HTTP/1.1", CDN_URL));
+    }
+
+    #[tokio::test]
+    async fn transient_cdn_error_is_retried() {
+        let responses = || vec![response(403, &[], b""), response(200, &[("Content-Type", "image/jpeg")], b"\xFF\xD8\xFF\xE0")];
+
+        let server = TestServer::start(responses());
+        let (data, _) = fetch_through(&image_proxy().with_retries(2), &server).await.unwrap();
+        assert_eq!(data, b"\xFF\xD8\xFF\xE0");
+        assert_eq!(server.requests().len(), 2);
+
+        let server = TestServer::start(responses());
+        assert!(fetch_through(&image_proxy(), &server).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+
+        // Not found is final
+        let server = TestServer::start(vec![response(404, &[], b""), response(200, &[("Content-Type", "image/jpeg")], b"\xFF\xD8")]);
+        assert!(fetch_through(&image_proxy().with_retries(2), &server).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn referer_is_sent_when_configured() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "image/jpeg")], b"\xFF\xD8\xFF\xE0")]);
+        let with_referer = image_proxy().with_referer(Some("https://www.instagram.com/".to_string()));
+        fetch_through(&with_referer, &server).await.unwrap();
+        fetch_through(&image_proxy(), &server).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("referer"), Some("https://www.instagram.com/"));
+        assert_eq!(requests[1].header("referer"), None);
+    }
+}
@@ -0,0 +1,110 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Signs image URLs for a limited time: the username, source URL, conversion params and expiry are covered,
+// so that none of them can be changed without invalidating the signature
+pub struct ImageSigner {
+    secret: String,
+}
+
+impl ImageSigner {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self { secret: secret.into() }
+    }
+
+    /// Hex HMAC-SHA256 signature of an image request expiring at `expires` (Unix timestamp)
+    pub fn sign(&self, username: &str, url: &str, params_key: &str, expires: u64) -> String {
+        self.mac(username, url, params_key, expires)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Check the signature of an image request, returns why it is rejected otherwise
+    pub fn verify(&self, username: &str, url: &str, params_key: &str, expires: Option<u64>, sig: Option<&str>) -> Result<(), String> {
+        let (expires, sig) = match (expires, sig) {
+            (Some(expires), Some(sig)) => (expires, sig),
+            _ => return Err("Missing image signature".to_string()),
+        };
+        if expires < now() {
+            return Err("Image signature expired".to_string());
+        }
+
+        // Compared in constant time by the MAC
+        let sig = decode_hex(sig).ok_or_else(|| "Invalid image signature".to_string())?;
+        self.mac(username, url, params_key, expires)
+            .verify_slice(&sig)
+            .map_err(|_| "Invalid image signature".to_string())
+    }
+
+    /// Expiry of a URL signed now and valid for `ttl` seconds
+    pub fn expires_in(ttl: u64) -> u64 {
+        now() + ttl
+    }
+
+    fn mac(&self, username: &str, url: &str, params_key: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        // One field per line, the URL last as it is the only one coming verbatim from the client
+        mac.update(format!("{}\n{}\n{}\n{}", username.to_lowercase(), params_key, expires, url).as_bytes());
+        mac
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://scontent.cdninstagram.com/v/t51/111_222_n.jpg";
+
+    #[test]
+    fn valid_signature_is_accepted() {
+        let signer = ImageSigner::new("secret");
+        let expires = ImageSigner::expires_in(60);
+        let sig = signer.sign("Alice", URL, "w200", expires);
+        assert_eq!(sig.len(), 64);
+        // Usernames are case insensitive
+        assert_eq!(signer.verify("alice", URL, "w200", Some(expires), Some(&sig)), Ok(()));
+    }
+
+    #[test]
+    fn tampered_request_is_rejected() {
+        let signer = ImageSigner::new("secret");
+        let expires = ImageSigner::expires_in(60);
+        let sig = signer.sign("alice", URL, "w200", expires);
+
+        let invalid = Err("Invalid image signature".to_string());
+        assert_eq!(signer.verify("alice", URL, "w2000", Some(expires), Some(&sig)), invalid);
+        assert_eq!(signer.verify("alice", &format!("{}?x=1", URL), "w200", Some(expires), Some(&sig)), invalid);
+        assert_eq!(signer.verify("bob", URL, "w200", Some(expires), Some(&sig)), invalid);
+        assert_eq!(signer.verify("alice", URL, "w200", Some(expires + 1), Some(&sig)), invalid);
+        assert_eq!(signer.verify("alice", URL, "w200", Some(expires), Some("not hex")), invalid);
+        assert_eq!(ImageSigner::new("other").verify("alice", URL, "w200", Some(expires), Some(&sig)), invalid);
+        assert_eq!(signer.verify("alice", URL, "w200", None, Some(&sig)), Err("Missing image signature".to_string()));
+    }
+
+    #[test]
+    fn expired_signature_is_rejected() {
+        let signer = ImageSigner::new("secret");
+        let expires = now() - 1;
+        let sig = signer.sign("alice", URL, "w200", expires);
+        assert_eq!(signer.verify("alice", URL, "w200", Some(expires), Some(&sig)), Err("Image signature expired".to_string()));
+    }
+}
@@ -3,7 +3,15 @@ use image::{DynamicImage, GenericImageView};
 use image::imageops::FilterType;
 use serde::{Deserialize, Serialize};
 
+// Upper bounds for filter sigmas, blur cost grows with the sigma
+const MAX_BLUR_SIGMA: f32 = 50.0;
+const MAX_SHARPEN_SIGMA: f32 = 10.0;
+
+// Largest source image size in pixels the image proxy decodes
+const MAX_SOURCE_DIMENSION: u32 = 16384;
+
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ImageProxyError {
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
@@ -25,6 +33,57 @@ pub enum ImageConversionFormat {
     Jpg,
     Png,
     Gif,
+    Avif,
+}
+
+impl ImageConversionFormat {
+    /// Parse a format name as accepted by the `format` parameter
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "webp" => Some(ImageConversionFormat::Webp),
+            "jpg" | "jpeg" => Some(ImageConversionFormat::Jpg),
+            "png" => Some(ImageConversionFormat::Png),
+            "gif" => Some(ImageConversionFormat::Gif),
+            "avif" => Some(ImageConversionFormat::Avif),
+            _ => None,
+        }
+    }
+    
+    // Format of a source image that can be encoded back, to keep it when no format is requested
+    fn from_source(format: image::ImageFormat) -> Option<Self> {
+        match format {
+            image::ImageFormat::WebP => Some(ImageConversionFormat::Webp),
+            image::ImageFormat::Jpeg => Some(ImageConversionFormat::Jpg),
+            image::ImageFormat::Png => Some(ImageConversionFormat::Png),
+            image::ImageFormat::Gif => Some(ImageConversionFormat::Gif),
+            _ => None,
+        }
+    }
+    
+    /// Pick the most efficient allowed format advertised by an `Accept` header (AVIF, then WebP),
+    /// or None when the client only accepts common formats
+    pub fn negotiate(accept: &str, allowed: impl Fn(&Self) -> bool) -> Option<Self> {
+        let accepted: Vec<&str> = accept
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let media_type = parts.next()?;
+                // Skip types explicitly refused with q=0
+                let refused = parts.any(|param| {
+                    param.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0)
+                });
+                (!refused).then_some(media_type)
+            })
+            .collect();
+        
+        if cfg!(feature = "avif") && accepted.contains(&"image/avif") && allowed(&ImageConversionFormat::Avif) {
+            Some(ImageConversionFormat::Avif)
+        } else if accepted.contains(&"image/webp") && allowed(&ImageConversionFormat::Webp) {
+            Some(ImageConversionFormat::Webp)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -35,6 +94,10 @@ pub enum ImageFit {
     Scale,
     Crop,
     Thumb,
+    // Largest size fitting within the box, keeping the aspect ratio (CSS `object-fit: contain` without padding)
+    Inside,
+    // Smallest size covering the box, keeping the aspect ratio (CSS `object-fit: cover` without cropping)
+    Outside,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -57,14 +120,33 @@ pub enum ImageFocus {
 pub struct ImageConversionParams {
     pub width: Option<u32>,
     pub height: Option<u32>,
+    // Resize factor relative to the source dimensions (exclusive with width and height)
+    pub scale: Option<f32>,
     pub format: Option<ImageConversionFormat>,
+    // Output quality (JPEG, WebP, AVIF), or compression effort for PNG (lower is smaller but slower)
     pub quality: Option<u8>,
+    // Lossless encoding (WebP only, quality is ignored)
+    #[serde(default)]
+    pub lossless: bool,
     pub fit: Option<ImageFit>,
     pub focus: Option<ImageFocus>,
+    #[serde(default)]
+    pub grayscale: bool,
+    pub blur: Option<f32>,
+    pub sharpen: Option<f32>,
+    pub rotate: Option<u16>,
+    pub flip: Option<String>,
+    // Apply the EXIF orientation tag before other transformations (defaults to true)
+    pub auto_orient: Option<bool>,
 }
 
 impl ImageConversionParams {
     pub fn to_cache_key(&self) -> String {
+        // Pass-through requests share the raw image entry
+        if !self.needs_conversion() {
+            return "original".to_string();
+        }
+        
         let mut parts = Vec::new();
         
         if let Some(width) = self.width {
@@ -73,18 +155,42 @@ impl ImageConversionParams {
         if let Some(height) = self.height {
             parts.push(format!("h{}", height));
         }
+        if let Some(scale) = self.scale {
+            parts.push(format!("s{}", scale));
+        }
         if let Some(ref format) = self.format {
             parts.push(format!("f{:?}", format).to_lowercase());
         }
         if let Some(quality) = self.quality {
             parts.push(format!("q{}", quality));
         }
+        if self.lossless {
+            parts.push("lossless".to_string());
+        }
         if let Some(ref fit) = self.fit {
             parts.push(format!("fit{:?}", fit).to_lowercase());
         }
         if let Some(ref focus) = self.focus {
             parts.push(format!("focus{:?}", focus).to_lowercase().replace("_", ""));
         }
+        if self.grayscale {
+            parts.push("gray".to_string());
+        }
+        if let Some(blur) = self.blur {
+            parts.push(format!("blur{}", blur));
+        }
+        if let Some(sharpen) = self.sharpen {
+            parts.push(format!("sharpen{}", sharpen));
+        }
+        if let Some(rotate) = self.rotate {
+            parts.push(format!("rot{}", rotate));
+        }
+        if let Some(ref flip) = self.flip {
+            parts.push(format!("flip{}", flip));
+        }
+        if self.auto_orient == Some(false) {
+            parts.push("noorient".to_string());
+        }
         
         if parts.is_empty() {
             "original".to_string()
@@ -95,36 +201,194 @@ impl ImageConversionParams {
     
     /// Check if any conversion parameters are set (i.e., if conversion is needed)
     pub fn needs_conversion(&self) -> bool {
-        self.width.is_some() || self.height.is_some() || self.format.is_some() 
-        || self.quality.is_some() || self.fit.is_some() || self.focus.is_some()
+        self.width.is_some() || self.height.is_some() || self.scale.is_some() || self.format.is_some() 
+        || self.quality.is_some() || self.lossless || self.fit.is_some() || self.focus.is_some()
+        || self.grayscale || self.blur.is_some() || self.sharpen.is_some()
+        || self.rotate.is_some() || self.flip.is_some()
+    }
+    
+    /// Check that the scale, rotation and flip values are supported
+    pub fn validate(&self) -> Result<(), ImageProxyError> {
+        if let Some(scale) = self.scale {
+            if !scale.is_finite() || scale <= 0.0 {
+                return Err(ImageProxyError::ConversionError(
+                    format!("Unsupported scale: {} (must be greater than 0)", scale)
+                ));
+            }
+            if self.width.is_some() || self.height.is_some() {
+                return Err(ImageProxyError::ConversionError(
+                    "scale can't be combined with width or height".to_string()
+                ));
+            }
+        }
+        if let Some(rotate) = self.rotate {
+            if rotate % 90 != 0 {
+                return Err(ImageProxyError::ConversionError(
+                    format!("Unsupported rotation: {} (must be a multiple of 90)", rotate)
+                ));
+            }
+        }
+        // ravif has no lossless mode, even at quality 100
+        if self.lossless && matches!(self.format, Some(ImageConversionFormat::Avif)) {
+            return Err(ImageProxyError::ConversionError(
+                "lossless is not supported with the avif format".to_string()
+            ));
+        }
+        if let Some(ref flip) = self.flip {
+            if flip != "h" && flip != "v" {
+                return Err(ImageProxyError::ConversionError(
+                    format!("Unsupported flip: {} (must be h or v)", flip)
+                ));
+            }
+        }
+        Ok(())
     }
 }
 
-// Convert image according to parameters
+// Convert image according to parameters, the output being at most `max_output` (width, height) when resized,
+// and lossy outputs encoded with `default_quality` when no quality is requested
 pub fn convert_image(
     image_data: Vec<u8>,
     params: &ImageConversionParams,
+    max_output: (u32, u32),
+    default_quality: u8,
 ) -> Result<(Vec<u8>, String), ImageProxyError> {
     // Load the image
-    let img = image::load_from_memory(&image_data)
-        .map_err(|e| ImageProxyError::ConversionError(format!("Failed to load image: {}", e)))?;
+    let (img, source_format) = load_image(&image_data)?;
+    let orientation = if params.auto_orient.unwrap_or(true) {
+        read_exif_orientation(&image_data)
+    } else {
+        None
+    };
+    
+    convert_loaded_image(img, source_format, orientation, params, max_output, default_quality)
+}
+
+// Image decoded once to produce several variants of it
+pub struct DecodedImage {
+    image: DynamicImage,
+    source_format: Option<ImageConversionFormat>,
+    orientation: Option<u32>,
+}
+
+impl DecodedImage {
+    pub fn decode(image_data: &[u8]) -> Result<Self, ImageProxyError> {
+        let (image, source_format) = load_image(image_data)?;
+        Ok(Self {
+            image,
+            source_format,
+            orientation: read_exif_orientation(image_data),
+        })
+    }
+    
+    // Same as `convert_image`, working on a copy of the decoded image
+    pub fn convert(
+        &self,
+        params: &ImageConversionParams,
+        max_output: (u32, u32),
+        default_quality: u8,
+    ) -> Result<(Vec<u8>, String), ImageProxyError> {
+        let orientation = self.orientation.filter(|_| params.auto_orient.unwrap_or(true));
+        convert_loaded_image(self.image.clone(), self.source_format.clone(), orientation, params, max_output, default_quality)
+    }
+}
+
+// Decode an image in the format detected from its content (whatever its content type),
+// returning it with that format when it can be encoded back
+fn load_image(image_data: &[u8]) -> Result<(DynamicImage, Option<ImageConversionFormat>), ImageProxyError> {
+    let format = image::guess_format(image_data)
+        .map_err(|e| ImageProxyError::ConversionError(format!("Failed to detect image format: {}", e)))?;
+    
+    let mut limits = image::io::Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    
+    let mut reader = image::io::Reader::with_format(std::io::Cursor::new(image_data), format);
+    reader.limits(limits);
+    let img = reader.decode()
+        .map_err(|e| ImageProxyError::ConversionError(format!("Failed to decode {:?} image: {}", format, e)))?;
+    
+    Ok((img, ImageConversionFormat::from_source(format)))
+}
+
+fn convert_loaded_image(
+    img: DynamicImage,
+    source_format: Option<ImageConversionFormat>,
+    orientation: Option<u32>,
+    params: &ImageConversionParams,
+    max_output: (u32, u32),
+    default_quality: u8,
+) -> Result<(Vec<u8>, String), ImageProxyError> {
+    // Honor EXIF orientation so the image is upright before it gets cropped or resized
+    let img = apply_exif_orientation(img, orientation);
     
     // Apply transformations
-    let processed_img = apply_transformations(img, params)?;
+    let processed_img = apply_transformations(img, params, max_output)?;
     
     // Convert to desired format
-    let (output_data, content_type) = encode_image(processed_img, params)?;
+    let (output_data, content_type) = encode_image(processed_img, params, source_format.as_ref(), default_quality)?;
     
     Ok((output_data, content_type))
 }
 
+// Read the EXIF orientation tag (1 to 8), if any
+fn read_exif_orientation(image_data: &[u8]) -> Option<u32> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(image_data))
+        .ok()?;
+    
+    exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+}
+
+// Transform the image according to its EXIF orientation
+fn apply_exif_orientation(img: DynamicImage, orientation: Option<u32>) -> DynamicImage {
+    match orientation {
+        Some(2) => img.fliph(),
+        Some(3) => img.rotate180(),
+        Some(4) => img.flipv(),
+        Some(5) => img.rotate90().fliph(),
+        Some(6) => img.rotate90(),
+        Some(7) => img.rotate270().fliph(),
+        Some(8) => img.rotate270(),
+        _ => img,
+    }
+}
+
 fn apply_transformations(
     mut img: DynamicImage,
     params: &ImageConversionParams,
+    max_output: (u32, u32),
 ) -> Result<DynamicImage, ImageProxyError> {
-    // Apply resizing if width or height is specified
-    if params.width.is_some() || params.height.is_some() {
-        img = resize_image(img, params)?;
+    params.validate()?;
+    
+    // Apply orientation first so the resize targets match the visible image
+    img = match params.rotate.map(|rotate| rotate % 360) {
+        Some(90) => img.rotate90(),
+        Some(180) => img.rotate180(),
+        Some(270) => img.rotate270(),
+        _ => img,
+    };
+    img = match params.flip.as_deref() {
+        Some("h") => img.fliph(),
+        Some("v") => img.flipv(),
+        _ => img,
+    };
+    
+    // Apply resizing if width, height or scale is specified
+    if params.width.is_some() || params.height.is_some() || params.scale.is_some() {
+        img = resize_image(img, params, max_output)?;
+    }
+    
+    // Apply filters after resizing so they run on the smallest image possible
+    if params.grayscale {
+        img = img.grayscale();
+    }
+    if let Some(sigma) = params.blur {
+        img = img.blur(sigma.min(MAX_BLUR_SIGMA));
+    }
+    if let Some(sigma) = params.sharpen {
+        img = img.unsharpen(sigma.min(MAX_SHARPEN_SIGMA), 0);
     }
     
     Ok(img)
@@ -133,22 +397,28 @@ fn apply_transformations(
 fn resize_image(
     img: DynamicImage,
     params: &ImageConversionParams,
+    max_output: (u32, u32),
 ) -> Result<DynamicImage, ImageProxyError> {
     let (current_width, current_height) = img.dimensions();
     
-    // Calculate target dimensions
-    let (target_width, target_height) = match (params.width, params.height) {
-        (Some(w), Some(h)) => (w, h),
-        (Some(w), None) => {
+    // Calculate target dimensions, relative to the source ones when scaling
+    let (target_width, target_height) = match (params.scale, params.width, params.height) {
+        (Some(scale), _, _) => (
+            ((current_width as f64 * scale as f64) as u32).max(1),
+            ((current_height as f64 * scale as f64) as u32).max(1),
+        ),
+        (None, Some(w), Some(h)) => (w, h),
+        (None, Some(w), None) => {
             let aspect_ratio = current_height as f64 / current_width as f64;
             (w, (w as f64 * aspect_ratio) as u32)
         },
-        (None, Some(h)) => {
+        (None, None, Some(h)) => {
             let aspect_ratio = current_width as f64 / current_height as f64;
             ((h as f64 * aspect_ratio) as u32, h)
         },
-        (None, None) => return Ok(img), // No resizing needed
+        (None, None, None) => return Ok(img), // No resizing needed
     };
+    let (target_width, target_height) = clamp_dimensions(target_width, target_height, max_output);
     
     // Apply fit strategy
     let fit_strategy = params.fit.as_ref().unwrap_or(&ImageFit::Scale);
@@ -159,7 +429,8 @@ fn resize_image(
             img.resize_exact(target_width, target_height, FilterType::Lanczos3)
         },
         ImageFit::Fill => {
-            // Scale to fill the target dimensions, then crop with focus
+            // Crop the source to the target aspect ratio with focus, then scale it to the target dimensions
+            // (cropping first keeps the intermediate image within the source, however extreme its aspect ratio)
             let (current_width, current_height) = img.dimensions();
             
             // Calculate scaling factor to fill the target dimensions
@@ -167,13 +438,12 @@ fn resize_image(
             let scale_y = target_height as f64 / current_height as f64;
             let scale = scale_x.max(scale_y); // Use the larger scale to fill
             
-            // Scale the image
-            let scaled_width = (current_width as f64 * scale) as u32;
-            let scaled_height = (current_height as f64 * scale) as u32;
-            let scaled_img = img.resize(scaled_width, scaled_height, FilterType::Lanczos3);
+            // Source area covering the target dimensions once scaled
+            let crop_width = ((target_width as f64 / scale).round() as u32).clamp(1, current_width);
+            let crop_height = ((target_height as f64 / scale).round() as u32).clamp(1, current_height);
+            let cropped_img = crop_image(img, crop_width, crop_height, params.focus.as_ref())?;
             
-            // Now crop from the scaled image using the focus point
-            crop_image(scaled_img, target_width, target_height, params.focus.as_ref())?
+            cropped_img.resize_exact(target_width, target_height, FilterType::Lanczos3)
         },
         ImageFit::Crop => {
             // Crop to exact dimensions from center or focus point
@@ -188,11 +458,50 @@ fn resize_image(
             // Create thumbnail (resize to fit) with high quality filter
             img.resize(target_width, target_height, FilterType::Lanczos3)
         },
+        ImageFit::Inside => {
+            // Scale to fit within the target dimensions, one of them being matched
+            let (current_width, current_height) = img.dimensions();
+            let scale = (target_width as f64 / current_width as f64).min(target_height as f64 / current_height as f64);
+            let (width, height) = scaled_dimensions(current_width, current_height, scale);
+            img.resize_exact(width, height, FilterType::Lanczos3)
+        },
+        ImageFit::Outside => {
+            // Scale to cover the target dimensions, one of them being matched, without cropping the overflow
+            let (current_width, current_height) = img.dimensions();
+            let scale = (target_width as f64 / current_width as f64).max(target_height as f64 / current_height as f64);
+            let (width, height) = scaled_dimensions(current_width, current_height, scale);
+            let (width, height) = clamp_dimensions(width, height, max_output);
+            img.resize_exact(width, height, FilterType::Lanczos3)
+        },
     };
     
     Ok(resized_img)
 }
 
+// Dimensions scaled by a factor, rounded to the nearest pixel (at least 1)
+fn scaled_dimensions(width: u32, height: u32, scale: f64) -> (u32, u32) {
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+// Scale dimensions down (keeping their ratio) to fit within the maximum ones, so that huge sizes
+// can't be used to allocate huge buffers
+fn clamp_dimensions(width: u32, height: u32, (max_width, max_height): (u32, u32)) -> (u32, u32) {
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+    
+    let scale = (max_width as f64 / width as f64).min(max_height as f64 / height as f64);
+    let clamped = (
+        ((width as f64 * scale) as u32).clamp(1, max_width),
+        ((height as f64 * scale) as u32).clamp(1, max_height),
+    );
+    log::debug!("Clamped output dimensions {}x{} to {}x{}", width, height, clamped.0, clamped.1);
+    clamped
+}
+
 fn crop_image(
     img: DynamicImage,
     target_width: u32,
@@ -271,28 +580,41 @@ fn pad_image(
     Ok(padded)
 }
 
+// Encode the image in the requested format, or keep the source one (JPEG when it can't be encoded)
 fn encode_image(
     img: DynamicImage,
     params: &ImageConversionParams,
+    source_format: Option<&ImageConversionFormat>,
+    default_quality: u8,
 ) -> Result<(Vec<u8>, String), ImageProxyError> {
     let mut output = Vec::new();
-    let format = params.format.as_ref().unwrap_or(&ImageConversionFormat::Jpg);
+    let format = params.format.as_ref().or(source_format).unwrap_or(&ImageConversionFormat::Jpg);
     
     match format {
         ImageConversionFormat::Webp => {
-            // WebP encoding with quality support using the webp crate
-            let quality = params.quality.unwrap_or(85).min(100);
+            // WebP encoding with quality and lossless support using the webp crate
+            let quality = params.quality.unwrap_or(default_quality).min(100);
             
+            // The encoder only takes 8-bit RGB(A) images, grayscale and 16-bit ones are converted first
+            let img = match img {
+                DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => img,
+                img if img.color().has_alpha() => DynamicImage::ImageRgba8(img.to_rgba8()),
+                img => DynamicImage::ImageRgb8(img.to_rgb8()),
+            };
             let encoder = webp::Encoder::from_image(&img)
                 .map_err(|e| ImageProxyError::ConversionError(format!("WebP encoder creation failed: {}", e)))?;
             
-            let webp_data = encoder.encode(quality as f32);
+            let webp_data = if params.lossless {
+                encoder.encode_lossless()
+            } else {
+                encoder.encode(quality as f32)
+            };
             output.extend_from_slice(&webp_data);
             
             Ok((output, "image/webp".to_string()))
         },
         ImageConversionFormat::Jpg => {
-            let quality = params.quality.unwrap_or(85).min(100);
+            let quality = params.quality.unwrap_or(default_quality).min(100);
             let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, quality);
             encoder.encode_image(&img)
                 .map_err(|e| ImageProxyError::ConversionError(format!("JPEG encoding failed: {}", e)))?;
@@ -300,7 +622,20 @@ fn encode_image(
             Ok((output, "image/jpeg".to_string()))
         },
         ImageConversionFormat::Png => {
-            img.write_to(&mut std::io::Cursor::new(&mut output), image::ImageFormat::Png)
+            use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+            use image::ImageEncoder;
+            
+            // PNG is lossless: like for JPEG, a lower quality gives a smaller image, here by compressing harder
+            // (slower), while a higher quality favors encoding speed
+            let (compression, filter) = match params.quality.map(|quality| quality.min(100)) {
+                None => (CompressionType::Default, PngFilterType::Adaptive),
+                Some(0..=33) => (CompressionType::Best, PngFilterType::Adaptive),
+                Some(34..=66) => (CompressionType::Default, PngFilterType::Adaptive),
+                Some(_) => (CompressionType::Fast, PngFilterType::Sub),
+            };
+            
+            PngEncoder::new_with_quality(&mut output, compression, filter)
+                .write_image(img.as_bytes(), img.width(), img.height(), img.color())
                 .map_err(|e| ImageProxyError::ConversionError(format!("PNG encoding failed: {}", e)))?;
             
             Ok((output, "image/png".to_string()))
@@ -311,5 +646,259 @@ fn encode_image(
             
             Ok((output, "image/gif".to_string()))
         },
+        ImageConversionFormat::Avif => encode_avif(img, params, default_quality),
+    }
+}
+
+#[cfg(feature = "avif")]
+fn encode_avif(
+    img: DynamicImage,
+    params: &ImageConversionParams,
+    default_quality: u8,
+) -> Result<(Vec<u8>, String), ImageProxyError> {
+    use image::ImageEncoder;
+    
+    let mut output = Vec::new();
+    let quality = params.quality.unwrap_or(default_quality).min(100);
+    let rgba = img.to_rgba8();
+    
+    // Speed 8 of 10 trades a little compression for much faster encoding
+    image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut output, 8, quality)
+        .write_image(&rgba, rgba.width(), rgba.height(), image::ColorType::Rgba8)
+        .map_err(|e| ImageProxyError::ConversionError(format!("AVIF encoding failed: {}", e)))?;
+    
+    Ok((output, "image/avif".to_string()))
+}
+
+#[cfg(not(feature = "avif"))]
+fn encode_avif(
+    _img: DynamicImage,
+    _params: &ImageConversionParams,
+    _default_quality: u8,
+) -> Result<(Vec<u8>, String), ImageProxyError> {
+    Err(ImageProxyError::ConversionError("AVIF output requires building with the `avif` feature".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    const MAX_OUTPUT: (u32, u32) = (4096, 4096);
+
+    // Colorful gradient, so that filters and encoders have something to work on
+    fn test_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(x * 255 / width) as u8, (y * 255 / height) as u8, ((x + y) % 2 * 255) as u8])
+        }))
+    }
+
+    fn encode(img: &DynamicImage, format: image::ImageFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut data), format).unwrap();
+        data
+    }
+
+    fn convert(data: Vec<u8>, params: &ImageConversionParams) -> DynamicImage {
+        let (output, _) = convert_image(data, params, MAX_OUTPUT, 85).unwrap();
+        image::load_from_memory(&output).unwrap()
+    }
+
+    fn png_params() -> ImageConversionParams {
+        ImageConversionParams { format: Some(ImageConversionFormat::Png), ..Default::default() }
+    }
+
+    #[test]
+    fn grayscale_output_has_equal_channels() {
+        let params = ImageConversionParams { grayscale: true, ..png_params() };
+        let output = convert(encode(&test_image(16, 16), image::ImageFormat::Png), &params).to_rgb8();
+        assert!(output.pixels().all(|Rgb([r, g, b])| r == g && g == b));
+    }
+
+    #[test]
+    fn blur_changes_pixel_values() {
+        let source = test_image(16, 16);
+        let params = ImageConversionParams { blur: Some(2.0), ..png_params() };
+        let output = convert(encode(&source, image::ImageFormat::Png), &params).to_rgb8();
+        assert_eq!(output.dimensions(), (16, 16));
+        assert_ne!(output.as_raw(), source.to_rgb8().as_raw());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn filters_are_part_of_the_cache_key() {
+        let params = ImageConversionParams { grayscale: true, blur: Some(2.0), sharpen: Some(1.0), ..Default::default() };
+        assert_eq!(params.to_cache_key(), "gray_blur2_sharpen1");
+        assert_eq!(ImageConversionParams::default().to_cache_key(), "original");
+    }
+
+    #[test]
+    fn rotation_by_90_swaps_dimensions() {
+        let params = ImageConversionParams { rotate: Some(90), ..png_params() };
+        let output = convert(encode(&test_image(20, 10), image::ImageFormat::Png), &params);
+        assert_eq!(output.dimensions(), (10, 20));
+
+        let params = ImageConversionParams { rotate: Some(180), flip: Some("h".to_string()), ..png_params() };
+        let output = convert(encode(&test_image(20, 10), image::ImageFormat::Png), &params);
+        assert_eq!(output.dimensions(), (20, 10));
+    }
+
+    #[test]
+    fn unsupported_rotation_or_flip_is_rejected() {
+        let params = ImageConversionParams { rotate: Some(45), ..Default::default() };
+        assert!(matches!(params.validate(), Err(ImageProxyError::ConversionError(_))));
+        let params = ImageConversionParams { flip: Some("x".to_string()), ..Default::default() };
+        assert!(matches!(params.validate(), Err(ImageProxyError::ConversionError(_))));
+        let params = ImageConversionParams { rotate: Some(270), flip: Some("v".to_string()), ..Default::default() };
+        assert!(params.validate().is_ok());
+        assert_eq!(params.to_cache_key(), "rot270_flipv");
+    }
+
+    // JPEG with an EXIF APP1 segment holding only an orientation tag
+    fn jpeg_with_orientation(img: &DynamicImage, orientation: u8) -> Vec<u8> {
+        let jpeg = encode(img, image::ImageFormat::Jpeg);
+        let mut exif = b"Exif\0\0MM\0\x2a\0\0\0\x08".to_vec();
+        exif.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, orientation, 0, 0, 0, 0, 0, 0]);
+
+        let mut data = jpeg[..2].to_vec();
+        data.extend_from_slice(&[0xFF, 0xE1]);
+        data.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        data.extend_from_slice(&exif);
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    #[test]
+    fn exif_orientation_6_is_applied() {
+        let data = jpeg_with_orientation(&test_image(20, 10), 6);
+        assert_eq!(read_exif_orientation(&data), Some(6));
+        assert_eq!(convert(data.clone(), &png_params()).dimensions(), (10, 20));
+
+        let params = ImageConversionParams { auto_orient: Some(false), ..png_params() };
+        assert_eq!(convert(data, &params).dimensions(), (20, 10));
+    }
+
+    #[test]
+    fn webp_quality_changes_the_output_size() {
+        let data = encode(&test_image(64, 64), image::ImageFormat::Png);
+        let webp = |quality, lossless| {
+            let params = ImageConversionParams { format: Some(ImageConversionFormat::Webp), quality: Some(quality), lossless, ..Default::default() };
+            let (output, content_type) = convert_image(data.clone(), &params, MAX_OUTPUT, 85).unwrap();
+            assert_eq!(content_type, "image/webp");
+            output.len()
+        };
+        assert!(webp(10, false) < webp(95, false));
+        assert_ne!(webp(10, true), webp(10, false));
+    }
+
+    #[test]
+    fn accept_header_negotiation() {
+        let any = |_: &ImageConversionFormat| true;
+        let avif = if cfg!(feature = "avif") { Some(ImageConversionFormat::Avif) } else { Some(ImageConversionFormat::Webp) };
+        assert_eq!(ImageConversionFormat::negotiate("image/avif,image/webp,image/*", any), avif);
+        assert_eq!(ImageConversionFormat::negotiate("image/webp;q=0.9, image/png", any), Some(ImageConversionFormat::Webp));
+        assert_eq!(ImageConversionFormat::negotiate("image/webp;q=0, image/*", any), None);
+        assert_eq!(ImageConversionFormat::negotiate("image/*,*/*", any), None);
+        assert_eq!(ImageConversionFormat::negotiate("image/webp", |format| *format != ImageConversionFormat::Webp), None);
+    }
+
+    #[test]
+    fn png_compression_follows_the_quality() {
+        let data = encode(&test_image(128, 128), image::ImageFormat::Png);
+        let png = |quality| {
+            let params = ImageConversionParams { quality: Some(quality), ..png_params() };
+            let (output, content_type) = convert_image(data.clone(), &params, MAX_OUTPUT, 85).unwrap();
+            assert_eq!(content_type, "image/png");
+            output.len()
+        };
+        // Low quality values compress the most
+        assert!(png(10) <= png(50));
+        assert!(png(50) <= png(90));
+    }
+
+    #[test]
+    fn over_cap_width_is_clamped_to_the_max() {
+        let data = encode(&test_image(20, 10), image::ImageFormat::Png);
+        let params = ImageConversionParams { width: Some(100_000), ..png_params() };
+        let (output, _) = convert_image(data, &params, (64, 64), 85).unwrap();
+        // The aspect ratio is kept
+        assert_eq!(image::load_from_memory(&output).unwrap().dimensions(), (64, 32));
+    }
+
+    #[test]
+    fn half_scale_halves_both_dimensions() {
+        let params = ImageConversionParams { scale: Some(0.5), ..png_params() };
+        assert_eq!(convert(encode(&test_image(20, 10), image::ImageFormat::Png), &params).dimensions(), (10, 5));
+    }
+
+    #[test]
+    fn scale_validation() {
+        let scale = |scale, width| ImageConversionParams { scale: Some(scale), width, ..Default::default() };
+        assert!(scale(0.5, None).validate().is_ok());
+        assert!(scale(2.0, None).validate().is_ok());
+        assert!(scale(0.5, Some(100)).validate().is_err());
+        assert!(scale(0.0, None).validate().is_err());
+        assert!(scale(f32::NAN, None).validate().is_err());
+    }
+
+    #[test]
+    fn cache_keys() {
+        assert_eq!(ImageConversionParams::default().to_cache_key(), "original");
+        let params = ImageConversionParams { scale: Some(0.5), quality: Some(80), ..png_params() };
+        assert_eq!(params.to_cache_key(), "s0.5_fpng_q80");
+        assert_ne!(params.to_cache_key(), ImageConversionParams { scale: Some(0.25), ..params.clone() }.to_cache_key());
+        let params = ImageConversionParams { width: Some(100), height: Some(50), auto_orient: Some(false), ..Default::default() };
+        assert_eq!(params.to_cache_key(), "w100_h50_noorient");
+    }
+
+    #[test]
+    fn inside_and_outside_fits_keep_the_aspect_ratio() {
+        let data = encode(&test_image(20, 10), image::ImageFormat::Png);
+        let fit = |fit, size| {
+            let params = ImageConversionParams { width: Some(size), height: Some(size), fit: Some(fit), ..png_params() };
+            convert(data.clone(), &params).dimensions()
+        };
+        assert_eq!(fit(ImageFit::Inside, 10), (10, 5));
+        assert_eq!(fit(ImageFit::Outside, 10), (20, 10));
+        assert_eq!(fit(ImageFit::Inside, 30), (30, 15));
+        assert_eq!(fit(ImageFit::Outside, 30), (60, 30));
+    }
+
+    #[test]
+    fn source_format_is_kept_without_requested_format() {
+        let params = ImageConversionParams { width: Some(8), ..Default::default() };
+        let (output, content_type) = convert_image(encode(&test_image(16, 16), image::ImageFormat::Png), &params, MAX_OUTPUT, 85).unwrap();
+        assert_eq!(content_type, "image/png");
+        assert_eq!(image::guess_format(&output).unwrap(), image::ImageFormat::Png);
+        assert_eq!(image::load_from_memory(&output).unwrap().dimensions(), (8, 8));
+
+        let (output, content_type) = convert_image(encode(&test_image(16, 16), image::ImageFormat::Jpeg), &params, MAX_OUTPUT, 85).unwrap();
+        assert_eq!(content_type, "image/jpeg");
+        assert_eq!(image::guess_format(&output).unwrap(), image::ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn undetected_format_is_a_conversion_error() {
+        let result = convert_image(b"<html>not an image</html>".to_vec(), &png_params(), MAX_OUTPUT, 85);
+        assert!(matches!(result, Err(ImageProxyError::ConversionError(message)) if message.contains("detect")));
+
+        // Detected as PNG, then failing to decode
+        let mut truncated = encode(&test_image(16, 16), image::ImageFormat::Png);
+        truncated.truncate(40);
+        let result = convert_image(truncated, &png_params(), MAX_OUTPUT, 85);
+        assert!(matches!(result, Err(ImageProxyError::ConversionError(message)) if message.contains("Png")));
+    }
+
+    #[test]
+    fn default_quality_applies_without_requested_quality() {
+        let source = encode(&test_image(64, 64), image::ImageFormat::Png);
+        let jpeg = |quality: Option<u8>, default_quality: u8| {
+            let params = ImageConversionParams { format: Some(ImageConversionFormat::Jpg), quality, ..Default::default() };
+            convert_image(source.clone(), &params, MAX_OUTPUT, default_quality).unwrap().0
+        };
+
+        assert_eq!(jpeg(None, 30), jpeg(Some(30), 85));
+        assert!(jpeg(None, 30).len() < jpeg(None, 95).len());
+        // The requested quality wins
+        assert_eq!(jpeg(Some(90), 30), jpeg(None, 90));
+    }
+}
@@ -1 +1,2 @@
-pub mod cors;
\ No newline at end of file
+pub mod cors;
+pub mod request_id;
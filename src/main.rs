@@ -8,25 +8,40 @@ mod models;
 mod proxy;
 mod scrapers;
 mod images;
+mod webhook;
+#[cfg(test)]
+mod test_server;
 
 use std::env;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
 
 use cache::{InstagramCache, ImageCache};
+use cache::disk::DiskBackend;
 use config::AppConfig;
 use proxy::ProxyManager;
 use dotenv::dotenv;
 use env_logger::Env;
-use log::info;
+use log::{error, info, warn};
 use rocket::{
     figment::{
         providers::{Format, Toml},
         Figment, Profile,
     },
-    Config,
+    fairing::AdHoc,
+    Config, Orbit, Rocket,
 };
 use scrapers::instagram::InstagramScraper;
+use scrapers::ScraperRegistry;
 use images::ImageProxy;
+use api::rate_limit::RateLimiter;
 use scrapn::cors::CORS;
+use scrapn::request_id::{self, RequestIdFairing};
+use webhook::WebhookNotifier;
+
+// Interval between checks of the image cache directory size
+const DISK_CACHE_CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 #[launch]
 async fn rocket() -> _ {
@@ -40,32 +55,138 @@ async fn rocket() -> _ {
     if let Ok(whitelist) = env::var("INSTAGRAM_USERNAME_WHITELIST") {
         figment = figment.merge(("instagram_username_whitelist", whitelist.split(',').map(|s| s.trim().to_string()).collect::<Vec<String>>()));
     }
+    if let Ok(blocklist) = env::var("INSTAGRAM_USERNAME_BLOCKLIST") {
+        figment = figment.merge(("instagram_username_blocklist", blocklist.split(',').map(|s| s.trim().to_string()).collect::<Vec<String>>()));
+    }
+
+    // Merge Redis URL if available from environment
+    if let Ok(redis_url) = env::var("REDIS_URL") {
+        figment = figment.merge(("redis_url", redis_url));
+    }
 
     // Merge Instagram cookies if available
     if let Ok(cookies) = env::var("INSTAGRAM_COOKIES") {
         figment = figment.merge(("instagram_cookies", cookies));
     }
     
+    // Merge Instagram cookie pool if available (cookie sets separated by `|`)
+    if let Ok(pool) = env::var("INSTAGRAM_COOKIES_POOL") {
+        figment = figment.merge(("instagram_cookies_pool", pool.split('|').map(|s| s.trim().to_string()).collect::<Vec<String>>()));
+    }
+    
     // Merge proxies if available from environment
     if let Ok(proxies) = env::var("PROXIES") {
-        figment = figment.merge(("proxies", proxies.split(',').map(|s| s.trim().to_string()).collect::<Vec<String>>()));
+        figment = figment.merge(("proxies", ProxyManager::parse_list(&proxies)));
+    }
+
+    // Merge CORS allowed origins if available from environment
+    if let Ok(origins) = env::var("CORS_ALLOWED_ORIGINS") {
+        figment = figment.merge(("cors_allowed_origins", origins.split(',').map(|s| s.trim().to_string()).collect::<Vec<String>>()));
+    }
+
+    // Merge API keys if available from environment
+    if let Ok(api_keys) = env::var("API_KEYS") {
+        figment = figment.merge(("api_keys", api_keys.split(',').map(|s| s.trim().to_string()).collect::<Vec<String>>()));
+    }
+
+    // Merge webhook settings if available from environment
+    if let Ok(webhook_url) = env::var("WEBHOOK_URL") {
+        figment = figment.merge(("webhook_url", webhook_url));
+    }
+    if let Ok(webhook_secret) = env::var("WEBHOOK_SECRET") {
+        figment = figment.merge(("webhook_secret", webhook_secret));
+    }
+
+    // Merge image signing secret if available from environment
+    if let Ok(image_signing_secret) = env::var("IMAGE_SIGNING_SECRET") {
+        figment = figment.merge(("image_signing_secret", image_signing_secret));
+    }
+
+    // Merge log format if available from environment
+    if let Ok(log_format) = env::var("LOG_FORMAT") {
+        figment = figment.merge(("log_format", log_format));
+    }
+
+    // Merge admin token if available from environment
+    if let Ok(admin_token) = env::var("ADMIN_TOKEN") {
+        figment = figment.merge(("admin_token", admin_token));
     }
 
     figment = figment.select(Profile::from_env_or("APP_PROFILE", "default"));
 
-    info!("Configuration loaded successfully");
+    // Initialize logger before loading the config, so that its errors are logged in the configured format
+    // (an invalid log_format falls back to text, then is reported by the validation)
+    let log_format = figment.extract_inner::<String>("log_format").unwrap_or_default();
+    init_logger(log_format == "json");
 
     // App config
-    let config = figment.extract::<AppConfig>().unwrap();
+    let config = match AppConfig::load(&figment) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // Initialize logger
-    env_logger::init_from_env(Env::default().default_filter_or("info"));
+    info!("Configuration loaded successfully");
     
-    // Create proxy manager with 4 hour unavailability period
-    let proxy_manager = ProxyManager::new(config.proxies.clone(), 4);
+    // Create proxy manager with the configured unavailability period
+    let proxy_manager = ProxyManager::new(config.proxies.clone(), config.proxy_unavailable_hours)
+        .with_connection_settings(config.connect_timeout(), config.local_address());
+
+    // Restore the proxy state saved on the last shutdown (none yet on the first start)
+    if let Some(path) = &config.proxy_state_file {
+        match proxy_manager.load_state(path) {
+            Ok(restored) => info!("Restored the state of {} proxies from {}", restored, path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to restore the proxy state from {}: {}", path, e),
+        }
+    }
+
+    // Report malformed proxies now rather than on their first request
+    let invalid_proxies = proxy_manager.validate_all();
+    for (proxy, reason) in &invalid_proxies {
+        if config.proxy_validation_strict {
+            error!("Invalid proxy {}: {}", proxy, reason);
+        } else {
+            warn!("Invalid proxy {}: {}", proxy, reason);
+        }
+    }
+    if config.proxy_validation_strict && !invalid_proxies.is_empty() {
+        error!("{} invalid proxies configured, exiting (proxy_validation_strict is enabled)", invalid_proxies.len());
+        std::process::exit(1);
+    }
     
+    let healthcheck_url = config
+        .proxy_healthcheck_url
+        .clone()
+        .unwrap_or_else(|| config::DEFAULT_PROXY_HEALTHCHECK_URL.to_string());
+
     // Log proxy information
     if let Some(_proxies) = &config.proxies {
+        // Discard dead proxies before serving the first request
+        if config.proxy_healthcheck_on_start {
+            info!("Checking proxies health using {}", healthcheck_url);
+            proxy_manager.health_check_all(&healthcheck_url, config.connect_timeout()).await;
+        }
+
+        // Periodically check proxies health
+        if let Some(interval) = config.proxy_healthcheck_interval {
+            let proxy_manager = proxy_manager.clone();
+            let healthcheck_url = healthcheck_url.clone();
+            let timeout = config.connect_timeout();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval * 60));
+                // The first tick completes immediately
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let (available, total) = proxy_manager.health_check_all(&healthcheck_url, timeout).await;
+                    info!("Proxy health check: {}/{} proxies available", available, total);
+                }
+            });
+        }
+
         let (available, total) = proxy_manager.get_proxy_count();
         info!(
             "Proxy rotation enabled with {}/{} available proxies",
@@ -78,19 +199,57 @@ async fn rocket() -> _ {
     // Create Instagram scraper
     let instagram_scraper = InstagramScraper::new(config.clone(), proxy_manager.clone());
 
-    // Create Instagram cache
-    let instagram_cache = InstagramCache::new(config.instagram_cache_duration);
-    
-    // Create Instagram image cache (cached permanently)
-    let instagram_image_cache = ImageCache::new();
-    info!("Instagram image proxy cache initialized (permanent storage)");
+    // Register platform scrapers
+    let mut scraper_registry = ScraperRegistry::new();
+    scraper_registry.register(Arc::new(instagram_scraper.clone()));
+    info!("Scrapers registered for platforms: {:?}", scraper_registry.platforms());
+
+    // Create Instagram cache and Instagram image cache (images are cached permanently)
+    let (instagram_cache, instagram_image_cache) = create_caches(&config);
+    let instagram_cache = match config.instagram_posts_cache_duration {
+        Some(hours) => instagram_cache.with_posts_cache_hours(hours),
+        None => instagram_cache,
+    };
+
+    // Notify profile changes to the webhook if configured
+    let instagram_cache = match &config.webhook_url {
+        Some(url) => {
+            info!("Webhook notifications enabled");
+            instagram_cache.with_notifier(WebhookNotifier::new(url.clone(), config.webhook_secret.clone(), config.timeout))
+        }
+        None => instagram_cache,
+    };
     
     // Create image proxy
     let image_proxy = ImageProxy::new(
         config.timeout,
-    );
+        config.connect_timeout(),
+        config.local_address(),
+    )
+    .with_retries(config.image_fetch_retries)
+    .with_referer(config.image_referer().map(str::to_string));
+    let image_proxy = match &config.image_fallback_path {
+        Some(path) => match image_proxy.with_fallback(path) {
+            Ok(image_proxy) => {
+                info!("Image proxy fallback image loaded from {}", path);
+                image_proxy
+            }
+            Err(e) => {
+                error!("Failed to load fallback image '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => image_proxy,
+    };
     info!("Image proxy initialized");
 
+    // Create client rate limiter
+    let rate_limiter = RateLimiter::new(config.rate_limit_per_minute);
+    match config.rate_limit_per_minute {
+        Some(limit) => info!("Rate limiting enabled: {} requests per minute per client", limit),
+        None => info!("Rate limiting disabled"),
+    }
+
     info!(
         "Starting Scrapn API server on {}:{}",
         config.address, config.port
@@ -98,19 +257,241 @@ async fn rocket() -> _ {
 
     // Build Rocket instance
     rocket::custom(figment)
-        .attach(CORS)
+        .attach(CORS::new(
+            config.cors_allowed_origins.clone(),
+            config.cors_allowed_methods.clone(),
+            config.cors_allowed_headers.clone(),
+        ))
+        .attach(RequestIdFairing)
+        .attach(AdHoc::on_shutdown("Graceful shutdown", |rocket| Box::pin(shutdown(rocket))))
         .manage(instagram_scraper)
+        .manage(scraper_registry)
         .manage(instagram_cache)
         .manage(instagram_image_cache)
         .manage(image_proxy)
+        .manage(rate_limiter)
+        .manage(proxy_manager)
+        .manage(api::admin::StartedAt(std::time::Instant::now()))
         .manage(config.clone())
         .mount(
             "/instagram",
-            routes![
+            request_id::scoped(routes![
                 api::instagram::get_user,
+                api::instagram::get_users_batch,
                 api::instagram::get_posts,
+                api::instagram::get_post,
                 api::instagram::get_reels,
+                api::instagram::get_highlights,
+                api::instagram::get_tagged,
+                api::instagram::purge_cache,
                 api::instagram::proxy_image,
-            ],
+                api::instagram::proxy_image_head,
+                api::instagram::sign_image_url,
+                api::instagram::get_image_variants,
+                api::instagram::get_avatar,
+                api::instagram::proxy_video,
+            ]),
         )
+        .mount("/profiles", request_id::scoped(routes![api::profiles::get_profile, api::profiles::get_profile_posts]))
+        .mount("/admin", request_id::scoped(routes![api::admin::get_proxies, api::admin::get_stats, api::admin::preload, api::admin::test_proxy]))
+        .mount("/", request_id::scoped(routes![api::openapi::openapi]))
+}
+
+// Let in-flight scrapes complete (within the shutdown grace period) so their results get cached, then save the proxy state
+async fn shutdown(rocket: &Rocket<Orbit>) {
+    let grace = Duration::from_secs(rocket.config().shutdown.grace as u64);
+
+    if let Some(scraper) = rocket.state::<InstagramScraper>() {
+        info!("Waiting up to {}s for in-flight scrapes", grace.as_secs());
+        if !scraper.wait_idle(grace).await {
+            warn!("Scrapes still running at shutdown, their results will be lost");
+        }
+    }
+
+    let state_file = rocket.state::<AppConfig>().and_then(|config| config.proxy_state_file.as_deref());
+    if let (Some(proxy_manager), Some(path)) = (rocket.state::<ProxyManager>(), state_file) {
+        match proxy_manager.save_state(path) {
+            Ok(()) => info!("Proxy state saved to {}", path),
+            Err(e) => error!("Failed to save the proxy state to {}: {}", path, e),
+        }
+    }
+
+    info!("Shutting down");
+}
+
+// Create the Instagram data and image caches, backed by Redis when configured or in memory otherwise
+fn create_caches(config: &AppConfig) -> (InstagramCache, ImageCache) {
+    #[cfg(feature = "redis")]
+    if let Some(redis_url) = &config.redis_url {
+        use cache::redis::RedisBackend;
+
+        let backends = (|| {
+            Ok::<_, redis::RedisError>((
+                RedisBackend::new(redis_url, "scrapn:user:")?,
+                RedisBackend::new(redis_url, "scrapn:posts:")?,
+                RedisBackend::new(redis_url, "scrapn:highlights:")?,
+                RedisBackend::new(redis_url, "scrapn:tagged:")?,
+                RedisBackend::new(redis_url, "scrapn:clips:")?,
+                RedisBackend::new(redis_url, "scrapn:image:")?,
+            ))
+        })();
+        match backends {
+            Ok((users, posts, highlights, tagged, clips, images)) => {
+                info!("Redis cache initialized");
+                return (
+                    InstagramCache::with_backends(
+                        Box::new(users),
+                        Box::new(posts),
+                        Box::new(highlights),
+                        Box::new(tagged),
+                        Box::new(clips),
+                        config.instagram_cache_duration,
+                    ),
+                    ImageCache::with_backend(Box::new(images)),
+                );
+            }
+            Err(e) => {
+                error!("Failed to connect to Redis: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(dir) = &config.image_cache_dir {
+        info!("In-memory cache initialized, images stored in {}", dir);
+        return (InstagramCache::new(config.instagram_cache_duration), create_disk_image_cache(dir, config.image_cache_disk_limit));
+    }
+
+    info!("In-memory cache initialized (images stored permanently)");
+    (InstagramCache::new(config.instagram_cache_duration), ImageCache::new())
+}
+
+// Create the image cache stored in a directory, periodically trimmed to the disk limit if any
+fn create_disk_image_cache(dir: &str, disk_limit: Option<u64>) -> ImageCache {
+    let backend = match DiskBackend::new(dir) {
+        Ok(backend) => backend,
+        Err(e) => {
+            error!("Failed to create image cache directory '{}': {}", dir, e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(max_bytes) = disk_limit {
+        info!("Image cache disk limit: {} bytes", max_bytes);
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(DISK_CACHE_CLEANUP_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let backend = backend.clone();
+                // File system calls are blocking
+                if let Err(e) = tokio::task::spawn_blocking(move || backend.enforce_limit(max_bytes)).await {
+                    error!("Image cache cleanup failed: {}", e);
+                }
+            }
+        });
+    }
+
+    ImageCache::with_backend(Box::new(backend))
+}
+
+// Initialize the logger, tagging lines logged while processing a request with its ID.
+// In JSON mode, each line is an object with timestamp, level, target, message and requestId (when any).
+fn init_logger(json: bool) {
+    let mut builder = env_logger::Builder::from_env(Env::default().default_filter_or("info"));
+    if json {
+        builder.format(|buf, record| {
+            let line = json_log_line(buf.timestamp().to_string(), record, request_id::current());
+            writeln!(buf, "{}", line)
+        });
+    } else {
+        builder.format(|buf, record| {
+            let request_id = request_id::current()
+                .map(|id| format!(" [{}]", id))
+                .unwrap_or_default();
+            writeln!(
+                buf,
+                "[{} {} {}]{} {}",
+                buf.timestamp(),
+                buf.default_styled_level(record.level()),
+                record.target(),
+                request_id,
+                record.args()
+            )
+        });
+    }
+    builder.init();
+}
+
+// JSON object logged for a record in JSON mode
+fn json_log_line(timestamp: String, record: &log::Record, request_id: Option<String>) -> serde_json::Value {
+    let mut line = serde_json::json!({
+        "timestamp": timestamp,
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    if let Some(request_id) = request_id {
+        line["requestId"] = request_id.into();
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    const PROXY: &str = "http://10.0.0.1:8080";
+
+    #[test]
+    fn shutdown_saves_the_proxy_state() {
+        let path = env::temp_dir().join(format!("scrapn-proxy-state-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let proxy_manager = ProxyManager::new(Some(vec![PROXY.to_string()]), 4);
+        for _ in 0..3 {
+            proxy_manager.mark_proxy_failure(PROXY);
+        }
+        let config = AppConfig { proxy_state_file: Some(path.clone()), ..AppConfig::default() };
+        let scraper = InstagramScraper::new(config.clone(), ProxyManager::new(None, 4));
+
+        let rocket = rocket::build()
+            .manage(proxy_manager)
+            .manage(scraper)
+            .manage(config)
+            .attach(AdHoc::on_shutdown("Graceful shutdown", |rocket| Box::pin(shutdown(rocket))));
+        let client = Client::tracked(rocket).unwrap();
+        client.terminate();
+
+        let restored = ProxyManager::new(Some(vec![PROXY.to_string()]), 4);
+        assert_eq!(restored.load_state(&path).unwrap(), 1);
+        assert_eq!(restored.get_proxy_count(), (0, 1));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn json_log_lines_are_parseable() {
+        let line = json_log_line(
+            "2024-01-01T00:00:00Z".to_string(),
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .target("scrapn::scrapers")
+                .args(format_args!("Proxy {} \"failed\"", 1))
+                .build(),
+            Some("abc123".to_string()),
+        )
+        .to_string();
+        assert!(!line.contains('\n'));
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["timestamp"], "2024-01-01T00:00:00Z");
+        assert_eq!(parsed["level"], "WARN");
+        assert_eq!(parsed["target"], "scrapn::scrapers");
+        assert_eq!(parsed["message"], "Proxy 1 \"failed\"");
+        assert_eq!(parsed["requestId"], "abc123");
+
+        let line = json_log_line(String::new(), &log::Record::builder().build(), None);
+        assert!(line.get("requestId").is_none());
+    }
 }
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+// Platforms supported by the scrapers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Instagram,
+}
+
+impl Platform {
+    /// Platform from its lowercase name, as used in the profile routes
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "instagram" => Some(Platform::Instagram),
+            _ => None,
+        }
+    }
+}
+
+// Platform-agnostic user profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub platform: Platform,
+    pub username: String,
+    pub full_name: Option<String>,
+    pub biography: Option<String>,
+    pub avatar_url: Option<String>,
+    pub is_private: bool,
+    pub is_verified: bool,
+    pub external_url: Option<String>,
+    pub posts_count: Option<u64>,
+    pub followers_count: Option<u64>,
+    pub following_count: Option<u64>,
+    pub scraped_at: DateTime<Utc>,
+}
+
+// Platform-agnostic post (image or video)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Post {
+    pub platform: Platform,
+    pub id: String,
+    pub code: String,
+    pub media_url: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub video_url: Option<String>,
+    pub caption: Option<String>,
+    pub likes_count: Option<u64>,
+    pub comments_count: Option<u64>,
+    pub views_count: Option<u64>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub is_video: bool,
+}
@@ -1,21 +1,63 @@
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use log;
 
+use crate::models::common::{Platform, Post, Profile};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InstagramPost {
     pub id: String,
     pub shortcode: String,
-    pub display_url: String,
+    pub display_url: Option<String>, // None when Instagram returned no image URL
     pub thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub resolutions: Vec<ImageVersion>, // Available sizes of the image, largest first
     pub caption: Option<String>,
+    #[serde(default)]
+    pub alt_text: Option<String>, // Accessibility caption of the image (written by the author or generated by Instagram)
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
     pub likes_count: Option<u64>,
     pub comments_count: Option<u64>,
     pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub timestamp_unix: Option<i64>, // Same as timestamp, in seconds since the epoch
     pub is_video: bool,
     pub video_url: Option<String>,
     pub video_view_count: Option<u64>,
+    #[serde(default)]
+    pub video_duration: Option<f64>, // In seconds
+    #[serde(default)]
+    pub video_width: Option<u32>,
+    #[serde(default)]
+    pub video_height: Option<u32>,
+    #[serde(default)]
+    pub carousel: Vec<InstagramCarouselItem>, // Media of carousel (sidecar) posts
+}
+
+// A media of a carousel post
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramCarouselItem {
+    pub id: String,
+    pub display_url: String,
+    pub is_video: bool,
+    pub video_url: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+// A size variant of a post image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageVersion {
+    pub width: u32,
+    pub height: u32,
+    pub url: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,23 +65,179 @@ pub struct InstagramPost {
 pub struct InstagramReel {
     pub id: String,
     pub shortcode: String,
-    pub display_url: String,
+    pub display_url: Option<String>,
     pub video_url: Option<String>,
     pub caption: Option<String>,
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
     pub views_count: Option<u64>,
     pub likes_count: Option<u64>,
     pub comments_count: Option<u64>,
     pub timestamp: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub timestamp_unix: Option<i64>, // Same as timestamp, in seconds since the epoch
+    #[serde(default)]
+    pub duration: Option<f64>, // In seconds
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
 }
 
+// Page of the reels tab, with the cursor of the next page when there is one
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct InstagramReelsPage {
+    pub reels: Vec<InstagramReel>,
+    pub end_cursor: Option<String>,
+}
+
+impl From<&InstagramPost> for InstagramReel {
+    fn from(post: &InstagramPost) -> Self {
+        InstagramReel {
+            id: post.id.clone(),
+            shortcode: post.shortcode.clone(),
+            display_url: post.display_url.clone(),
+            video_url: post.video_url.clone(),
+            caption: post.caption.clone(),
+            hashtags: post.hashtags.clone(),
+            mentions: post.mentions.clone(),
+            views_count: post.video_view_count,
+            likes_count: post.likes_count,
+            comments_count: post.comments_count,
+            timestamp: post.timestamp,
+            timestamp_unix: post.timestamp_unix,
+            duration: post.video_duration,
+            width: post.video_width,
+            height: post.video_height,
+        }
+    }
+}
+
+// Extract `#hashtags` and `@mentions` from a caption, deduplicated (case-insensitively)
+// and in order of first appearance. Tags must start at a word boundary, so emails
+// like `me@example.com` or `a#b` are ignored.
+pub fn extract_caption_tags(caption: Option<&str>) -> (Vec<String>, Vec<String>) {
+    let mut hashtags: Vec<String> = Vec::new();
+    let mut mentions: Vec<String> = Vec::new();
+    
+    let caption = match caption {
+        Some(caption) => caption,
+        None => return (hashtags, mentions),
+    };
+    
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let chars: Vec<char> = caption.chars().collect();
+    let mut i = 0;
+    
+    while i < chars.len() {
+        let marker = chars[i];
+        let at_boundary = i == 0 || !is_word_char(chars[i - 1]);
+        
+        if (marker == '#' || marker == '@') && at_boundary {
+            // Usernames may contain dots, hashtags can't
+            let mut end = i + 1;
+            while end < chars.len() && (is_word_char(chars[end]) || (marker == '@' && chars[end] == '.')) {
+                end += 1;
+            }
+            
+            let tag: String = chars[i + 1..end].iter().collect();
+            // A trailing dot ends the sentence rather than the username
+            let tag = tag.trim_end_matches('.').to_string();
+            
+            if !tag.is_empty() {
+                let target = if marker == '#' { &mut hashtags } else { &mut mentions };
+                if !target.iter().any(|existing| existing.to_lowercase() == tag.to_lowercase()) {
+                    target.push(tag);
+                }
+            }
+            
+            i = end.max(i + 1);
+        } else {
+            i += 1;
+        }
+    }
+    
+    (hashtags, mentions)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramHighlight {
+    pub id: String,
+    pub title: String,
+    pub cover_url: Option<String>,
+    pub media_count: Option<u64>,
+}
+
+// Domains of the CDNs serving Instagram media
+const INSTAGRAM_CDN_DOMAINS: [&str; 2] = ["cdninstagram.com", "fbcdn.net"];
+
+// Check that a URL is an http(s) URL served by an Instagram/Facebook CDN domain (or one of its subdomains)
+pub fn is_instagram_cdn_url(url: &str) -> bool {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return false,
+    };
+    
+    if parsed.scheme() != "https" && parsed.scheme() != "http" {
+        return false;
+    }
+    
+    match parsed.host_str() {
+        Some(host) => {
+            let host = host.to_lowercase();
+            INSTAGRAM_CDN_DOMAINS.iter().any(|domain| {
+                host == *domain || host.ends_with(&format!(".{}", domain))
+            })
+        }
+        None => false,
+    }
+}
+
+// Serialized with abbreviated counts (`followersCountShort`...) next to the raw ones, see the Serialize impl
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InstagramUserStats {
     pub posts_count: Option<u64>,
     pub followers_count: Option<u64>,
     pub following_count: Option<u64>,
 }
 
+impl Serialize for InstagramUserStats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("InstagramUserStats", 6)?;
+        state.serialize_field("postsCount", &self.posts_count)?;
+        state.serialize_field("followersCount", &self.followers_count)?;
+        state.serialize_field("followingCount", &self.following_count)?;
+        state.serialize_field("postsCountShort", &self.posts_count.map(short_count))?;
+        state.serialize_field("followersCountShort", &self.followers_count.map(short_count))?;
+        state.serialize_field("followingCountShort", &self.following_count.map(short_count))?;
+        state.end()
+    }
+}
+
+/// Abbreviate a count the way Instagram displays it: 999, 1K, 12.3K, 1.2M, 3B.
+/// One decimal at most, rounded down so that a count is never shown above its value.
+pub fn short_count(count: u64) -> String {
+    const UNITS: [(u64, &str); 3] = [(1_000_000_000, "B"), (1_000_000, "M"), (1_000, "K")];
+
+    match UNITS.iter().find(|(unit, _)| count >= *unit) {
+        Some((unit, suffix)) => {
+            let tenths = count / (unit / 10);
+            match tenths % 10 {
+                0 => format!("{}{}", tenths / 10, suffix),
+                decimal => format!("{}.{}{}", tenths / 10, decimal, suffix),
+            }
+        },
+        None => count.to_string(),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InstagramUser {
@@ -50,6 +248,14 @@ pub struct InstagramUser {
     pub is_private: bool,
     pub is_verified: bool,
     pub external_url: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub is_business: bool,
+    #[serde(default)]
+    pub account_type: Option<String>, // "personal", "business" or "creator" when known
+    #[serde(default)]
+    pub business_contact_method: Option<String>, // How a business prefers to be contacted, e.g. "call" or "text"
     pub stats: InstagramUserStats,
     pub posts: Option<Vec<InstagramPost>>,
     pub reels: Option<Vec<InstagramReel>>,
@@ -58,8 +264,15 @@ pub struct InstagramUser {
 }
 
 impl InstagramUser {
-    // Check if a URL belongs to this user's content (profile pic, posts, reels)
+    // Check if a URL is served by an Instagram CDN and belongs to this user's content (profile pic, posts, reels)
     pub fn is_content_url(&self, url: &str) -> bool {
+        // Only CDN URLs can be content: fuzzy identifier matching must never
+        // let URLs from other hosts through
+        if !is_instagram_cdn_url(url) {
+            log::debug!("URL host is not an Instagram CDN: {}", url);
+            return false;
+        }
+        
         // Helper function to extract key image identifiers from Instagram URLs
         fn extract_instagram_image_id(url: &str) -> Option<String> {
             log::debug!("Extracting ID from URL: {}", url);
@@ -133,7 +346,7 @@ impl InstagramUser {
                     
                     // Extract the base number before any = or encoding characters
                     let extract_base = |s: &str| -> String {
-                        if let Some(idx) = s.find(|c| c == '=' || c == '%') {
+                        if let Some(idx) = s.find(['=', '%']) {
                             s[0..idx].to_string()
                         } else {
                             s.to_string()
@@ -179,7 +392,7 @@ impl InstagramUser {
         // Check posts
         if let Some(posts) = self.posts.as_ref() {
             for post in posts {
-                if urls_match(&post.display_url, url) {
+                if post.display_url.as_deref().is_some_and(|display_url| urls_match(display_url, url)) {
                     return true;
                 }
                 
@@ -189,18 +402,29 @@ impl InstagramUser {
                     }
                 }
                 
+                if post.resolutions.iter().any(|version| urls_match(&version.url, url)) {
+                    return true;
+                }
+                
                 if let Some(video) = &post.video_url {
                     if urls_match(video, url) {
                         return true;
                     }
                 }
+                
+                for item in &post.carousel {
+                    if urls_match(&item.display_url, url)
+                        || item.video_url.as_deref().is_some_and(|video| urls_match(video, url)) {
+                        return true;
+                    }
+                }
             }
         }
         
         // Check reels
         if let Some(reels) = self.reels.as_ref() {
             for reel in reels {
-                if urls_match(&reel.display_url, url) {
+                if reel.display_url.as_deref().is_some_and(|display_url| urls_match(display_url, url)) {
                     return true;
                 }
                 
@@ -216,6 +440,44 @@ impl InstagramUser {
     }
 }
 
+impl From<InstagramUser> for Profile {
+    fn from(user: InstagramUser) -> Self {
+        Profile {
+            platform: Platform::Instagram,
+            username: user.username,
+            full_name: user.full_name,
+            biography: user.biography,
+            avatar_url: user.profile_pic_url,
+            is_private: user.is_private,
+            is_verified: user.is_verified,
+            external_url: user.external_url,
+            posts_count: user.stats.posts_count,
+            followers_count: user.stats.followers_count,
+            following_count: user.stats.following_count,
+            scraped_at: user.scraped_at,
+        }
+    }
+}
+
+impl From<InstagramPost> for Post {
+    fn from(post: InstagramPost) -> Self {
+        Post {
+            platform: Platform::Instagram,
+            id: post.id,
+            code: post.shortcode,
+            media_url: post.display_url,
+            thumbnail_url: post.thumbnail_url,
+            video_url: post.video_url,
+            caption: post.caption,
+            likes_count: post.likes_count,
+            comments_count: post.comments_count,
+            views_count: post.video_view_count,
+            timestamp: post.timestamp,
+            is_video: post.is_video,
+        }
+    }
+}
+
 // Response wrapper for API to include timing info
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -223,6 +485,7 @@ pub struct InstagramUserResponse {
     pub data: InstagramUser,
     pub from_cache: bool,
     pub cache_age: Option<u64>, // Age in seconds if from cache
+    pub scrape_duration_ms: Option<u64>, // Time taken by the scrape (retries included) if scraped fresh
 }
 
 #[derive(Debug, Serialize)]
@@ -231,6 +494,9 @@ pub struct InstagramPostsResponse {
     pub data: Vec<InstagramPost>,
     pub from_cache: bool,
     pub cache_age: Option<u64>,
+    pub limited: bool, // More posts exist than the ones scraped
+    pub total_count: Option<u64>,
+    pub gap: bool, // The requested after_shortcode wasn't found, all posts are returned
 }
 
 #[derive(Debug, Serialize)]
@@ -239,4 +505,167 @@ pub struct InstagramReelsResponse {
     pub data: Vec<InstagramReel>,
     pub from_cache: bool,
     pub cache_age: Option<u64>,
-} 
\ No newline at end of file
+    pub limited: bool, // More reels follow in the reels tab, or reels derived from the scraped posts are incomplete when the posts are
+    pub end_cursor: Option<String>, // Cursor of the next page of the reels tab (None for reels derived from the posts)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramPostResponse {
+    pub data: InstagramPost,
+    pub from_cache: bool,
+    pub cache_age: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramHighlightsResponse {
+    pub data: Vec<InstagramHighlight>,
+    pub from_cache: bool,
+    pub cache_age: Option<u64>,
+}
+
+// Outcome of a single username in a batch request
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum InstagramBatchEntry {
+    Success(Box<InstagramUserResponse>),
+    Error { error: String, code: String },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramBatchResponse {
+    pub data: HashMap<String, InstagramBatchEntry>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramImageVariantsResponse {
+    pub url: String,
+    // Data URL of each variant, keyed by its conversion params
+    pub variants: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramImageDataUrlResponse {
+    pub data_url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramSignedImageResponse {
+    // Path and query of the signed image URL, relative to the server
+    pub url: String,
+    // Unix timestamp after which the URL is rejected
+    pub expires: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstagramCachePurgeResponse {
+    pub username: String,
+    pub user_purged: bool,
+    pub images_purged: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caption_tags_mixed_with_punctuation() {
+        let caption = "Sunset 🌅#beach, #Summer! with @jane.doe and @john_smith. Again #beach #summer (@jane.doe)";
+        let (hashtags, mentions) = extract_caption_tags(Some(caption));
+        assert_eq!(hashtags, vec!["beach", "Summer"]);
+        assert_eq!(mentions, vec!["jane.doe", "john_smith"]);
+    }
+
+    #[test]
+    fn caption_tags_edge_cases() {
+        let (hashtags, mentions) = extract_caption_tags(Some("mail me@example.com a#b ends with #"));
+        assert!(hashtags.is_empty());
+        assert!(mentions.is_empty());
+
+        let (hashtags, mentions) = extract_caption_tags(None);
+        assert!(hashtags.is_empty() && mentions.is_empty());
+    }
+
+    fn user_with_post(display_url: &str) -> InstagramUser {
+        serde_json::from_value(serde_json::json!({
+            "username": "jane",
+            "fullName": null,
+            "biography": null,
+            "profilePicUrl": "https://scontent.cdninstagram.com/v/t51/123456_789_n.jpg?ig_cache_key=avatar",
+            "isPrivate": false,
+            "isVerified": false,
+            "externalUrl": null,
+            "stats": { "postsCount": 1, "followersCount": 1, "followingCount": 1 },
+            "posts": [{
+                "id": "1",
+                "shortcode": "abc",
+                "displayUrl": display_url,
+                "thumbnailUrl": null,
+                "caption": null,
+                "likesCount": null,
+                "commentsCount": null,
+                "timestamp": null,
+                "isVideo": false,
+                "videoUrl": null,
+                "videoViewCount": null
+            }],
+            "reels": [],
+            "scrapedAt": "2024-01-01T00:00:00Z",
+            "postsLimited": false
+        })).unwrap()
+    }
+
+    #[test]
+    fn content_url_requires_a_cdn_host() {
+        let path = "/v/t51.2885-15/497961779_18033097154648370_200386581629336489_n.jpg?stp=dst-jpg";
+        let user = user_with_post(&format!("https://scontent-cdg4-1.cdninstagram.com{}", path));
+
+        assert!(user.is_content_url(&format!("https://scontent-cdg4-1.cdninstagram.com{}", path)));
+        assert!(!user.is_content_url(&format!("https://evil.com{}", path)));
+        assert!(!user.is_content_url(&format!("https://cdninstagram.com.evil.com{}", path)));
+    }
+
+    #[test]
+    fn cdn_urls() {
+        assert!(is_instagram_cdn_url("https://scontent.cdninstagram.com/v/image.jpg"));
+        assert!(is_instagram_cdn_url("https://scontent-cdg4-1.xx.fbcdn.net/v/image.jpg"));
+        assert!(is_instagram_cdn_url("http://FBCDN.NET/image.jpg"));
+        assert!(!is_instagram_cdn_url("https://notcdninstagram.com/image.jpg"));
+        assert!(!is_instagram_cdn_url("https://scontent.cdninstagram.com@evil.com/image.jpg"));
+        assert!(!is_instagram_cdn_url("ftp://scontent.cdninstagram.com/image.jpg"));
+        assert!(!is_instagram_cdn_url("not a url"));
+    }
+
+    #[test]
+    fn short_count_boundaries() {
+        assert_eq!(short_count(0), "0");
+        assert_eq!(short_count(999), "999");
+        assert_eq!(short_count(1000), "1K");
+        assert_eq!(short_count(1099), "1K");
+        assert_eq!(short_count(12_345), "12.3K");
+        assert_eq!(short_count(999_999), "999.9K");
+        assert_eq!(short_count(1_000_000), "1M");
+        assert_eq!(short_count(1_250_000), "1.2M");
+        assert_eq!(short_count(3_000_000_000), "3B");
+    }
+
+    #[test]
+    fn short_counts_are_serialized_next_to_the_raw_ones() {
+        let stats = InstagramUserStats { posts_count: Some(999), followers_count: Some(1_250_000), following_count: None };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["followersCount"], 1_250_000);
+        assert_eq!(json["followersCountShort"], "1.2M");
+        assert_eq!(json["postsCountShort"], "999");
+        assert!(json["followingCountShort"].is_null());
+
+        // The short counts are ignored when read back
+        let stats: InstagramUserStats = serde_json::from_value(json).unwrap();
+        assert_eq!(stats.followers_count, Some(1_250_000));
+    }
+}
@@ -1 +1,2 @@
-pub mod instagram; 
\ No newline at end of file
+pub mod common;
+pub mod instagram;
\ No newline at end of file
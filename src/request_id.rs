@@ -0,0 +1,152 @@
+use std::future::Future;
+use std::time::Instant;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::route::{self, Handler, Route};
+use rocket::{Data, Request, Response};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// Identifier of the request being processed, taken from the incoming `X-Request-Id`
+/// header when valid or generated otherwise. Use it as a request guard.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    fn from_header(value: Option<&str>) -> Self {
+        let valid = |id: &&str| {
+            !id.is_empty()
+                && id.len() <= 128
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+
+        match value.filter(valid) {
+            Some(id) => RequestId(id.to_string()),
+            None => RequestId(Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// Run a future with this request ID attached to its log lines
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT_REQUEST_ID.scope(self.0.clone(), future).await
+    }
+}
+
+// Route handler running the wrapped one with the request ID attached, guards included
+#[derive(Clone)]
+struct ScopedHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for ScopedHandler {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        request_id(request).scope(self.0.handle(request, data)).await
+    }
+}
+
+/// Attach the request ID to every log line of the routes' handlers (mount the returned routes)
+pub fn scoped(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(ScopedHandler(route.handler));
+            route
+        })
+        .collect()
+}
+
+/// ID of the request whose future is currently running, if any
+pub fn current() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(request_id(request).clone())
+    }
+}
+
+fn request_id<'r>(request: &'r Request<'_>) -> &'r RequestId {
+    request.local_cache(|| RequestId::from_header(request.headers().get_one(REQUEST_ID_HEADER)))
+}
+
+// Time at which the request was received, to log its duration
+struct RequestStart(Instant);
+
+/// Assigns an ID to each request, returned in the `X-Request-Id` response header
+pub struct RequestIdFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestIdFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Assign request IDs",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut rocket::Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+        request_id(request);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let id = request_id(request);
+        let elapsed = request.local_cache(|| RequestStart(Instant::now())).0.elapsed();
+
+        log::info!(
+            "[{}] {} {} -> {} ({} ms)",
+            id.0,
+            request.method(),
+            request.uri(),
+            response.status(),
+            elapsed.as_millis()
+        );
+
+        response.set_header(Header::new(REQUEST_ID_HEADER, id.0.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[rocket::get("/")]
+    fn index() -> String {
+        current().unwrap_or_default()
+    }
+
+    fn client() -> Client {
+        let rocket = rocket::build().attach(RequestIdFairing).mount("/", scoped(rocket::routes![index]));
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn response_has_a_request_id() {
+        let client = client();
+        let response = client.get("/").dispatch();
+        let id = response.headers().get_one(REQUEST_ID_HEADER).unwrap().to_string();
+        assert!(!id.is_empty());
+        // Handlers see the same ID
+        assert_eq!(response.into_string().unwrap(), id);
+    }
+
+    #[test]
+    fn valid_incoming_request_id_is_kept() {
+        let client = client();
+        let response = client.get("/").header(Header::new(REQUEST_ID_HEADER, "abc-123")).dispatch();
+        assert_eq!(response.headers().get_one(REQUEST_ID_HEADER), Some("abc-123"));
+
+        let response = client.get("/").header(Header::new(REQUEST_ID_HEADER, "bad id")).dispatch();
+        assert_ne!(response.headers().get_one(REQUEST_ID_HEADER), Some("bad id"));
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use reqwest::Client;
+
+// HTTP clients kept per proxy (direct requests under an empty key), so that requests
+// through the same proxy reuse its connection pool instead of handshaking again
+#[derive(Debug, Default)]
+pub struct ClientCache {
+    clients: Mutex<HashMap<String, Client>>,
+}
+
+impl ClientCache {
+    /// Get the client of a proxy (None for direct requests), building it on first use
+    pub fn get_or_build<E>(&self, proxy_url: Option<&str>, build: impl FnOnce() -> Result<Client, E>) -> Result<Client, E> {
+        let key = proxy_url.unwrap_or_default();
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(key) {
+            return Ok(client.clone());
+        }
+
+        let client = build()?;
+        clients.insert(key.to_string(), client.clone());
+        Ok(client)
+    }
+
+    /// Replace the client of a proxy, e.g. after its protocol was corrected
+    pub fn replace(&self, proxy_url: &str, client: Client) {
+        self.clients.lock().unwrap().insert(proxy_url.to_string(), client);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    #[test]
+    fn clients_are_built_once_per_proxy() {
+        let cache = ClientCache::default();
+        let mut builds = 0;
+        let mut get = |proxy_url: Option<&str>| {
+            cache
+                .get_or_build(proxy_url, || {
+                    builds += 1;
+                    Ok::<_, Infallible>(Client::new())
+                })
+                .unwrap()
+        };
+
+        get(Some("http://proxy-a:8080"));
+        get(Some("http://proxy-a:8080"));
+        get(Some("http://proxy-b:8080"));
+        get(None);
+        get(None);
+        assert_eq!(builds, 3);
+    }
+
+    #[test]
+    fn replaced_clients_are_not_rebuilt() {
+        let cache = ClientCache::default();
+        cache.replace("http://proxy-a:8080", Client::new());
+        let client = cache.get_or_build(Some("http://proxy-a:8080"), || Err("rebuilt"));
+        assert!(client.is_ok());
+    }
+}
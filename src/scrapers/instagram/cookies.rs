@@ -0,0 +1,103 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::warn;
+
+// Time during which a rate limited or challenged cookie set is kept out of rotation
+const COOKIE_COOLDOWN: Duration = Duration::from_secs(30 * 60);
+
+// Instagram cookie sets (one per account) rotated across authenticated requests
+#[derive(Debug)]
+pub struct CookiePool {
+    sets: Vec<String>,
+    next: AtomicUsize,
+    // Index of the cookie sets cooling down -> end of their cooldown
+    cooling: Mutex<HashMap<usize, Instant>>,
+}
+
+impl CookiePool {
+    /// Build the pool from the configured cookie sets (duplicates and empty ones are dropped),
+    /// returns None when there is none
+    pub fn new(sets: impl IntoIterator<Item = String>) -> Option<Self> {
+        let mut unique: Vec<String> = Vec::new();
+        for set in sets {
+            let set = set.trim().to_string();
+            if !set.is_empty() && !unique.contains(&set) {
+                unique.push(set);
+            }
+        }
+
+        (!unique.is_empty()).then(|| Self {
+            sets: unique,
+            next: AtomicUsize::new(0),
+            cooling: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Pick a cookie set, skipping the ones cooling down.
+    /// A given proxy is always paired with the same set while it is available, direct requests rotate through the pool.
+    /// When every set is cooling down, the one recovering first is used anyway.
+    pub fn pick(&self, proxy_url: Option<&str>) -> String {
+        let start = match proxy_url {
+            Some(proxy) => {
+                let mut hasher = DefaultHasher::new();
+                proxy.hash(&mut hasher);
+                hasher.finish() as usize
+            },
+            None => self.next.fetch_add(1, Ordering::Relaxed),
+        };
+
+        let now = Instant::now();
+        let mut cooling = self.cooling.lock().unwrap();
+        cooling.retain(|_, until| *until > now);
+
+        let index = (0..self.sets.len())
+            .map(|offset| (start + offset) % self.sets.len())
+            .find(|index| !cooling.contains_key(index))
+            .or_else(|| cooling.iter().min_by_key(|(_, until)| **until).map(|(index, _)| *index))
+            .unwrap_or(0);
+
+        self.sets[index].clone()
+    }
+
+    /// Take a cookie set out of rotation for a while (after a rate limit or a checkpoint)
+    pub fn cool_down(&self, cookies: &str) {
+        if let Some(index) = self.sets.iter().position(|set| set == cookies) {
+            warn!("Cookie set #{} cooling down for {} minutes", index + 1, COOKIE_COOLDOWN.as_secs() / 60);
+            self.cooling.lock().unwrap().insert(index, Instant::now() + COOKIE_COOLDOWN);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> CookiePool {
+        CookiePool::new(["a=1", "b=2", "a=1", " "].map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn direct_requests_rotate_and_proxies_keep_their_set() {
+        let pool = pool();
+        assert_ne!(pool.pick(None), pool.pick(None));
+
+        let proxy = Some("http://10.0.0.1:8080");
+        assert_eq!(pool.pick(proxy), pool.pick(proxy));
+        assert!(CookiePool::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn cooling_set_is_skipped() {
+        let pool = pool();
+        pool.cool_down("a=1");
+        assert!((0..4).all(|_| pool.pick(None) == "b=2"));
+
+        // Every set cooling down: the one recovering first is used anyway
+        pool.cool_down("b=2");
+        assert_eq!(pool.pick(None), "a=1");
+    }
+}
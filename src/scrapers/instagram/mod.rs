@@ -1,97 +1,373 @@
 use scraper::{Html, Selector};
-use reqwest::{Client, Proxy};
+use reqwest::{Client, Proxy, RequestBuilder, Response};
 use regex::Regex;
 use serde_json::Value;
 use chrono::{Utc, TimeZone};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::future::Future;
 use std::time::Duration;
-use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use rand::Rng;
 use log::{info, error, warn, debug};
 
+use crate::models::common::{Platform, Post, Profile};
 use crate::models::instagram::{
-    InstagramUser, InstagramPost, InstagramReel, InstagramUserStats
+    InstagramUser, InstagramPost, InstagramReel, InstagramReelsPage, InstagramUserStats, InstagramHighlight, InstagramCarouselItem, ImageVersion, extract_caption_tags
 };
 use crate::config::AppConfig;
 use crate::proxy::ProxyManager;
+use crate::scrapers::Scraper;
+use crate::scrapers::stats::{ScrapeStats, ScrapeStatsSnapshot};
 
-#[derive(Error, Debug)]
-pub enum ScraperError {
-    #[error("Network error: {0}")]
-    NetworkError(#[from] reqwest::Error),
-    
-    #[error("Parsing error: {0}")]
-    ParsingError(String),
-    
-    #[error("Rate limited or blocked")]
-    RateLimited,
-    
-    #[error("Profile not found")]
-    ProfileNotFound,
-    
-    #[error("Private profile")]
-    PrivateProfile,
-    
-    #[error("Proxy error: {0}")]
-    ProxyError(String),
-    
-    #[error("All proxies failed")]
-    AllProxiesFailed,
-    
-    #[error("Unauthorized access: {0}")]
-    UnauthorizedAccess(String),
-}
+mod clients;
+mod cookies;
+use clients::ClientCache;
+use cookies::CookiePool;
 
+pub use crate::scrapers::ScraperError;
+
+// User-Agent sent to the mobile API when no User-Agent pool is configured
+const MOBILE_API_USER_AGENT: &str = "Instagram 219.0.0.12.117 Android";
+
+#[derive(Clone)]
 pub struct InstagramScraper {
     config: AppConfig,
     proxy_manager: Option<ProxyManager>,
+    user_agent_index: Arc<AtomicUsize>,
+    // Cookie sets rotated across authenticated requests, None when no cookies are configured
+    cookie_pool: Option<Arc<CookiePool>>,
+    // Clients reused across requests, one per proxy
+    clients: Arc<ClientCache>,
+    in_flight: Arc<AtomicUsize>,
+    stats: Arc<ScrapeStats>,
+    // Bounds the number of concurrent profile scrapes when configured
+    scrape_permits: Option<Arc<Semaphore>>,
+}
+
+// Counts a scrape as in flight until dropped
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl InstagramScraper {
     pub fn new(config: AppConfig, proxy_manager: ProxyManager) -> Self {
         Self { 
-            config,
             proxy_manager: Some(proxy_manager),
+            user_agent_index: Arc::new(AtomicUsize::new(0)),
+            cookie_pool: CookiePool::new(
+                config.instagram_cookies.iter().chain(config.instagram_cookies_pool.iter().flatten()).cloned()
+            ).map(Arc::new),
+            clients: Arc::new(ClientCache::default()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            stats: Arc::new(ScrapeStats::default()),
+            scrape_permits: config.max_concurrent_scrapes.map(|max| Arc::new(Semaphore::new(max))),
+            config,
+        }
+    }
+    
+    /// Wait until no scrape is running, returning false if some are still running after the timeout
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
+    
+    // Delay before a retry: configured base delay plus a random jitter,
+    // so that concurrent retries don't hit the proxies at the same time
+    fn retry_delay(&self) -> Duration {
+        let (base_ms, jitter_ms) = self.config.retry_delay_ms();
+        let jitter = if jitter_ms > 0 {
+            rand::thread_rng().gen_range(0..=jitter_ms)
+        } else {
+            0
+        };
+        Duration::from_millis(base_ms + jitter)
+    }
+    
+    // Client of the requests made through a proxy (direct when None), built on first use then reused
+    // so that connections stay open between requests. The User-Agent is set on each request.
+    fn client(&self, proxy_url: Option<&str>) -> Result<Client, ScraperError> {
+        self.clients.get_or_build(proxy_url, || {
+            // Use the normalized proxy URL with explicit protocol
+            let proxy = proxy_url.map(|proxy| match &self.proxy_manager {
+                Some(proxy_manager) => proxy_manager.normalize_proxy_url(proxy),
+                None => proxy.to_string(),
+            });
+            self.build_client(proxy.as_deref())
+        })
+    }
+    
+    // Build a client sending requests through a proxy URL with an explicit protocol (direct when None)
+    fn build_client(&self, proxy_url: Option<&str>) -> Result<Client, ScraperError> {
+        let client_builder = Client::builder()
+            .timeout(Duration::from_secs(self.config.timeout))
+            .connect_timeout(self.config.connect_timeout())
+            .local_address(self.config.local_address());
+        
+        let client_builder = if let Some(proxy) = proxy_url {
+            info!("Building client for proxy {}", proxy);
+            match Proxy::all(proxy) {
+                Ok(proxy) => client_builder.proxy(proxy),
+                Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
+            }
+        } else {
+            client_builder
+        };
+        
+        client_builder.build()
+            .map_err(|e| ScraperError::ProxyError(format!("Failed to build client: {}", e)))
+    }
+    
+    // Send a request built from the client of a proxy (direct when None). When a proxy given without protocol
+    // can't be connected to, the protocol guessed from its port may be wrong: the request is retried once with
+    // the alternate protocol, which the proxy keeps when the retry succeeds.
+    async fn send(&self, request: RequestBuilder, proxy_url: Option<&str>) -> Result<Response, reqwest::Error> {
+        let retry = request.try_clone();
+        let error = match request.send().await {
+            Ok(response) => return Ok(response),
+            Err(e) => e,
+        };
+        
+        let (proxy, proxy_manager, retry) = match (proxy_url, &self.proxy_manager, retry) {
+            (Some(proxy), Some(proxy_manager), Some(retry)) if error.is_connect() => (proxy, proxy_manager, retry),
+            _ => return Err(error),
+        };
+        let (protocol, alternate_url) = match proxy_manager.alternate_protocol(proxy) {
+            Some(alternate) => alternate,
+            None => return Err(error),
+        };
+        let client = match self.build_client(Some(&alternate_url)) {
+            Ok(client) => client,
+            Err(_) => return Err(error),
+        };
+        
+        warn!("Failed to connect to proxy {}, retrying with protocol {:?}", proxy, protocol);
+        match client.execute(retry.build()?).await {
+            Ok(response) => {
+                proxy_manager.set_proxy_protocol(proxy, protocol);
+                self.clients.replace(proxy, client);
+                Ok(response)
+            }
+            Err(e) => {
+                warn!("Proxy {} also failed with protocol {:?}: {}", proxy, protocol, e);
+                Err(error)
+            }
+        }
+    }
+    
+    // Accept-Language header of scraping requests: the configured one, or the endpoint default
+    fn accept_language<'a>(&'a self, default: &'a str) -> &'a str {
+        self.config.scrape_accept_language.as_deref().unwrap_or(default)
+    }
+    
+    /// Pick a User-Agent from the configured pool.
+    /// A given proxy is always paired with the same User-Agent, direct requests rotate through the pool.
+    /// Returns None when no pool is configured.
+    fn pick_user_agent(&self, proxy_url: Option<&str>) -> Option<String> {
+        let pool = self.config.user_agents.as_ref().filter(|agents| !agents.is_empty())?;
+        
+        let index = match proxy_url {
+            Some(proxy) => {
+                let mut hasher = DefaultHasher::new();
+                proxy.hash(&mut hasher);
+                hasher.finish() as usize
+            },
+            None => self.user_agent_index.fetch_add(1, Ordering::Relaxed),
+        };
+        
+        Some(pool[index % pool.len()].clone())
+    }
+    
+    /// Pick the cookies of an authenticated request from the pool (see `CookiePool::pick`).
+    /// Returns None when no cookies are configured.
+    fn pick_cookies(&self, proxy_url: Option<&str>) -> Option<String> {
+        self.cookie_pool.as_ref().map(|pool| pool.pick(proxy_url))
+    }
+    
+    fn has_cookies(&self) -> bool {
+        self.cookie_pool.is_some()
+    }
+    
+    // Take the cookies of a request out of rotation after Instagram rate limited or challenged them
+    fn cool_down_cookies(&self, cookies: Option<&str>) {
+        if let (Some(pool), Some(cookies)) = (&self.cookie_pool, cookies) {
+            pool.cool_down(cookies);
+        }
+    }
+    
+    // ChallengeRequired error when a response body asks for a checkpoint, cooling down the cookies of the request
+    fn challenge_error(&self, cookies: Option<&str>, body: &str) -> Option<ScraperError> {
+        if !is_challenge_response(body) {
+            return None;
         }
+        error!("Instagram requires a challenge, the account behind the cookies is flagged");
+        self.cool_down_cookies(cookies);
+        Some(ScraperError::ChallengeRequired)
     }
   
+    // Pause between two proxy attempts when configured, so that a failing scrape doesn't burst through the pool
+    async fn wait_between_proxy_attempts(&self) {
+        if self.config.proxy_attempt_delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(self.config.proxy_attempt_delay_ms)).await;
+        }
+    }
+    
+    // Run a scrape once a scrape slot is free, within the configured deadline, dropping it (and any partial
+    // result) when exceeded, and count its outcome. Every public scrape goes through here.
+    async fn with_deadline<T>(&self, username: &str, scrape: impl Future<Output = Result<T, ScraperError>>) -> Result<T, ScraperError> {
+        // Queue behind the running scrapes rather than stampeding the proxies
+        let _permit = self.acquire_scrape_permit(username).await?;
+        
+        let result = match self.config.scrape_deadline {
+            Some(deadline) => tokio::time::timeout(Duration::from_secs(deadline), scrape)
+                .await
+                .unwrap_or_else(|_| {
+                    warn!("Scrape cancelled after {}s deadline", deadline);
+                    Err(ScraperError::Timeout(deadline))
+                }),
+            None => scrape.await,
+        };
+        match &result {
+            Ok(_) => self.stats.record_success(),
+            Err(err) => self.stats.record_error(err),
+        }
+        result
+    }
+    
+    /// Outcome counters of the scrapes since startup, with the number of scrapes currently running
+    pub fn stats(&self) -> ScrapeStatsSnapshot {
+        self.stats.snapshot(self.in_flight.load(Ordering::SeqCst))
+    }
+    
     pub async fn scrape_user(&self, username: &str) -> Result<InstagramUser, ScraperError> {
+        self.with_deadline(username, self.scrape_user_endpoints(username)).await
+    }
+    
+    async fn scrape_user_endpoints(&self, username: &str) -> Result<InstagramUser, ScraperError> {
+        let _in_flight = InFlightGuard::new(&self.in_flight);
         info!("Scraping Instagram user: {}", username);
 
-        // First attempt: Try the mobile API endpoint
-        match self.try_mobile_api_endpoint(username).await {
-            Ok(user) => return Ok(user),
-            Err(ScraperError::AllProxiesFailed) => {
-                warn!("All proxies failed for mobile API endpoint, trying HTML scraping");
-            },
-            Err(e) => {
-                warn!("Mobile API endpoint failed: {}, trying HTML scraping", e);
+        // Try each configured endpoint in turn, returning the error of the last one if all fail
+        let endpoints = self.config.scrape_endpoints();
+        let mut last_error = None;
+        for (index, endpoint) in endpoints.iter().enumerate() {
+            let result = match endpoint.as_str() {
+                "mobile" => self.try_mobile_api_endpoint(username).await,
+                "web" => self.try_web_api_endpoint(username).await,
+                "html" => self.try_html_scraping(username).await,
+                other => Err(ScraperError::ParsingError(format!("Unknown scrape endpoint: {}", other))),
+            };
+            self.stats.record_endpoint(endpoint, result.is_ok());
+            
+            match result {
+                Ok(user) => return Ok(user),
+                Err(e) => {
+                    match endpoints.get(index + 1) {
+                        Some(next) => warn!("{} endpoint failed: {}, trying {} endpoint", endpoint, e, next),
+                        None => error!("{} endpoint failed: {}", endpoint, e),
+                    }
+                    last_error = Some(e);
+                }
             }
         }
         
-        // Second attempt: Try the web API endpoint with proxy rotation
-        match self.try_web_api_endpoint(username).await {
-            Ok(user) => return Ok(user),
-            Err(ScraperError::AllProxiesFailed) => {
-                warn!("All proxies failed for web API endpoint, trying mobile API endpoint");
-            },
-            Err(e) => {
-                warn!("Web API endpoint failed: {}, trying mobile API endpoint", e);
+        Err(last_error.unwrap_or(ScraperError::AllProxiesFailed))
+    }
+    
+    /// Scrape a user through exactly the given proxy, bypassing rotation, retries and proxy health tracking,
+    /// to diagnose why a proxy fails. Each configured endpoint is tried in turn like a regular scrape.
+    pub async fn scrape_user_through_proxy(&self, username: &str, proxy_url: &str) -> Result<InstagramUser, ScraperError> {
+        // Scrape with a one-off client and without the proxy manager, so that the test neither caches a client
+        // for the proxy nor corrects the protocol of a configured proxy. Only its URL is normalized.
+        let proxy_url = match &self.proxy_manager {
+            Some(proxy_manager) => proxy_manager.normalize_proxy_url(proxy_url),
+            None => proxy_url.to_string(),
+        };
+        let scraper = InstagramScraper {
+            proxy_manager: None,
+            clients: Arc::new(ClientCache::default()),
+            ..self.clone()
+        };
+        self.with_deadline(username, scraper.scrape_user_through_proxy_endpoints(username, &proxy_url)).await
+    }
+    
+    async fn scrape_user_through_proxy_endpoints(&self, username: &str, proxy_url: &str) -> Result<InstagramUser, ScraperError> {
+        let _in_flight = InFlightGuard::new(&self.in_flight);
+        info!("Scraping Instagram user {} through forced proxy {}", username, proxy_url);
+
+        let endpoints = self.config.scrape_endpoints();
+        let mut last_error = None;
+        for endpoint in &endpoints {
+            let result = match endpoint.as_str() {
+                "mobile" => {
+                    let url = format!("https://i.instagram.com/api/v1/users/web_profile_info/?username={}", username);
+                    self.make_mobile_api_request(&url, username, Some(proxy_url)).await
+                },
+                "web" => self.make_api_request(&self.web_api_url(username), username, Some(proxy_url)).await,
+                "html" => {
+                    let url = format!("https://www.instagram.com/{}/", username);
+                    self.make_html_request(&url, username, Some(proxy_url)).await
+                },
+                other => Err(ScraperError::ParsingError(format!("Unknown scrape endpoint: {}", other))),
+            };
+            
+            match result {
+                Ok(user) => return Ok(user),
+                Err(e) => {
+                    warn!("{} endpoint failed through forced proxy {}: {}", endpoint, proxy_url, e);
+                    last_error = Some(e);
+                }
             }
         }
         
-     
+        Err(last_error.unwrap_or(ScraperError::AllProxiesFailed))
+    }
+    
+    /// Scrape user with retry logic - retries only when all proxies fail
+    pub async fn scrape_user_with_retry(&self, username: &str) -> Result<InstagramUser, ScraperError> {
+        self.with_deadline(username, self.scrape_user_retrying(username)).await
+    }
+    
+    // Wait for a scrape slot when concurrent scrapes are bounded, for at most `scrape_queue_timeout`
+    async fn acquire_scrape_permit(&self, username: &str) -> Result<Option<OwnedSemaphorePermit>, ScraperError> {
+        let permits = match &self.scrape_permits {
+            Some(permits) => permits,
+            None => return Ok(None),
+        };
         
-        // Third attempt: Try HTML scraping
-        match self.try_html_scraping(username).await {
-            Ok(user) => return Ok(user),
-            Err(e) => {
-                error!("HTML scraping failed: {}", e);
-                return Err(e);
+        let timeout = self.config.scrape_queue_timeout;
+        match tokio::time::timeout(Duration::from_secs(timeout), permits.clone().acquire_owned()).await {
+            Ok(permit) => Ok(Some(permit.map_err(|e| ScraperError::ParsingError(format!("Scrape semaphore closed: {}", e)))?)),
+            Err(_) => {
+                warn!("No scrape slot available for {} after {}s", username, timeout);
+                let err = ScraperError::Busy(timeout);
+                self.stats.record_error(&err);
+                Err(err)
             }
         }
     }
     
-    /// Scrape user with retry logic - retries only when all proxies fail
-    pub async fn scrape_user_with_retry(&self, username: &str) -> Result<InstagramUser, ScraperError> {
+    async fn scrape_user_retrying(&self, username: &str) -> Result<InstagramUser, ScraperError> {
         let mut last_error = None;
         
         for attempt in 0..=self.config.max_retries {
@@ -99,10 +375,10 @@ impl InstagramScraper {
                 info!("Retry attempt {}/{} for user: {}", attempt, self.config.max_retries, username);
                 
                 // Add a small delay between retries to avoid overwhelming the system
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                tokio::time::sleep(self.retry_delay()).await;
             }
             
-            match self.scrape_user(username).await {
+            match self.scrape_user_endpoints(username).await {
                 Ok(user) => {
                     if attempt > 0 {
                         info!("Successfully scraped user {} after {} retries", username, attempt);
@@ -136,7 +412,7 @@ impl InstagramScraper {
     
     async fn try_web_api_endpoint(&self, username: &str) -> Result<InstagramUser, ScraperError> {
         // Request the user's profile page using the API-like endpoint
-        let url = format!("https://www.instagram.com/{}/?__a=1&__d=dis", username);
+        let url = self.web_api_url(username);
         
         info!("Trying web API endpoint for {}", username);
         
@@ -159,20 +435,26 @@ impl InstagramScraper {
             }
             
             // Try up to available_proxies number of proxies
-            for _ in 0..available {
-                if let Some(proxy_url) = proxy_manager.get_random_proxy() {
+            for attempt in 0..available {
+                if attempt > 0 {
+                    self.wait_between_proxy_attempts().await;
+                }
+                if let Some(proxy_url) = proxy_manager.get_proxy_for_key(username) {
                     info!("Trying request with proxy: {}", proxy_url);
                     
                     match self.make_api_request(&url, username, Some(&proxy_url)).await {
                         Ok(result) => {
+                            proxy_manager.mark_proxy_success(&proxy_url);
                             return Ok(result);
                         }
                         Err(err) => {
                             // If it's a proxy error, mark this proxy as unavailable
                             if let ScraperError::ProxyError(msg) = &err {
-                                warn!("Proxy error: {}, marking proxy as unavailable", msg);
-                                proxy_manager.mark_proxy_unavailable(&proxy_url);
+                                warn!("Proxy error: {}, recording proxy failure", msg);
+                                proxy_manager.mark_proxy_failure(&proxy_url);
                             }
+                            // Rotate to another proxy for the next attempt
+                            proxy_manager.release_proxy_for_key(username);
                             last_error = Some(err);
                         }
                     }
@@ -183,48 +465,21 @@ impl InstagramScraper {
             if let Some(err) = last_error {
                 warn!("All proxies failed: {}", err);
             }
-            return Err(ScraperError::AllProxiesFailed);
+            Err(ScraperError::AllProxiesFailed)
         } else {
             // No proxy manager, use the default client
-            return self.make_api_request(&url, username, None).await;
+            self.make_api_request(&url, username, None).await
         }
     }
     
     async fn make_api_request(&self, url: &str, username: &str, proxy_url: Option<&str>) -> Result<InstagramUser, ScraperError> {
-        let client_builder = Client::builder()
-            .timeout(Duration::from_secs(self.config.timeout))
-            .user_agent(&self.config.user_agent);
-            
-        // Add proxy if provided
-        let client_builder = if let Some(proxy) = proxy_url {
-            if let Some(proxy_manager) = &self.proxy_manager {
-                // Use the normalized proxy URL with explicit protocol
-                let normalized_proxy = proxy_manager.normalize_proxy_url(proxy);
-                info!("Using normalized proxy URL: {}", normalized_proxy);
-                match Proxy::all(&normalized_proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            } else {
-                // Fallback to original behavior if no proxy manager
-                match Proxy::all(proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            }
-        } else {
-            client_builder
-        };
-        
-        let client = match client_builder.build() {
-            Ok(client) => client,
-            Err(e) => return Err(ScraperError::ProxyError(format!("Failed to build client: {}", e))),
-        };
+        let client = self.client(proxy_url)?;
         
         // Build request with appropriate headers to mimic a browser
         let mut request = client.get(url)
+            .header("User-Agent", self.pick_user_agent(proxy_url).unwrap_or_else(|| self.config.user_agent.clone()))
             .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8")
-            .header("Accept-Language", "en-US,en;q=0.5")
+            .header("Accept-Language", self.accept_language("en-US,en;q=0.5"))
             .header("Connection", "keep-alive")
             .header("Upgrade-Insecure-Requests", "1")
             .header("Sec-Fetch-Dest", "document")
@@ -234,12 +489,13 @@ impl InstagramScraper {
             .header("TE", "trailers");
         
         // Add cookies if available in config
-        if let Some(cookies) = &self.config.instagram_cookies {
+        let cookies = self.pick_cookies(proxy_url);
+        if let Some(cookies) = &cookies {
             info!("Using Instagram cookies for authentication (limited to first page of posts)");
             request = request.header("Cookie", cookies);
         }
         
-        let response = match request.send().await {
+        let response = match self.send(request, proxy_url).await {
             Ok(resp) => resp,
             Err(e) => {
                 if let Some(_proxy) = proxy_url {
@@ -252,7 +508,8 @@ impl InstagramScraper {
         let status = response.status();
         
         // Log headers for debugging
-        self.log_response_headers(&response, "web API");
+        let retry_after = self.log_response_headers(&response, "web API");
+        let content_type = response_content_type(&response);
         
         if status == reqwest::StatusCode::NOT_FOUND {
             let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
@@ -263,12 +520,16 @@ impl InstagramScraper {
         if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
             let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
             error!("Rate limited by Instagram. Body: {}", body);
-            return Err(ScraperError::RateLimited);
+            self.cool_down_cookies(cookies.as_deref());
+            return Err(ScraperError::RateLimited { retry_after });
         }
         
         if !status.is_success() {
             let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
             error!("Failed to fetch profile, status: {}. Body: {}", status, body);
+            if let Some(err) = self.challenge_error(cookies.as_deref(), &body) {
+                return Err(err);
+            }
             return Err(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
         }
         
@@ -283,8 +544,12 @@ impl InstagramScraper {
                 // Log the response body for debugging
                 info!("Web API response body: {}", text_body);
                 
+                if let Some(err) = self.challenge_error(cookies.as_deref(), &text_body) {
+                    return Err(err);
+                }
+                
                 // Try to parse the JSON
-                match serde_json::from_str::<Value>(&text_body) {
+                match parse_json_lenient(&text_body) {
                     Ok(json_data) => {
                         // Check if the profile is private
                         if let Some(is_private) = json_data.get("graphql")
@@ -309,9 +574,9 @@ impl InstagramScraper {
                             };
                             
                             // Check if we have empty posts but a non-zero post count (pagination issue)
-                            if user_data.posts.as_ref().map_or(false, |p| p.is_empty()) && 
+                            if user_data.posts.as_ref().is_some_and(|p| p.is_empty()) && 
                                user_data.stats.posts_count.unwrap_or(0) > 0 && 
-                               self.config.instagram_cookies.is_some()
+                               self.has_cookies()
                             {
                                 // We can try to fetch additional posts if we have auth cookies
                                 info!("Initial fetch returned no posts but post count > 0. Trying to fetch posts via API...");
@@ -334,6 +599,10 @@ impl InstagramScraper {
                         }
                     },
                     Err(e) => {
+                        if proxy_url.is_some() && is_proxy_block_page(content_type.as_deref(), &text_body) {
+                            warn!("Proxy returned a block page instead of the web API response for {}", username);
+                            return Err(ScraperError::ProxyError("Proxy returned a block page".to_string()));
+                        }
                         if text_body.trim().is_empty() {
                             error!("Failed to parse JSON response: {}. Error: {}, Response body is empty", username, e);
                         } else if text_body.len() < 100 {
@@ -380,20 +649,26 @@ impl InstagramScraper {
             }
             
             // Try up to available_proxies number of proxies
-            for _ in 0..available {
-                if let Some(proxy_url) = proxy_manager.get_random_proxy() {
+            for attempt in 0..available {
+                if attempt > 0 {
+                    self.wait_between_proxy_attempts().await;
+                }
+                if let Some(proxy_url) = proxy_manager.get_proxy_for_key(username) {
                     info!("Trying mobile API request with proxy: {}", proxy_url);
                     
                     match self.make_mobile_api_request(&url, username, Some(&proxy_url)).await {
                         Ok(result) => {
+                            proxy_manager.mark_proxy_success(&proxy_url);
                             return Ok(result);
                         }
                         Err(err) => {
                             // If it's a proxy error, mark this proxy as unavailable
                             if let ScraperError::ProxyError(msg) = &err {
-                                warn!("Proxy error: {}, marking proxy as unavailable", msg);
-                                proxy_manager.mark_proxy_unavailable(&proxy_url);
+                                warn!("Proxy error: {}, recording proxy failure", msg);
+                                proxy_manager.mark_proxy_failure(&proxy_url);
                             }
+                            // Rotate to another proxy for the next attempt
+                            proxy_manager.release_proxy_for_key(username);
                             last_error = Some(err);
                         }
                     }
@@ -404,60 +679,33 @@ impl InstagramScraper {
             if let Some(err) = last_error {
                 warn!("All proxies failed for mobile API request: {}", err);
             }
-            return Err(ScraperError::AllProxiesFailed);
+            Err(ScraperError::AllProxiesFailed)
         } else {
             // No proxy manager, use the default client
-            return self.make_mobile_api_request(&url, username, None).await;
+            self.make_mobile_api_request(&url, username, None).await
         }
     }
     
     async fn make_mobile_api_request(&self, url: &str, username: &str, proxy_url: Option<&str>) -> Result<InstagramUser, ScraperError> {
-        let client_builder = Client::builder()
-            .timeout(Duration::from_secs(self.config.timeout))
-            .user_agent("Instagram 76.0.0.15.395 Android (28/9; 420dpi; 1080x2034; OnePlus; ONEPLUS A6003; OnePlus6; qcom; en_US; 139064830)");
-            
-        // Add proxy if provided
-        let client_builder = if let Some(proxy) = proxy_url {
-            if let Some(proxy_manager) = &self.proxy_manager {
-                // Use the normalized proxy URL with explicit protocol
-                let normalized_proxy = proxy_manager.normalize_proxy_url(proxy);
-                info!("Using normalized proxy URL: {}", normalized_proxy);
-                match Proxy::all(&normalized_proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            } else {
-                // Fallback to original behavior if no proxy manager
-                match Proxy::all(proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            }
-        } else {
-            client_builder
-        };
-        
-        let client = match client_builder.build() {
-            Ok(client) => client,
-            Err(e) => return Err(ScraperError::ProxyError(format!("Failed to build client: {}", e))),
-        };
+        let client = self.client(proxy_url)?;
         
         // Build request with mobile API specific headers
         let mut request = client.get(url)
-            .header("User-Agent", "Instagram 219.0.0.12.117 Android")
+            .header("User-Agent", self.pick_user_agent(proxy_url).unwrap_or_else(|| MOBILE_API_USER_AGENT.to_string()))
             .header("Accept", "application/json")
-            .header("Accept-Language", "en-US")
+            .header("Accept-Language", self.accept_language("en-US"))
             .header("X-IG-App-ID", "936619743392459")
             .header("X-ASBD-ID", "198387")
             .header("X-IG-WWW-Claim", "0");
         
         // Add cookies if available in config
-        if let Some(cookies) = &self.config.instagram_cookies {
+        let cookies = self.pick_cookies(proxy_url);
+        if let Some(cookies) = &cookies {
             info!("Using Instagram cookies for mobile API authentication (limited to first page of posts)");
             request = request.header("Cookie", cookies);
         }
         
-        let response = match request.send().await {
+        let response = match self.send(request, proxy_url).await {
             Ok(resp) => resp,
             Err(e) => {
                 if let Some(_proxy) = proxy_url {
@@ -471,6 +719,7 @@ impl InstagramScraper {
         
         // Log headers for debugging
         self.log_response_headers(&response, "mobile API");
+        let content_type = response_content_type(&response);
         
         if status == reqwest::StatusCode::NOT_FOUND {
             let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
@@ -478,15 +727,25 @@ impl InstagramScraper {
             return Err(ScraperError::ProfileNotFound);
         }
         
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            self.cool_down_cookies(cookies.as_deref());
+        }
+        
         if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
             let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
             error!("Unauthorized access to mobile API (cookies may be required): {}. Body: {}", username, body);
+            if let Some(err) = self.challenge_error(cookies.as_deref(), &body) {
+                return Err(err);
+            }
             return Err(ScraperError::UnauthorizedAccess(body));
         }
         
         if !status.is_success() {
             let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
             error!("Failed to fetch profile via mobile API, status: {}. Body: {}", status, body);
+            if let Some(err) = self.challenge_error(cookies.as_deref(), &body) {
+                return Err(err);
+            }
             return Err(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
         }
         
@@ -501,11 +760,15 @@ impl InstagramScraper {
                 // Log the response body for debugging
                 info!("Mobile API response body: {}", text_body);
                 
+                if let Some(err) = self.challenge_error(cookies.as_deref(), &text_body) {
+                    return Err(err);
+                }
+                
                 // Try to parse the JSON
-                match serde_json::from_str::<Value>(&text_body) {
+                match parse_json_lenient(&text_body) {
                     Ok(json_data) => {
                         // Log the complete JSON structure if authentication is used
-                        if self.config.instagram_cookies.is_some() {
+                        if self.has_cookies() {
                             debug!("Mobile API authenticated response structure: {}", 
                                   serde_json::to_string_pretty(&json_data)
                                   .unwrap_or_else(|_| "Failed to format JSON".to_string()));
@@ -530,9 +793,9 @@ impl InstagramScraper {
                             };
                             
                             // Check if we have empty posts but a non-zero post count (pagination issue)
-                            if user_data.posts.as_ref().map_or(false, |p| p.is_empty()) && 
+                            if user_data.posts.as_ref().is_some_and(|p| p.is_empty()) && 
                                user_data.stats.posts_count.unwrap_or(0) > 0 && 
-                               self.config.instagram_cookies.is_some()
+                               self.has_cookies()
                             {
                                 // We can try to fetch additional posts if we have auth cookies
                                 info!("Initial fetch returned no posts but post count > 0. Trying to fetch posts via API...");
@@ -555,6 +818,10 @@ impl InstagramScraper {
                         }
                     },
                     Err(e) => {
+                        if proxy_url.is_some() && is_proxy_block_page(content_type.as_deref(), &text_body) {
+                            warn!("Proxy returned a block page instead of the mobile API response for {}", username);
+                            return Err(ScraperError::ProxyError("Proxy returned a block page".to_string()));
+                        }
                         if text_body.trim().is_empty() {
                             error!("Failed to parse mobile API JSON response: {}. Error: {}, Response body is empty", username, e);
                         } else if text_body.len() < 100 {
@@ -601,20 +868,26 @@ impl InstagramScraper {
             }
             
             // Try up to available_proxies number of proxies
-            for _ in 0..available {
-                if let Some(proxy_url) = proxy_manager.get_random_proxy() {
+            for attempt in 0..available {
+                if attempt > 0 {
+                    self.wait_between_proxy_attempts().await;
+                }
+                if let Some(proxy_url) = proxy_manager.get_proxy_for_key(username) {
                     info!("Trying HTML request with proxy: {}", proxy_url);
                     
                     match self.make_html_request(&url, username, Some(&proxy_url)).await {
                         Ok(result) => {
+                            proxy_manager.mark_proxy_success(&proxy_url);
                             return Ok(result);
                         }
                         Err(err) => {
                             // If it's a proxy error, mark this proxy as unavailable
                             if let ScraperError::ProxyError(msg) = &err {
-                                warn!("Proxy error: {}, marking proxy as unavailable", msg);
-                                proxy_manager.mark_proxy_unavailable(&proxy_url);
+                                warn!("Proxy error: {}, recording proxy failure", msg);
+                                proxy_manager.mark_proxy_failure(&proxy_url);
                             }
+                            // Rotate to another proxy for the next attempt
+                            proxy_manager.release_proxy_for_key(username);
                             last_error = Some(err);
                         }
                     }
@@ -625,56 +898,30 @@ impl InstagramScraper {
             if let Some(err) = last_error {
                 warn!("All proxies failed for HTML scraping: {}", err);
             }
-            return Err(ScraperError::AllProxiesFailed);
+            Err(ScraperError::AllProxiesFailed)
         } else {
             // No proxy manager, use the default client
-            return self.make_html_request(&url, username, None).await;
+            self.make_html_request(&url, username, None).await
         }
     }
     
     async fn make_html_request(&self, url: &str, username: &str, proxy_url: Option<&str>) -> Result<InstagramUser, ScraperError> {
-        let client_builder = Client::builder()
-            .timeout(Duration::from_secs(self.config.timeout))
-            .user_agent(&self.config.user_agent);
-            
-        // Add proxy if provided
-        let client_builder = if let Some(proxy) = proxy_url {
-            if let Some(proxy_manager) = &self.proxy_manager {
-                // Use the normalized proxy URL with explicit protocol
-                let normalized_proxy = proxy_manager.normalize_proxy_url(proxy);
-                info!("Using normalized proxy URL: {}", normalized_proxy);
-                match Proxy::all(&normalized_proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            } else {
-                // Fallback to original behavior if no proxy manager
-                match Proxy::all(proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            }
-        } else {
-            client_builder
-        };
-        
-        let client = match client_builder.build() {
-            Ok(client) => client,
-            Err(e) => return Err(ScraperError::ProxyError(format!("Failed to build client: {}", e))),
-        };
+        let client = self.client(proxy_url)?;
         
         // Build request with appropriate headers for HTML page
         let mut request = client.get(url)
+            .header("User-Agent", self.pick_user_agent(proxy_url).unwrap_or_else(|| self.config.user_agent.clone()))
             .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8")
-            .header("Accept-Language", "en-US,en;q=0.5");
+            .header("Accept-Language", self.accept_language("en-US,en;q=0.5"));
         
         // Add cookies if available
-        if let Some(cookies) = &self.config.instagram_cookies {
+        let cookies = self.pick_cookies(proxy_url);
+        if let Some(cookies) = &cookies {
             info!("Using Instagram cookies for HTML scraping (limited to first page of posts)");
             request = request.header("Cookie", cookies);
         }
         
-        let response = match request.send().await {
+        let response = match self.send(request, proxy_url).await {
             Ok(resp) => resp,
             Err(e) => {
                 if let Some(_proxy) = proxy_url {
@@ -701,16 +948,40 @@ impl InstagramScraper {
             return Err(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
         }
         
+        // Logged-out visitors get redirected to the login page instead of the profile
+        let redirected_to_login = response.url().path().starts_with("/accounts/login");
+        
         // Get the HTML content
         match response.text().await {
             Ok(html) => {
+                if redirected_to_login || is_login_wall(&html) {
+                    error!("Login wall returned instead of the profile HTML of {}", username);
+                    self.cool_down_cookies(cookies.as_deref());
+                    let message = if cookies.is_some() {
+                        "Instagram requires a login, the configured instagram_cookies may have expired"
+                    } else {
+                        "Instagram requires a login, set instagram_cookies to authenticate"
+                    };
+                    return Err(ScraperError::UnauthorizedAccess(message.to_string()));
+                }
+                
                 if html.is_empty() {
                     error!("Empty HTML response body for {}", username);
                     return Err(ScraperError::ParsingError("Empty response body".to_string()));
                 }
                 
-                // If response is too short, it might be a captcha or error page
-                if html.len() < 1000 {
+                if proxy_url.is_some() && has_proxy_block_marker(&html) {
+                    warn!("Proxy returned a block page instead of the profile HTML of {}", username);
+                    return Err(ScraperError::ProxyError("Proxy returned a block page".to_string()));
+                }
+                
+                if is_html_block_page(&html) {
+                    error!("Block page returned instead of the profile HTML of {}. Body: {}", username, html);
+                    return Err(ScraperError::ParsingError("HTML response is a block page (captcha or rate limit)".to_string()));
+                }
+                
+                // A short page not even mentioning the user is likely an error page, while tiny profiles are legit
+                if html.len() < self.config.html_min_length && !html.to_lowercase().contains(&username.to_lowercase()) {
                     error!("HTML response too short (likely blocked or captcha): {}. Body: {}", username, html);
                     return Err(ScraperError::ParsingError("HTML response too short, likely blocked".to_string()));
                 }
@@ -740,7 +1011,7 @@ impl InstagramScraper {
     }
     
     fn extract_user_data_from_json(&self, data: &Value, username: &str) -> Option<InstagramUser> {
-        // This handles the JSON format for the ?__a=1&__d=dis endpoint
+        // This handles the JSON format of the web API endpoint (?__a=1&__d=dis by default)
         let user = data.get("graphql")?.get("user")?;
         
         let now = Utc::now();
@@ -752,9 +1023,9 @@ impl InstagramScraper {
         
         // Get stats early so we can use post count later
         let stats = InstagramUserStats {
-            posts_count: user.get("edge_owner_to_timeline_media")?.get("count")?.as_u64(),
-            followers_count: user.get("edge_followed_by")?.get("count")?.as_u64(),
-            following_count: user.get("edge_follow")?.get("count")?.as_u64(),
+            posts_count: parse_count(user.get("edge_owner_to_timeline_media")?.get("count")?),
+            followers_count: parse_count(user.get("edge_followed_by")?.get("count")?),
+            following_count: parse_count(user.get("edge_follow")?.get("count")?),
         };
         
         // We'll still create the user object even for private profiles,
@@ -762,11 +1033,11 @@ impl InstagramScraper {
         if !is_private {
             if let Some(timeline) = user.get("edge_owner_to_timeline_media") {
                 // Check if the post count is greater than our limit
-                if let Some(count) = timeline.get("count").and_then(|v| v.as_u64()) {
-                    if count > 12 { // Instagram typically shows 12 posts per page
+                if let Some(count) = timeline.get("count").and_then(parse_count) {
+                    if count > self.config.instagram_page_size {
                         posts_limited = true;
-                        info!("Posts will be limited to first page (about 12 posts) of {} available for {}", 
-                                count, username);
+                        info!("Posts will be limited to first page (about {} posts) of {} available for {}", 
+                                self.config.instagram_page_size, count, username);
                     }
                 }
                 
@@ -787,17 +1058,7 @@ impl InstagramScraper {
                 if !post_vec.is_empty() {
                     let video_posts: Vec<InstagramReel> = post_vec.iter()
                         .filter(|post| post.is_video)
-                        .map(|post| InstagramReel {
-                            id: post.id.clone(),
-                            shortcode: post.shortcode.clone(),
-                            display_url: post.display_url.clone(),
-                            video_url: post.video_url.clone(),
-                            caption: post.caption.clone(),
-                            views_count: post.video_view_count,
-                            likes_count: post.likes_count,
-                            comments_count: post.comments_count,
-                            timestamp: post.timestamp,
-                        })
+                        .map(InstagramReel::from)
                         .collect();
                     
                     if !video_posts.is_empty() {
@@ -813,6 +1074,8 @@ impl InstagramScraper {
             }
         }
         
+        let (category, is_business, account_type, business_contact_method) = self.extract_account_info(user);
+        
         Some(InstagramUser {
             username: username.to_string(),
             full_name: user.get("full_name").and_then(|v| v.as_str()).map(str::to_string),
@@ -824,6 +1087,10 @@ impl InstagramScraper {
             is_private,
             is_verified: user.get("is_verified").and_then(|v| v.as_bool()).unwrap_or(false),
             external_url: user.get("external_url").and_then(|v| v.as_str()).map(str::to_string),
+            category,
+            is_business,
+            account_type,
+            business_contact_method,
             stats,
             posts,
             reels,
@@ -832,13 +1099,49 @@ impl InstagramScraper {
         })
     }
     
+    // Extract category, business flag, account type (personal/business/creator) and business contact method from user JSON
+    fn extract_account_info(&self, user: &Value) -> (Option<String>, bool, Option<String>, Option<String>) {
+        let category = user.get("category_name")
+            .or_else(|| user.get("business_category_name"))
+            .or_else(|| user.get("category"))
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
+        
+        let is_business_flag = user.get("is_business_account").and_then(|v| v.as_bool());
+        let is_professional_flag = user.get("is_professional_account").and_then(|v| v.as_bool());
+        
+        // Numeric account_type is returned by the mobile API: 1 = personal, 2 = business, 3 = creator
+        let account_type = match user.get("account_type").and_then(|v| v.as_u64()) {
+            Some(1) => Some("personal".to_string()),
+            Some(2) => Some("business".to_string()),
+            Some(3) => Some("creator".to_string()),
+            _ => match (is_business_flag, is_professional_flag) {
+                (Some(true), _) => Some("business".to_string()),
+                (_, Some(true)) => Some("creator".to_string()),
+                (Some(false), _) | (_, Some(false)) => Some("personal".to_string()),
+                (None, None) => None,
+            },
+        };
+        
+        let is_business = is_business_flag.unwrap_or(account_type.as_deref() == Some("business"));
+        
+        // "UNKNOWN" is returned for accounts without a preference
+        let business_contact_method = user.get("business_contact_method")
+            .and_then(|v| v.as_str())
+            .map(str::to_lowercase)
+            .filter(|v| !v.is_empty() && v != "unknown");
+        
+        (category, is_business, account_type, business_contact_method)
+    }
+    
     fn extract_posts_from_json(&self, timeline: &Value) -> Option<Vec<InstagramPost>> {
         let edges = timeline.get("edges")?.as_array()?;
         let mut posts = Vec::new();
         
         // If edges is empty but there's a count, return an empty array instead of None
         if edges.is_empty() {
-            if let Some(count) = timeline.get("count").and_then(|v| v.as_u64()) {
+            if let Some(count) = timeline.get("count").and_then(parse_count) {
                 if count > 0 {
                     info!("Found timeline with {} posts but edges array is empty (pagination). Returning empty posts array.", count);
                     return Some(Vec::new());
@@ -849,28 +1152,37 @@ impl InstagramScraper {
         for edge in edges {
             let node = edge.get("node")?;
             
+            let caption = node.get("edge_media_to_caption")
+                .and_then(|v| v.get("edges"))
+                .and_then(|v| v.as_array())
+                .and_then(|arr| arr.first())
+                .and_then(|v| v.get("node"))
+                .and_then(|v| v.get("text"))
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            let (hashtags, mentions) = extract_caption_tags(caption.as_deref());
+            let timestamp = node.get("taken_at_timestamp")
+                .and_then(|v| v.as_i64())
+                .and_then(|ts| Utc.timestamp_opt(ts, 0).single());
+            
             let post = InstagramPost {
                 id: node.get("id")?.as_str()?.to_string(),
                 shortcode: node.get("shortcode")?.as_str()?.to_string(),
-                display_url: node.get("display_url")?.as_str()?.to_string(),
+                display_url: Some(node.get("display_url")?.as_str()?.to_string()),
                 thumbnail_url: node.get("thumbnail_src").and_then(|v| v.as_str()).map(str::to_string),
-                caption: node.get("edge_media_to_caption")
-                    .and_then(|v| v.get("edges"))
-                    .and_then(|v| v.as_array())
-                    .and_then(|arr| arr.first())
-                    .and_then(|v| v.get("node"))
-                    .and_then(|v| v.get("text"))
-                    .and_then(|v| v.as_str())
-                    .map(str::to_string),
+                resolutions: self.extract_resolutions(node),
+                caption,
+                alt_text: accessibility_caption(node),
+                hashtags,
+                mentions,
                 likes_count: node.get("edge_liked_by")
                     .and_then(|v| v.get("count"))
-                    .and_then(|v| v.as_u64()),
+                    .and_then(parse_count),
                 comments_count: node.get("edge_media_to_comment")
                     .and_then(|v| v.get("count"))
-                    .and_then(|v| v.as_u64()),
-                timestamp: node.get("taken_at_timestamp")
-                    .and_then(|v| v.as_i64())
-                    .and_then(|ts| Utc.timestamp_opt(ts, 0).single()),
+                    .and_then(parse_count),
+                timestamp,
+                timestamp_unix: timestamp.map(|timestamp| timestamp.timestamp()),
                 is_video: node.get("is_video")
                     .and_then(|v| v.as_bool())
                     .unwrap_or(false),
@@ -880,10 +1192,26 @@ impl InstagramScraper {
                     None
                 },
                 video_view_count: if node.get("is_video").and_then(|v| v.as_bool()).unwrap_or(false) {
-                    node.get("video_view_count").and_then(|v| v.as_u64())
+                    node.get("video_view_count").and_then(parse_count)
+                } else {
+                    None
+                },
+                video_duration: if node.get("is_video").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    node.get("video_duration").and_then(|v| v.as_f64())
+                } else {
+                    None
+                },
+                video_width: if node.get("is_video").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    node.get("dimensions").and_then(|v| v.get("width")).and_then(|v| v.as_u64()).map(|v| v as u32)
+                } else {
+                    None
+                },
+                video_height: if node.get("is_video").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    node.get("dimensions").and_then(|v| v.get("height")).and_then(|v| v.as_u64()).map(|v| v as u32)
                 } else {
                     None
                 },
+                carousel: self.extract_carousel(node),
             };
             
             posts.push(post);
@@ -914,26 +1242,38 @@ impl InstagramScraper {
         // Try to extract from a newer pattern - look for script with type="application/json"
         let html_doc = Html::parse_document(html);
         let script_selector = Selector::parse("script[type='application/json']").ok()?;
+        let scripts: Vec<Value> = html_doc.select(&script_selector)
+            .filter_map(|script| serde_json::from_str::<Value>(&script.text().collect::<String>()).ok())
+            .collect();
+        
+        for json in &scripts {
+            // Look for user data in various locations within the JSON
+            if let Some(data) = json.get("require")
+                .and_then(|v| v.as_array())
+                .and_then(|arr| arr.iter().find(|item| 
+                    item.get(0).and_then(|v| v.as_str()).unwrap_or("") == "ProfilePageContainer"
+                ))
+                .and_then(|item| item.get(3))
+                .and_then(|v| v.get("user")) {
+                
+                return self.extract_user_data_from_api_response(data, username);
+            }
+        }
         
-        for script in html_doc.select(&script_selector) {
-            if let Some(content) = script.text().next() {
-                if let Ok(json) = serde_json::from_str::<Value>(content) {
-                    // Look for user data in various locations within the JSON
-                    if let Some(data) = json.get("require")
-                        .and_then(|v| v.as_array())
-                        .and_then(|arr| arr.iter().find(|item| 
-                            item.get(0).and_then(|v| v.as_str()).unwrap_or("") == "ProfilePageContainer"
-                        ))
-                        .and_then(|item| item.get(3))
-                        .and_then(|v| v.get("user")) {
-                        
-                        return self.extract_user_data_from_api_response(data, username);
-                    }
-                }
+        // Since the redesign, the results of the page queries are embedded in `data-sjs` scripts under `__bbox`,
+        // the profile (PolarisProfilePageContentQuery) and its first posts coming in separate results
+        let user = scripts.iter().find_map(|json| find_bbox_data(json, &|data| bbox_profile_user(data, username)))?;
+        let mut user = user.clone();
+        let has_timeline = ["edge_owner_to_timeline_media", "items", "feed"].iter().any(|key| user.get(key).is_some());
+        if !has_timeline {
+            if let Some(items) = scripts.iter().find_map(|json| find_bbox_data(json, &bbox_timeline_items)) {
+                debug!("Found {} posts in the embedded timeline of {}", items.len(), username);
+                user["items"] = Value::Array(items);
             }
         }
         
-        None
+        info!("Extracted profile of {} from the embedded page query results", username);
+        self.extract_user_data_from_api_response(&user, username)
     }
     
     fn extract_user_data_from_api_response(&self, data: &Value, username: &str) -> Option<InstagramUser> {
@@ -944,12 +1284,12 @@ impl InstagramScraper {
         
         // Extract stats
         let stats = InstagramUserStats {
-            posts_count: data.get("media_count").and_then(|v| v.as_u64())
-                .or_else(|| data.get("edge_owner_to_timeline_media").and_then(|v| v.get("count")).and_then(|v| v.as_u64())),
-            followers_count: data.get("follower_count").and_then(|v| v.as_u64())
-                .or_else(|| data.get("edge_followed_by").and_then(|v| v.get("count")).and_then(|v| v.as_u64())),
-            following_count: data.get("following_count").and_then(|v| v.as_u64())
-                .or_else(|| data.get("edge_follow").and_then(|v| v.get("count")).and_then(|v| v.as_u64())),
+            posts_count: data.get("media_count").and_then(parse_count)
+                .or_else(|| data.get("edge_owner_to_timeline_media").and_then(|v| v.get("count")).and_then(parse_count)),
+            followers_count: data.get("follower_count").and_then(parse_count)
+                .or_else(|| data.get("edge_followed_by").and_then(|v| v.get("count")).and_then(parse_count)),
+            following_count: data.get("following_count").and_then(parse_count)
+                .or_else(|| data.get("edge_follow").and_then(|v| v.get("count")).and_then(parse_count)),
         };
         
         // Extract posts and reels if available
@@ -960,10 +1300,10 @@ impl InstagramScraper {
         if !is_private {
             // Check if we should limit posts based on the stats
             if let Some(count) = stats.posts_count {
-                if count > 12 { // Instagram typically shows 12 posts per page
+                if count > self.config.instagram_page_size {
                     posts_limited = true;
-                    info!("Posts will be limited to first page (about 12 posts) of {} available for {}", 
-                            count, username);
+                    info!("Posts will be limited to first page (about {} posts) of {} available for {}", 
+                            self.config.instagram_page_size, count, username);
                 }
             }
             
@@ -1042,40 +1382,19 @@ impl InstagramScraper {
                 
                 let extracted = self.extract_posts_from_json(reels_data);
                 if let Some(reels_vec) = extracted {
-                    reels = Some(reels_vec.into_iter()
+                    reels = Some(reels_vec.iter()
                                 .filter(|post| post.is_video)
-                                .map(|post| InstagramReel {
-                                    id: post.id.clone(),
-                                    shortcode: post.shortcode.clone(),
-                                    display_url: post.display_url.clone(),
-                                    video_url: post.video_url.clone(),
-                                    caption: post.caption.clone(),
-                                    views_count: post.video_view_count,
-                                    likes_count: post.likes_count,
-                                    comments_count: post.comments_count,
-                                    timestamp: post.timestamp,
-                                })
+                                .map(InstagramReel::from)
                                 .collect());
                 }
             }
             
             // If no reels found directly, derive from posts
-            if reels.is_none() && posts.is_some() {
-                let post_vec = posts.as_ref().unwrap();
+            if let (None, Some(post_vec)) = (&reels, &posts) {
                 if !post_vec.is_empty() {
                     let video_posts: Vec<InstagramReel> = post_vec.iter()
                         .filter(|post| post.is_video)
-                        .map(|post| InstagramReel {
-                            id: post.id.clone(),
-                            shortcode: post.shortcode.clone(),
-                            display_url: post.display_url.clone(),
-                            video_url: post.video_url.clone(),
-                            caption: post.caption.clone(),
-                            views_count: post.video_view_count,
-                            likes_count: post.likes_count,
-                            comments_count: post.comments_count,
-                            timestamp: post.timestamp,
-                        })
+                        .map(InstagramReel::from)
                         .collect();
                     
                     if !video_posts.is_empty() {
@@ -1091,6 +1410,12 @@ impl InstagramScraper {
             }
         }
         
+        // The fallbacks above can capture the same media twice
+        let posts = posts.map(|posts| dedup_by_id(posts, |post| &post.id));
+        let reels = reels.map(|reels| dedup_by_id(reels, |reel| &reel.id));
+        
+        let (category, is_business, account_type, business_contact_method) = self.extract_account_info(data);
+        
         Some(InstagramUser {
             username: username.to_string(),
             full_name: data.get("full_name").and_then(|v| v.as_str()).map(str::to_string),
@@ -1102,6 +1427,10 @@ impl InstagramScraper {
             is_private,
             is_verified: data.get("is_verified").and_then(|v| v.as_bool()).unwrap_or(false),
             external_url: data.get("external_url").and_then(|v| v.as_str()).map(str::to_string),
+            category,
+            is_business,
+            account_type,
+            business_contact_method,
             stats,
             posts,
             reels,
@@ -1110,6 +1439,83 @@ impl InstagramScraper {
         })
     }
     
+    // Extract the media of a carousel post from `carousel_media` (API items) or `edge_sidecar_to_children` (graphql nodes)
+    fn extract_carousel(&self, node: &Value) -> Vec<InstagramCarouselItem> {
+        let children: Vec<&Value> = if let Some(media) = node.get("carousel_media").and_then(|v| v.as_array()) {
+            media.iter().collect()
+        } else if let Some(edges) = node.get("edge_sidecar_to_children")
+            .and_then(|v| v.get("edges"))
+            .and_then(|v| v.as_array()) {
+            edges.iter().filter_map(|edge| edge.get("node")).collect()
+        } else {
+            return Vec::new();
+        };
+        
+        children.into_iter().filter_map(|child| {
+            let id = child.get("id").and_then(|v| v.as_str()).map(str::to_string)
+                .or_else(|| child.get("pk").and_then(|v| v.as_u64()).map(|pk| pk.to_string()))?;
+            let display_url = child.get("display_url").and_then(|v| v.as_str()).map(str::to_string)
+                .or_else(|| self.extract_resolutions(child).into_iter().next().map(|version| version.url))?;
+            let is_video = child.get("is_video").and_then(|v| v.as_bool()).unwrap_or(false)
+                || child.get("media_type").and_then(|v| v.as_u64()) == Some(2);
+            let video_url = if is_video {
+                child.get("video_url").and_then(|v| v.as_str())
+                    .or_else(|| child.get("video_versions")
+                        .and_then(|v| v.as_array())
+                        .and_then(|versions| versions.first())
+                        .and_then(|version| version.get("url"))
+                        .and_then(|v| v.as_str()))
+                    .map(str::to_string)
+            } else {
+                None
+            };
+            let dimension = |api_key: &str, graphql_key: &str| {
+                child.get(api_key)
+                    .or_else(|| child.get("dimensions").and_then(|d| d.get(graphql_key)))
+                    .and_then(|v| v.as_u64())
+                    .map(|v| v as u32)
+            };
+            
+            Some(InstagramCarouselItem {
+                id,
+                display_url,
+                is_video,
+                video_url,
+                width: dimension("original_width", "width"),
+                height: dimension("original_height", "height"),
+            })
+        }).collect()
+    }
+    
+    // Extract image sizes (largest first) from `image_versions2.candidates` (API items) or `display_resources` (graphql nodes)
+    fn extract_resolutions(&self, node: &Value) -> Vec<ImageVersion> {
+        let (candidates, url_key, width_key, height_key) = if let Some(candidates) = node
+            .get("image_versions2")
+            .and_then(|v| v.get("candidates"))
+            .and_then(|v| v.as_array())
+        {
+            (candidates, "url", "width", "height")
+        } else if let Some(resources) = node.get("display_resources").and_then(|v| v.as_array()) {
+            (resources, "src", "config_width", "config_height")
+        } else {
+            return Vec::new();
+        };
+        
+        let mut versions: Vec<ImageVersion> = candidates
+            .iter()
+            .filter_map(|candidate| {
+                Some(ImageVersion {
+                    width: candidate.get(width_key).and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+                    height: candidate.get(height_key).and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+                    url: candidate.get(url_key)?.as_str()?.to_string(),
+                })
+            })
+            .collect();
+        
+        versions.sort_by_key(|version| std::cmp::Reverse(version.width as u64 * version.height as u64));
+        versions
+    }
+    
     fn extract_posts_from_items(&self, items: &[Value]) -> Option<Vec<InstagramPost>> {
         let mut posts = Vec::new();
         
@@ -1160,32 +1566,24 @@ impl InstagramScraper {
                 || item.get("product_type").and_then(|v| v.as_str()).unwrap_or("") == "igtv"
                 || item.get("media").and_then(|m| m.get("media_type").and_then(|v| v.as_u64())).unwrap_or(1) == 2;
             
-            // Extract display URL (main image) - this can be in many different places
-            let display_url = item.get("display_url").and_then(|v| v.as_str())
-                .or_else(|| item.get("image_versions2")
-                    .and_then(|v| v.get("candidates"))
-                    .and_then(|v| v.as_array())
-                    .and_then(|arr| arr.first())
-                    .and_then(|v| v.get("url").and_then(|u| u.as_str())))
+            // Extract available image sizes, from the item itself or its first carousel/media entry
+            let resolutions = Some(self.extract_resolutions(item))
+                .filter(|versions| !versions.is_empty())
                 .or_else(|| item.get("carousel_media")
                     .and_then(|v| v.as_array())
                     .and_then(|arr| arr.first())
-                    .and_then(|v| v.get("image_versions2"))
-                    .and_then(|v| v.get("candidates"))
-                    .and_then(|v| v.as_array())
-                    .and_then(|arr| arr.first())
-                    .and_then(|v| v.get("url").and_then(|u| u.as_str())))
-                .or_else(|| item.get("media")
-                    .and_then(|m| m.get("image_versions2"))
-                    .and_then(|v| v.get("candidates"))
-                    .and_then(|v| v.as_array())
-                    .and_then(|arr| arr.first())
-                    .and_then(|v| v.get("url").and_then(|u| u.as_str())))
+                    .map(|v| self.extract_resolutions(v)))
+                .filter(|versions| !versions.is_empty())
+                .or_else(|| item.get("media").map(|m| self.extract_resolutions(m)))
+                .unwrap_or_default();
+            
+            // Extract display URL (main image), defaulting to the highest resolution
+            let display_url = item.get("display_url").and_then(|v| v.as_str())
+                .or_else(|| resolutions.first().map(|version| version.url.as_str()))
                 .or_else(|| item.get("media")
                     .and_then(|m| m.get("thumbnail_url"))
                     .and_then(|v| v.as_str()))
-                .unwrap_or("https://example.com/placeholder.jpg")
-                .to_string();
+                .map(str::to_string);
             
             // Extract thumbnail URL - sometimes different from display URL
             let thumbnail_url = item.get("thumbnail_src").and_then(|v| v.as_str())
@@ -1219,20 +1617,22 @@ impl InstagramScraper {
                     })
             });
             
+            let (hashtags, mentions) = extract_caption_tags(caption.as_deref());
+            
             // Extract likes count
-            let likes_count = item.get("like_count").and_then(|v| v.as_u64())
-                .or_else(|| item.get("likes").and_then(|v| v.get("count")).and_then(|v| v.as_u64()))
+            let likes_count = item.get("like_count").and_then(parse_count)
+                .or_else(|| item.get("likes").and_then(|v| v.get("count")).and_then(parse_count))
                 .or_else(|| item.get("media")
                     .and_then(|m| m.get("like_count"))
-                    .and_then(|v| v.as_u64()));
+                    .and_then(parse_count));
             
             // Extract comments count
-            let comments_count = item.get("comment_count").and_then(|v| v.as_u64())
-                .or_else(|| item.get("comments").and_then(|v| v.get("count")).and_then(|v| v.as_u64()))
-                .or_else(|| item.get("comments_count").and_then(|v| v.as_u64()))
+            let comments_count = item.get("comment_count").and_then(parse_count)
+                .or_else(|| item.get("comments").and_then(|v| v.get("count")).and_then(parse_count))
+                .or_else(|| item.get("comments_count").and_then(parse_count))
                 .or_else(|| item.get("media")
                     .and_then(|m| m.get("comment_count"))
-                    .and_then(|v| v.as_u64()));
+                    .and_then(parse_count));
             
             // Extract timestamp
             let timestamp = item.get("taken_at").and_then(|v| v.as_i64())
@@ -1249,35 +1649,63 @@ impl InstagramScraper {
                     .or_else(|| item.get("media")
                         .and_then(|m| m.get("video_url"))
                         .and_then(|v| v.as_str()))
+                    // Mobile API items (feeds, clips) list the video renditions, best first
+                    .or_else(|| item.get("video_versions")
+                        .and_then(|v| v.get(0))
+                        .and_then(|v| v.get("url"))
+                        .and_then(|v| v.as_str()))
                     .map(str::to_string)
             } else {
                 None
             };
             
             let video_view_count = if is_video {
-                item.get("view_count").and_then(|v| v.as_u64())
-                    .or_else(|| item.get("play_count").and_then(|v| v.as_u64()))
-                    .or_else(|| item.get("video_view_count").and_then(|v| v.as_u64()))
+                item.get("view_count").and_then(parse_count)
+                    .or_else(|| item.get("play_count").and_then(parse_count))
+                    .or_else(|| item.get("video_view_count").and_then(parse_count))
                     .or_else(|| item.get("media")
                         .and_then(|m| m.get("view_count"))
-                        .and_then(|v| v.as_u64()))
+                        .and_then(parse_count))
             } else {
                 None
             };
             
-            // Create and add the post
-            let post = InstagramPost {
+            // Extract video duration and dimensions if it's a video
+            let (video_duration, video_width, video_height) = if is_video {
+                let video = item.get("media").unwrap_or(item);
+                (
+                    item.get("video_duration").or_else(|| video.get("video_duration")).and_then(|v| v.as_f64()),
+                    item.get("original_width").or_else(|| video.get("original_width"))
+                        .and_then(|v| v.as_u64()).map(|v| v as u32),
+                    item.get("original_height").or_else(|| video.get("original_height"))
+                        .and_then(|v| v.as_u64()).map(|v| v as u32),
+                )
+            } else {
+                (None, None, None)
+            };
+            
+            // Create and add the post
+            let post = InstagramPost {
                 id,
                 shortcode,
                 display_url,
                 thumbnail_url,
+                resolutions,
                 caption,
+                alt_text: accessibility_caption(item),
+                hashtags,
+                mentions,
                 likes_count,
                 comments_count,
                 timestamp,
+                timestamp_unix: timestamp.map(|timestamp| timestamp.timestamp()),
                 is_video,
                 video_url,
                 video_view_count,
+                video_duration,
+                video_width,
+                video_height,
+                carousel: self.extract_carousel(item),
             };
             
             posts.push(post);
@@ -1290,62 +1718,66 @@ impl InstagramScraper {
         }
     }
     
+    // URL of the web API profile endpoint, with the configured query string
+    // (`{username}` placeholders replaced by the username)
+    fn web_api_url(&self, username: &str) -> String {
+        let params = self.config.web_api_query_params.trim().trim_start_matches('?');
+        format!("https://www.instagram.com/{}/?{}", username, params.replace("{username}", username))
+    }
+    
+    // GraphQL URLs of the first page of posts of a user: the doc_id endpoint when configured, then the legacy query_hash one
+    fn posts_page_urls(&self, user_id: &str) -> Vec<String> {
+        let variables = format!("%7B%22id%22%3A%22{}%22%2C%22first%22%3A{}%7D", user_id, self.config.instagram_page_size);
+        
+        let mut urls = Vec::new();
+        if let Some(doc_id) = &self.config.instagram_posts_doc_id {
+            urls.push(format!("https://www.instagram.com/graphql/query/?doc_id={}&variables={}", doc_id, variables));
+        }
+        urls.push(format!("https://www.instagram.com/graphql/query/?query_hash={}&variables={}", self.config.instagram_posts_query_hash, variables));
+        urls
+    }
+    
     // Method to fetch a specific page of posts for a user
     async fn fetch_user_posts_paged(&self, user_id: &str, _username: &str, proxy_url: Option<&str>) -> Result<Vec<InstagramPost>, ScraperError> {
-        // Make a request to get the first page of posts
-        let url = format!("https://www.instagram.com/graphql/query/?query_hash=8c2a529969ee035a5063f2fc8602a0fd&variables=%7B%22id%22%3A%22{}%22%2C%22first%22%3A12%7D", user_id);
-        
-        let client_builder = Client::builder()
-            .timeout(Duration::from_secs(self.config.timeout))
-            .user_agent(&self.config.user_agent);
+        let client = self.client(proxy_url)?;
         
-        // Add proxy if provided
-        let client_builder = if let Some(proxy) = proxy_url {
-            if let Some(proxy_manager) = &self.proxy_manager {
-                // Use the normalized proxy URL with explicit protocol
-                let normalized_proxy = proxy_manager.normalize_proxy_url(proxy);
-                info!("Using normalized proxy URL: {}", normalized_proxy);
-                match Proxy::all(&normalized_proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
-            } else {
-                // Fallback to original behavior if no proxy manager
-                match Proxy::all(proxy) {
-                    Ok(proxy) => client_builder.proxy(proxy),
-                    Err(e) => return Err(ScraperError::ProxyError(format!("Failed to create proxy: {}", e))),
-                }
+        // Try each query in turn, a deprecated one answering 400 or 404
+        let user_agent = self.pick_user_agent(proxy_url).unwrap_or_else(|| self.config.user_agent.clone());
+        let mut last_error = None;
+        for url in self.posts_page_urls(user_id) {
+            let response = match client.get(&url).header("User-Agent", &user_agent).send().await {
+                Ok(resp) => resp,
+                Err(e) => return Err(ScraperError::NetworkError(e)),
+            };
+            
+            let status = response.status();
+            if status == reqwest::StatusCode::BAD_REQUEST || status == reqwest::StatusCode::NOT_FOUND {
+                warn!("Posts query {} rejected with status {}, trying the next one", url, status);
+                last_error = Some(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
+                continue;
             }
-        } else {
-            client_builder
-        };
-        
-        let client = match client_builder.build() {
-            Ok(client) => client,
-            Err(e) => return Err(ScraperError::ProxyError(format!("Failed to build client: {}", e))),
-        };
-        
-        let response = match client.get(url).send().await {
-            Ok(resp) => resp,
-            Err(e) => return Err(ScraperError::NetworkError(e)),
-        };
-        
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
-            error!("Failed to fetch posts, status: {}. Body: {}", status, body);
-            return Err(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
+                error!("Failed to fetch posts, status: {}. Body: {}", status, body);
+                return Err(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
+            }
+            
+            let json_data = response.json::<Value>().await?;
+            return self.extract_posts_page(&json_data);
         }
         
-        let json_data = response.json::<Value>().await?;
-        
-        if let Some(data) = json_data.get("data").and_then(|d| d.get("user")) {
-            // Fix the Option handling instead of using ? operator
-            let timeline = match data.get("edge_owner_to_timeline_media") {
-                Some(t) => t,
-                None => return Err(ScraperError::ParsingError("Missing edge_owner_to_timeline_media in response".to_string())),
-            };
-            
+        Err(last_error.unwrap_or_else(|| ScraperError::ParsingError("No posts query configured".to_string())))
+    }
+    
+    // Extract the posts of a GraphQL posts page, in the legacy (query_hash) or newer (doc_id) layout
+    fn extract_posts_page(&self, json_data: &Value) -> Result<Vec<InstagramPost>, ScraperError> {
+        let data = json_data.get("data");
+        let timeline = data
+            .and_then(|d| d.get("user"))
+            .and_then(|user| user.get("edge_owner_to_timeline_media"))
+            .or_else(|| data.and_then(|d| d.get("xdt_api__v1__feed__user_timeline_graphql_connection")));
+        
+        if let Some(timeline) = timeline {
             let edges = match timeline.get("edges") {
                 Some(e) => e,
                 None => return Err(ScraperError::ParsingError("Missing edges in timeline media".to_string())),
@@ -1356,7 +1788,9 @@ impl InstagramScraper {
                 None => return Err(ScraperError::ParsingError("Edges is not an array".to_string())),
             };
             
-            match self.extract_posts_from_items(edges_array) {
+            // Both layouts wrap each post in the node of an edge
+            let nodes: Vec<Value> = edges_array.iter().filter_map(|edge| edge.get("node")).cloned().collect();
+            match self.extract_posts_from_items(&nodes) {
                 Some(posts) => Ok(posts),
                 None => Err(ScraperError::ParsingError("Failed to extract posts from edges".to_string())),
             }
@@ -1366,7 +1800,280 @@ impl InstagramScraper {
         }
     }
     
-    fn log_response_headers(&self, response: &reqwest::Response, endpoint_type: &str) {
+    // Fetch a mobile API URL as JSON for a username, rotating proxies like the profile endpoints
+    async fn fetch_mobile_api_json(&self, url: &str, username: &str) -> Result<Value, ScraperError> {
+        if let Some(proxy_manager) = &self.proxy_manager {
+            let mut last_error = None;
+            
+            let (available, total) = proxy_manager.get_proxy_count();
+            
+            // If no proxies are available, return error - don't try without proxy
+            if available == 0 {
+                if total > 0 {
+                    warn!("No proxies available (all marked as unavailable), not falling back to direct connection");
+                    return Err(ScraperError::AllProxiesFailed);
+                } else {
+                    warn!("No proxies configured");
+                    return Err(ScraperError::ProxyError("No proxies configured".to_string()));
+                }
+            }
+            
+            for attempt in 0..available {
+                if attempt > 0 {
+                    self.wait_between_proxy_attempts().await;
+                }
+                if let Some(proxy_url) = proxy_manager.get_proxy_for_key(username) {
+                    match self.make_mobile_api_json_request(url, Some(&proxy_url)).await {
+                        Ok(result) => {
+                            proxy_manager.mark_proxy_success(&proxy_url);
+                            return Ok(result);
+                        }
+                        Err(err @ (ScraperError::ProxyError(_) | ScraperError::NetworkError(_))) => {
+                            if let ScraperError::ProxyError(msg) = &err {
+                                warn!("Proxy error: {}, recording proxy failure", msg);
+                                proxy_manager.mark_proxy_failure(&proxy_url);
+                            }
+                            // Rotate to another proxy for the next attempt
+                            proxy_manager.release_proxy_for_key(username);
+                            last_error = Some(err);
+                        }
+                        // Errors from Instagram itself (not found, unauthorized, rate limited...) won't change with another proxy
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+            
+            if let Some(err) = last_error {
+                warn!("All proxies failed for mobile API request {}: {}", url, err);
+            }
+            Err(ScraperError::AllProxiesFailed)
+        } else {
+            self.make_mobile_api_json_request(url, None).await
+        }
+    }
+    
+    async fn make_mobile_api_json_request(&self, url: &str, proxy_url: Option<&str>) -> Result<Value, ScraperError> {
+        let client = self.client(proxy_url)?;
+        
+        let mut request = client.get(url)
+            .header("User-Agent", self.pick_user_agent(proxy_url).unwrap_or_else(|| MOBILE_API_USER_AGENT.to_string()))
+            .header("Accept", "application/json")
+            .header("Accept-Language", self.accept_language("en-US"))
+            .header("X-IG-App-ID", "936619743392459")
+            .header("X-ASBD-ID", "198387")
+            .header("X-IG-WWW-Claim", "0");
+        
+        let cookies = self.pick_cookies(proxy_url);
+        if let Some(cookies) = &cookies {
+            request = request.header("Cookie", cookies);
+        }
+        
+        let response = match self.send(request, proxy_url).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                if proxy_url.is_some() {
+                    return Err(ScraperError::ProxyError(format!("Proxy request failed: {}", e)));
+                }
+                return Err(ScraperError::NetworkError(e));
+            }
+        };
+        
+        let status = response.status();
+        let retry_after = self.log_response_headers(&response, "mobile API");
+        let content_type = response_content_type(&response);
+        
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(ScraperError::ProfileNotFound);
+        }
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            self.cool_down_cookies(cookies.as_deref());
+            return Err(ScraperError::RateLimited { retry_after });
+        }
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            let body = response.text().await.unwrap_or_else(|_| "<failed to read body>".to_string());
+            error!("Unauthorized access to mobile API {} (cookies may be required). Body: {}", url, body);
+            if let Some(err) = self.challenge_error(cookies.as_deref(), &body) {
+                return Err(err);
+            }
+            return Err(ScraperError::UnauthorizedAccess(body));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Mobile API request {} failed with status: {}", url, status);
+            if let Some(err) = self.challenge_error(cookies.as_deref(), &body) {
+                return Err(err);
+            }
+            return Err(ScraperError::ParsingError(format!("HTTP error status: {}", status)));
+        }
+        
+        let body = response.text().await?;
+        if let Some(err) = self.challenge_error(cookies.as_deref(), &body) {
+            return Err(err);
+        }
+        parse_json_lenient(&body).map_err(|e| {
+            if proxy_url.is_some() && is_proxy_block_page(content_type.as_deref(), &body) {
+                warn!("Proxy returned a block page instead of the mobile API response for {}", url);
+                return ScraperError::ProxyError("Proxy returned a block page".to_string());
+            }
+            ScraperError::ParsingError(format!("Invalid mobile API JSON: {}", e))
+        })
+    }
+    
+    // Resolve the numeric id of a user, needed by the feed endpoints
+    async fn resolve_user_id(&self, username: &str) -> Result<String, ScraperError> {
+        let url = format!("https://i.instagram.com/api/v1/users/web_profile_info/?username={}", username);
+        let json_data = self.fetch_mobile_api_json(&url, username).await?;
+        
+        let user = json_data.get("data").and_then(|d| d.get("user"))
+            .ok_or(ScraperError::ProfileNotFound)?;
+        
+        user.get("id").and_then(|id| id.as_str()).map(str::to_string)
+            .ok_or_else(|| ScraperError::ParsingError("Missing user id in profile info".to_string()))
+    }
+    
+    /// Scrape a single post of a user from its shortcode
+    pub async fn scrape_post(&self, username: &str, shortcode: &str) -> Result<InstagramPost, ScraperError> {
+        self.with_deadline(username, self.fetch_post(username, shortcode)).await
+    }
+    
+    async fn fetch_post(&self, username: &str, shortcode: &str) -> Result<InstagramPost, ScraperError> {
+        let _in_flight = InFlightGuard::new(&self.in_flight);
+        info!("Fetching post {} of {}", shortcode, username);
+        
+        let media_id = shortcode_to_media_id(shortcode).ok_or(ScraperError::PostNotFound)?;
+        let url = format!("https://i.instagram.com/api/v1/media/{}/info/", media_id);
+        let json_data = self.fetch_mobile_api_json(&url, username).await
+            .map_err(|e| match e {
+                ScraperError::ProfileNotFound => ScraperError::PostNotFound,
+                e => e,
+            })?;
+        
+        let item = json_data.get("items").and_then(|i| i.as_array()).and_then(|items| items.first())
+            .ok_or(ScraperError::PostNotFound)?;
+        
+        // The post must belong to the requested user
+        let owner = item.get("user").and_then(|u| u.get("username")).and_then(|u| u.as_str());
+        if !owner.is_some_and(|owner| owner.eq_ignore_ascii_case(username)) {
+            warn!("Post {} does not belong to {} (owner: {:?})", shortcode, username, owner);
+            return Err(ScraperError::PostNotFound);
+        }
+        
+        self.extract_posts_from_items(std::slice::from_ref(item))
+            .and_then(|posts| posts.into_iter().next())
+            .ok_or_else(|| ScraperError::ParsingError(format!("Failed to extract post {}", shortcode)))
+    }
+    
+    /// Scrape the highlights of a user (requires Instagram cookies)
+    pub async fn scrape_highlights(&self, username: &str) -> Result<Vec<InstagramHighlight>, ScraperError> {
+        self.with_deadline(username, self.fetch_highlights(username)).await
+    }
+    
+    async fn fetch_highlights(&self, username: &str) -> Result<Vec<InstagramHighlight>, ScraperError> {
+        let _in_flight = InFlightGuard::new(&self.in_flight);
+        if !self.has_cookies() {
+            return Err(ScraperError::UnauthorizedAccess("Instagram cookies are required to fetch highlights".to_string()));
+        }
+        
+        info!("Fetching highlights for {}", username);
+        
+        let user_id = self.resolve_user_id(username).await?;
+        let url = format!("https://i.instagram.com/api/v1/highlights/{}/highlights_tray/", user_id);
+        let json_data = self.fetch_mobile_api_json(&url, username).await?;
+        
+        let tray = json_data.get("tray").and_then(|t| t.as_array())
+            .ok_or_else(|| ScraperError::ParsingError("Missing tray in highlights response".to_string()))?;
+        
+        Ok(tray.iter().filter_map(|item| self.extract_highlight(item)).collect())
+    }
+    
+    /// Scrape the posts a user is tagged in (first page of the tagged feed)
+    pub async fn scrape_tagged(&self, username: &str) -> Result<Vec<InstagramPost>, ScraperError> {
+        self.with_deadline(username, self.fetch_tagged(username)).await
+    }
+    
+    async fn fetch_tagged(&self, username: &str) -> Result<Vec<InstagramPost>, ScraperError> {
+        let _in_flight = InFlightGuard::new(&self.in_flight);
+        info!("Fetching tagged posts for {}", username);
+        
+        let user_id = self.resolve_user_id(username).await?;
+        let url = format!("https://i.instagram.com/api/v1/usertags/{}/feed/?count={}", user_id, self.config.instagram_page_size);
+        let json_data = self.fetch_mobile_api_json(&url, username).await?;
+        
+        let items = json_data.get("items").and_then(|i| i.as_array())
+            .ok_or_else(|| ScraperError::ParsingError("Missing items in tagged feed response".to_string()))?;
+        
+        Ok(self.extract_posts_from_items(items).unwrap_or_default())
+    }
+    
+    /// Scrape a page of `page_size` reels of a user from the reels tab (clips feed), the first one when no cursor
+    /// is given. The reels tab holds reels missing from the posts the user reels are derived from.
+    pub async fn scrape_reels(&self, username: &str, cursor: Option<&str>, page_size: u64) -> Result<InstagramReelsPage, ScraperError> {
+        self.with_deadline(username, self.fetch_reels(username, cursor, page_size)).await
+    }
+    
+    async fn fetch_reels(&self, username: &str, cursor: Option<&str>, page_size: u64) -> Result<InstagramReelsPage, ScraperError> {
+        let _in_flight = InFlightGuard::new(&self.in_flight);
+        info!("Fetching reels tab for {} (cursor: {:?})", username, cursor);
+        
+        let user_id = self.resolve_user_id(username).await?;
+        let url = clips_url(&user_id, cursor, page_size)?;
+        let json_data = self.fetch_mobile_api_json(&url, username).await?;
+        
+        self.extract_reels_from_clips(&json_data)
+    }
+    
+    fn extract_reels_from_clips(&self, json_data: &Value) -> Result<InstagramReelsPage, ScraperError> {
+        let items = json_data.get("items").and_then(|i| i.as_array())
+            .ok_or_else(|| ScraperError::ParsingError("Missing items in clips feed response".to_string()))?;
+        
+        // Each clip wraps its media, in the same format as the feed items
+        let media: Vec<Value> = items.iter()
+            .map(|item| item.get("media").unwrap_or(item).clone())
+            .collect();
+        
+        let reels = self.extract_posts_from_items(&media)
+            .unwrap_or_default()
+            .iter()
+            .map(InstagramReel::from)
+            .collect();
+        
+        let paging_info = json_data.get("paging_info");
+        let more_available = paging_info.and_then(|p| p.get("more_available")).and_then(|m| m.as_bool()).unwrap_or(false);
+        let end_cursor = paging_info
+            .and_then(|p| p.get("max_id"))
+            .and_then(|m| m.as_str())
+            .filter(|cursor| more_available && !cursor.is_empty())
+            .map(str::to_string);
+        
+        Ok(InstagramReelsPage { reels, end_cursor })
+    }
+    
+    fn extract_highlight(&self, item: &Value) -> Option<InstagramHighlight> {
+        // Highlight ids are prefixed with "highlight:"
+        let id = item.get("id").and_then(|v| v.as_str())?;
+        let id = id.strip_prefix("highlight:").unwrap_or(id).to_string();
+        
+        let cover_media = item.get("cover_media");
+        let cover_url = cover_media
+            .and_then(|m| m.get("cropped_image_version"))
+            .and_then(|v| v.get("url"))
+            .or_else(|| cover_media
+                .and_then(|m| m.get("full_image_version"))
+                .and_then(|v| v.get("url")))
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        
+        Some(InstagramHighlight {
+            id,
+            title: item.get("title").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+            cover_url,
+            media_count: item.get("media_count").and_then(parse_count),
+        })
+    }
+    
+    // Log the headers of a failed or throttled response, returning the delay in seconds
+    // Instagram asked to wait before retrying, when given
+    fn log_response_headers(&self, response: &reqwest::Response, endpoint_type: &str) -> Option<u64> {
         let headers = response.headers();
         let status = response.status();
         
@@ -1384,5 +2091,953 @@ impl InstagramScraper {
            headers.contains_key("x-fb-debug") {
             info!("{}", header_log);
         }
+        
+        retry_after_secs(headers)
     }
-} 
\ No newline at end of file
+}
+
+// Markers of the login page Instagram serves in place of profiles to logged-out visitors
+const LOGIN_WALL_MARKERS: [&str; 3] = [
+    "\"LoginAndSignupPage\"",
+    "<title>Login • Instagram</title>",
+    "id=\"loginForm\"",
+];
+
+fn is_login_wall(html: &str) -> bool {
+    LOGIN_WALL_MARKERS.iter().any(|marker| html.contains(marker))
+}
+
+// Markers (lowercase) of the pages some proxies return with a 200 status in place of the response they refused to fetch
+const PROXY_BLOCK_MARKERS: [&str; 4] = [
+    "access denied by proxy",
+    "blocked by proxy",
+    "proxy policy",
+    "generated by squid",
+];
+
+fn has_proxy_block_marker(body: &str) -> bool {
+    let body = body.to_lowercase();
+    PROXY_BLOCK_MARKERS.iter().any(|marker| body.contains(marker))
+}
+
+// Markers of the pages Instagram serves instead of the profile HTML to clients it blocks
+const HTML_BLOCK_MARKERS: [&str; 3] = [
+    "please wait a few minutes before you try again",
+    "www.google.com/recaptcha",
+    "g-recaptcha",
+];
+
+fn is_html_block_page(html: &str) -> bool {
+    let html = html.to_lowercase();
+    HTML_BLOCK_MARKERS.iter().any(|marker| html.contains(marker))
+}
+
+// Whether a body an API endpoint answered with, that isn't valid JSON, was injected by the proxy:
+// either a known block page, or an HTML page that doesn't come from Instagram
+fn is_proxy_block_page(content_type: Option<&str>, body: &str) -> bool {
+    let is_html = content_type.is_some_and(|content_type| content_type.to_lowercase().starts_with("text/html"));
+    has_proxy_block_marker(body) || (is_html && !body.to_lowercase().contains("instagram"))
+}
+
+// Delay in seconds to wait before retrying, from the Retry-After header (in seconds)
+// or the x-ratelimit-reset one (in seconds, or the reset time as a Unix timestamp)
+fn retry_after_secs(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+    
+    header("retry-after").or_else(|| {
+        let reset = header("x-ratelimit-reset")?;
+        // Values past 2001 are timestamps rather than delays
+        if reset > 1_000_000_000 {
+            Some((reset as i64 - Utc::now().timestamp()).max(0) as u64)
+        } else {
+            Some(reset)
+        }
+    })
+}
+
+// Accessibility caption of a post (from the mobile API items or the GraphQL nodes), ignoring empty ones
+fn accessibility_caption(post: &Value) -> Option<String> {
+    post.get("accessibility_caption")
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|caption| !caption.is_empty())
+        .map(str::to_string)
+}
+
+fn response_content_type(response: &reqwest::Response) -> Option<String> {
+    response.headers().get("content-type").and_then(|h| h.to_str().ok()).map(str::to_string)
+}
+
+// Markers of the responses Instagram sends instead of the data when an account must pass a checkpoint
+const CHALLENGE_MARKERS: [&str; 3] = [
+    "\"checkpoint_required\"",
+    "\"challenge_required\"",
+    "\"checkpoint_url\"",
+];
+
+fn is_challenge_response(body: &str) -> bool {
+    CHALLENGE_MARKERS.iter().any(|marker| body.contains(marker))
+}
+
+// Parse a count given as a JSON number or as a string, either numeric ("12,345")
+// or abbreviated ("1.2m", "15k")
+fn parse_count(value: &Value) -> Option<u64> {
+    if let Some(count) = value.as_u64() {
+        return Some(count);
+    }
+    
+    let text = value.as_str()?.trim().replace([',', ' '], "").to_lowercase();
+    let (number, multiplier) = match text.chars().last()? {
+        'k' => (&text[..text.len() - 1], 1_000.0),
+        'm' => (&text[..text.len() - 1], 1_000_000.0),
+        'b' => (&text[..text.len() - 1], 1_000_000_000.0),
+        _ => (text.as_str(), 1.0),
+    };
+    
+    if multiplier == 1.0 {
+        return number.parse::<u64>().ok();
+    }
+    let count = number.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)?;
+    Some((count * multiplier).round() as u64)
+}
+
+// Paths of the profile in the data of the embedded profile page query results
+const BBOX_USER_PATHS: [&[&str]; 2] = [&["user"], &["xdt_api__v1__users__web_profile_info", "user"]];
+
+// Find the first embedded query result (`__bbox` -> `result` -> `data`, at any depth) for which `probe` finds something
+fn find_bbox_data<'a, T>(value: &'a Value, probe: &dyn Fn(&'a Value) -> Option<T>) -> Option<T> {
+    match value {
+        Value::Object(object) => object.get("__bbox")
+            .and_then(|bbox| bbox.get("result"))
+            .and_then(|result| result.get("data"))
+            .and_then(probe)
+            .or_else(|| object.values().find_map(|child| find_bbox_data(child, probe))),
+        Value::Array(items) => items.iter().find_map(|child| find_bbox_data(child, probe)),
+        _ => None,
+    }
+}
+
+// Profile of the username in the data of an embedded query result
+fn bbox_profile_user<'a>(data: &'a Value, username: &str) -> Option<&'a Value> {
+    BBOX_USER_PATHS.iter()
+        .filter_map(|path| path.iter().try_fold(data, |value, key| value.get(key)))
+        .find(|user| user.get("username").and_then(Value::as_str).is_some_and(|name| name.eq_ignore_ascii_case(username)))
+}
+
+// Posts (mobile API items) of the timeline in the data of an embedded query result
+fn bbox_timeline_items(data: &Value) -> Option<Vec<Value>> {
+    let edges = data.get("xdt_api__v1__feed__user_timeline_graphql_connection")?.get("edges")?.as_array()?;
+    Some(edges.iter().filter_map(|edge| edge.get("node")).cloned().collect())
+}
+
+// URL of a page of the reels tab of a user, the page following `cursor` (the max_id of the previous page) when given
+fn clips_url(user_id: &str, cursor: Option<&str>, page_size: u64) -> Result<String, ScraperError> {
+    let mut params = vec![("target_user_id", user_id.to_string()), ("page_size", page_size.to_string())];
+    if let Some(cursor) = cursor {
+        params.push(("max_id", cursor.to_string()));
+    }
+    reqwest::Url::parse_with_params("https://i.instagram.com/api/v1/clips/user/", &params)
+        .map(String::from)
+        .map_err(|e| ScraperError::ParsingError(format!("Invalid clips feed URL: {}", e)))
+}
+
+// Decode a post shortcode (base64 with a custom alphabet) into its media id
+fn shortcode_to_media_id(shortcode: &str) -> Option<u128> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    
+    if shortcode.is_empty() {
+        return None;
+    }
+    shortcode.bytes().try_fold(0u128, |id, c| {
+        let digit = ALPHABET.iter().position(|&a| a == c)? as u128;
+        id.checked_mul(64)?.checked_add(digit)
+    })
+}
+
+// Prefixes Instagram sometimes puts before JSON bodies to prevent JSON hijacking
+const JSON_GUARD_PREFIXES: [&str; 3] = ["for (;;);", "while(1);", ")]}'"];
+
+// Opening braces tried when looking for a JSON object embedded in a body
+const MAX_JSON_SALVAGE_CANDIDATES: usize = 32;
+
+// Parse a JSON body, salvaging it when it is wrapped in a guard prefix or in HTML
+// by parsing its first balanced object. The original error is returned if nothing can be salvaged.
+fn parse_json_lenient(body: &str) -> Result<Value, serde_json::Error> {
+    let error = match serde_json::from_str::<Value>(body) {
+        Ok(json) => return Ok(json),
+        Err(e) => e,
+    };
+    
+    let trimmed = body.trim_start();
+    let unprefixed = JSON_GUARD_PREFIXES.iter()
+        .find_map(|prefix| trimmed.strip_prefix(prefix))
+        .unwrap_or(trimmed);
+    if let Ok(json) = serde_json::from_str::<Value>(unprefixed) {
+        warn!("Salvaged JSON body after stripping its guard prefix");
+        return Ok(json);
+    }
+    
+    // Braces of inline scripts or styles may come first in HTML, so try a few candidates
+    let salvaged = unprefixed.match_indices('{')
+        .take(MAX_JSON_SALVAGE_CANDIDATES)
+        .filter_map(|(start, _)| balanced_json_object(&unprefixed[start..]))
+        .find_map(|object| serde_json::from_str::<Value>(object).ok().filter(Value::is_object));
+    match salvaged {
+        Some(json) => {
+            warn!("Salvaged JSON object embedded in a {} bytes body", body.len());
+            Ok(json)
+        }
+        None => Err(error),
+    }
+}
+
+// Balanced `{...}` at the start of a text, ignoring braces inside strings
+fn balanced_json_object(text: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    
+    for (offset, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[..=offset]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Remove items with an already seen id, keeping the first occurrence
+fn dedup_by_id<T>(items: Vec<T>, id: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut seen = HashSet::new();
+    items.into_iter().filter(|item| seen.insert(id(item).to_string())).collect()
+}
+
+#[rocket::async_trait]
+impl Scraper for InstagramScraper {
+    fn platform(&self) -> Platform {
+        Platform::Instagram
+    }
+    
+    async fn scrape_user(&self, username: &str) -> Result<Profile, ScraperError> {
+        let user = self.scrape_user_with_retry(username).await?;
+        Ok(user.into())
+    }
+    
+    async fn scrape_posts(&self, username: &str) -> Result<Vec<Post>, ScraperError> {
+        let user = self.scrape_user_with_retry(username).await?;
+        Ok(user.posts.unwrap_or_default().into_iter().map(Post::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::test_server::{response, TestServer};
+
+    fn scraper(config: AppConfig) -> InstagramScraper {
+        InstagramScraper::new(config, ProxyManager::new(None, 4))
+    }
+
+    // Web API profile response (?__a=1) with a single photo post
+    fn web_profile(extra: Value) -> Value {
+        let mut user = json!({
+            "username": "jane",
+            "full_name": "Jane",
+            "is_private": false,
+            "edge_followed_by": { "count": 10 },
+            "edge_follow": { "count": 5 },
+            "edge_owner_to_timeline_media": {
+                "count": 1,
+                "edges": [{ "node": { "id": "1", "shortcode": "abc", "display_url": "https://scontent.cdninstagram.com/abc.jpg", "is_video": false, "taken_at_timestamp": 86400 } }]
+            }
+        });
+        user.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        json!({ "graphql": { "user": user } })
+    }
+
+    #[test]
+    fn business_fields_are_extracted() {
+        let data = web_profile(json!({
+            "category_name": "Bakery",
+            "is_business_account": true,
+            "business_contact_method": "CALL"
+        }));
+        let user = scraper(AppConfig::default()).extract_user_data_from_json(&data, "jane").unwrap();
+        assert_eq!(user.category.as_deref(), Some("Bakery"));
+        assert!(user.is_business);
+        assert_eq!(user.account_type.as_deref(), Some("business"));
+        assert_eq!(user.business_contact_method.as_deref(), Some("call"));
+    }
+
+    #[test]
+    fn business_fields_default_when_omitted() {
+        let user = scraper(AppConfig::default()).extract_user_data_from_json(&web_profile(json!({})), "jane").unwrap();
+        assert_eq!(user.category, None);
+        assert!(!user.is_business);
+        assert_eq!(user.account_type, None);
+        assert_eq!(user.business_contact_method, None);
+        assert_eq!(user.posts.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn account_type_from_the_mobile_api() {
+        let data = json!({ "username": "jane", "account_type": 3, "is_business_account": false });
+        let user = scraper(AppConfig::default()).extract_user_data_from_api_response(&data, "jane").unwrap();
+        assert_eq!(user.account_type.as_deref(), Some("creator"));
+        assert!(!user.is_business);
+    }
+
+    #[test]
+    fn successive_requests_rotate_user_agents() {
+        let config = AppConfig {
+            user_agents: Some(vec!["UA-1".to_string(), "UA-2".to_string()]),
+            ..AppConfig::default()
+        };
+        let scraper = scraper(config);
+
+        let first = scraper.pick_user_agent(None).unwrap();
+        let second = scraper.pick_user_agent(None).unwrap();
+        assert_ne!(first, second);
+
+        // A proxy keeps its User-Agent
+        let proxy = Some("http://10.0.0.1:8080");
+        assert_eq!(scraper.pick_user_agent(proxy), scraper.pick_user_agent(proxy));
+    }
+
+    #[test]
+    fn no_user_agent_pool_falls_back_to_the_single_user_agent() {
+        let config = AppConfig { user_agents: Some(Vec::new()), ..AppConfig::default() };
+        assert_eq!(scraper(config).pick_user_agent(None), None);
+    }
+
+    #[test]
+    fn retry_delay_stays_within_the_jitter() {
+        let config = AppConfig { retry_base_delay_ms: Some(100), retry_jitter_ms: Some(50), ..AppConfig::default() };
+        let jittered = scraper(config);
+        for _ in 0..100 {
+            let delay = jittered.retry_delay();
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(150));
+        }
+
+        let config = AppConfig { retry_base_delay_ms: Some(100), retry_jitter_ms: Some(0), ..AppConfig::default() };
+        assert_eq!(scraper(config).retry_delay(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn resolutions_from_api_items() {
+        let item = json!({
+            "pk": "1",
+            "code": "abc",
+            "image_versions2": { "candidates": [
+                { "url": "https://scontent.cdninstagram.com/small.jpg", "width": 320, "height": 320 },
+                { "url": "https://scontent.cdninstagram.com/large.jpg", "width": 1080, "height": 1080 }
+            ] }
+        });
+        let post = scraper(AppConfig::default()).extract_posts_from_items(&[item]).unwrap().remove(0);
+        let widths: Vec<u32> = post.resolutions.iter().map(|version| version.width).collect();
+        assert_eq!(widths, vec![1080, 320]);
+        assert_eq!(post.display_url.as_deref(), Some("https://scontent.cdninstagram.com/large.jpg"));
+    }
+
+    #[test]
+    fn resolutions_from_graphql_nodes() {
+        let timeline = json!({ "count": 1, "edges": [{ "node": {
+            "id": "1",
+            "shortcode": "abc",
+            "display_url": "https://scontent.cdninstagram.com/large.jpg",
+            "display_resources": [
+                { "src": "https://scontent.cdninstagram.com/small.jpg", "config_width": 640, "config_height": 800 },
+                { "src": "https://scontent.cdninstagram.com/large.jpg", "config_width": 1080, "config_height": 1350 }
+            ]
+        } }] });
+        let post = scraper(AppConfig::default()).extract_posts_from_json(&timeline).unwrap().remove(0);
+        let sizes: Vec<(u32, u32)> = post.resolutions.iter().map(|version| (version.width, version.height)).collect();
+        assert_eq!(sizes, vec![(1080, 1350), (640, 800)]);
+        assert_eq!(post.resolutions[1].url, "https://scontent.cdninstagram.com/small.jpg");
+    }
+
+    #[test]
+    fn reel_duration_and_dimensions() {
+        let clips = json!({
+            "items": [
+                { "media": {
+                    "pk": "1",
+                    "code": "reel1",
+                    "media_type": 2,
+                    "video_duration": 12.5,
+                    "original_width": 1080,
+                    "original_height": 1920,
+                    "video_versions": [{ "url": "https://scontent.cdninstagram.com/reel1.mp4" }]
+                } },
+                { "media": { "pk": "2", "code": "reel2", "product_type": "clips" } }
+            ],
+            "paging_info": { "more_available": false }
+        });
+        let page = scraper(AppConfig::default()).extract_reels_from_clips(&clips).unwrap();
+        assert_eq!(page.reels.len(), 2);
+        assert_eq!(page.reels[0].duration, Some(12.5));
+        assert_eq!((page.reels[0].width, page.reels[0].height), (Some(1080), Some(1920)));
+        assert_eq!(page.reels[0].video_url.as_deref(), Some("https://scontent.cdninstagram.com/reel1.mp4"));
+        // Absent fields are tolerated
+        assert_eq!((page.reels[1].duration, page.reels[1].width, page.reels[1].height), (None, None, None));
+        assert_eq!(page.end_cursor, None);
+    }
+
+    #[test]
+    fn posts_limited_follows_the_page_size() {
+        let data = web_profile(json!({ "edge_owner_to_timeline_media": {
+            "count": 20,
+            "edges": [{ "node": { "id": "1", "shortcode": "abc", "display_url": "https://scontent.cdninstagram.com/abc.jpg" } }]
+        } }));
+
+        let config = AppConfig { instagram_page_size: 24, ..AppConfig::default() };
+        assert!(!scraper(config).extract_user_data_from_json(&data, "jane").unwrap().posts_limited);
+
+        let config = AppConfig { instagram_page_size: 12, ..AppConfig::default() };
+        assert!(scraper(config).extract_user_data_from_json(&data, "jane").unwrap().posts_limited);
+    }
+
+    #[test]
+    fn highlights_from_the_tray() {
+        let tray = json!({ "tray": [
+            {
+                "id": "highlight:17900000000000001",
+                "title": "Travel",
+                "media_count": 12,
+                "cover_media": { "cropped_image_version": { "url": "https://scontent.cdninstagram.com/cover.jpg" } }
+            },
+            { "id": "17900000000000002", "cover_media": { "full_image_version": { "url": "https://scontent.cdninstagram.com/full.jpg" } } },
+            { "title": "No id" }
+        ] });
+        let scraper = scraper(AppConfig::default());
+        let highlights: Vec<InstagramHighlight> = tray["tray"].as_array().unwrap().iter()
+            .filter_map(|item| scraper.extract_highlight(item))
+            .collect();
+
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].id, "17900000000000001");
+        assert_eq!(highlights[0].title, "Travel");
+        assert_eq!(highlights[0].media_count, Some(12));
+        assert_eq!(highlights[0].cover_url.as_deref(), Some("https://scontent.cdninstagram.com/cover.jpg"));
+        assert_eq!(highlights[1].title, "");
+        assert_eq!(highlights[1].cover_url.as_deref(), Some("https://scontent.cdninstagram.com/full.jpg"));
+    }
+
+    #[tokio::test]
+    async fn highlights_require_cookies() {
+        let result = scraper(AppConfig::default()).scrape_highlights("jane").await;
+        assert!(matches!(result, Err(ScraperError::UnauthorizedAccess(_))));
+    }
+
+    #[test]
+    fn duplicate_posts_and_reels_are_removed() {
+        let scraper = scraper(AppConfig::default());
+        let ids = |user: InstagramUser| -> (Vec<String>, Vec<String>) {
+            (
+                user.posts.unwrap().into_iter().map(|post| post.id).collect(),
+                user.reels.unwrap().into_iter().map(|reel| reel.id).collect(),
+            )
+        };
+
+        let data = json!({ "username": "jane", "items": [
+            { "pk": "1", "code": "one" },
+            { "pk": "2", "code": "two", "media_type": 2 },
+            { "pk": "1", "code": "one" }
+        ] });
+        let (posts, reels) = ids(scraper.extract_user_data_from_api_response(&data, "jane").unwrap());
+        assert_eq!(posts, vec!["1", "2"]);
+        assert_eq!(reels, vec!["2"]);
+
+        let video = json!({ "node": { "id": "v", "shortcode": "vid", "display_url": "https://scontent.cdninstagram.com/v.jpg", "is_video": true } });
+        let data = json!({ "username": "jane", "edge_felix_video_timeline": { "count": 2, "edges": [video, video] } });
+        let (posts, reels) = ids(scraper.extract_user_data_from_api_response(&data, "jane").unwrap());
+        assert_eq!(posts, vec!["v"]);
+        assert_eq!(reels, vec!["v"]);
+    }
+
+    #[test]
+    fn counts_from_numbers_and_strings() {
+        assert_eq!(parse_count(&json!(12345)), Some(12345));
+        assert_eq!(parse_count(&json!("12345")), Some(12345));
+        assert_eq!(parse_count(&json!("12,345")), Some(12345));
+        assert_eq!(parse_count(&json!("1.2m")), Some(1_200_000));
+        assert_eq!(parse_count(&json!("15K")), Some(15_000));
+        assert_eq!(parse_count(&json!("2b")), Some(2_000_000_000));
+        assert_eq!(parse_count(&json!("many")), None);
+        assert_eq!(parse_count(&json!("-1k")), None);
+        assert_eq!(parse_count(&json!(null)), None);
+    }
+
+    #[test]
+    fn stringified_follower_counts_are_kept() {
+        let data = web_profile(json!({ "edge_followed_by": { "count": "12345" }, "edge_follow": { "count": "1.2k" } }));
+        let user = scraper(AppConfig::default()).extract_user_data_from_json(&data, "jane").unwrap();
+        assert_eq!(user.stats.followers_count, Some(12345));
+        assert_eq!(user.stats.following_count, Some(1200));
+    }
+
+    // Hosts of the tunnels opened through a test proxy refusing them, in order
+    async fn scraped_hosts(order: Option<Vec<&str>>) -> Vec<String> {
+        let server = TestServer::start(vec![response(403, &[], b"")]);
+        let config = AppConfig {
+            scrape_endpoint_order: order.map(|order| order.into_iter().map(String::from).collect()),
+            ..AppConfig::default()
+        };
+        let scraper = InstagramScraper::new(config, ProxyManager::new(Some(vec![server.url.clone()]), 4));
+        assert!(scraper.scrape_user("jane").await.is_err());
+
+        server.requests().iter()
+            .filter_map(|request| request.line.strip_prefix("CONNECT ").and_then(|target| target.split(':').next()))
+            .map(String::from)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn endpoints_are_tried_in_the_configured_order() {
+        assert_eq!(scraped_hosts(None).await, ["i.instagram.com", "www.instagram.com", "www.instagram.com"]);
+        assert_eq!(scraped_hosts(Some(vec!["html", "mobile"])).await, ["www.instagram.com", "i.instagram.com"]);
+        assert_eq!(scraped_hosts(Some(vec!["mobile"])).await, ["i.instagram.com"]);
+    }
+
+    #[tokio::test]
+    async fn slow_scrape_is_cut_off_at_the_deadline() {
+        let scraper = scraper(AppConfig { scrape_deadline: Some(1), ..AppConfig::default() });
+        let started = std::time::Instant::now();
+
+        let slow_scrape = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(())
+        };
+        assert!(matches!(scraper.with_deadline("jane", slow_scrape).await, Err(ScraperError::Timeout(1))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Scrapes finishing in time are unaffected
+        assert!(scraper.with_deadline("jane", async { Ok(()) }).await.is_ok());
+    }
+
+    #[test]
+    fn shortcodes_decode_to_media_ids() {
+        assert_eq!(shortcode_to_media_id("B"), Some(1));
+        assert_eq!(shortcode_to_media_id("BA"), Some(64));
+        assert_eq!(shortcode_to_media_id("CqZ6yF9r0Zi"), Some(3069743150104790626));
+        assert_eq!(shortcode_to_media_id(""), None);
+        assert_eq!(shortcode_to_media_id("no.such.post"), None);
+    }
+
+    #[tokio::test]
+    async fn scrapes_over_the_limit_are_serialized() {
+        let scraper = scraper(AppConfig { max_concurrent_scrapes: Some(1), ..AppConfig::default() });
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        let scrape = || async {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now_running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(())
+        };
+        let (first, second) = tokio::join!(scraper.with_deadline("jane", scrape()), scraper.with_deadline("john", scrape()));
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn timestamps_are_serialized_both_ways() {
+        let item = json!({ "pk": "1", "code": "abc", "taken_at": 1700000000 });
+        let post = scraper(AppConfig::default()).extract_posts_from_items(&[item]).unwrap().remove(0);
+        let json = serde_json::to_value(&post).unwrap();
+        assert_eq!(json["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(json["timestampUnix"], 1700000000);
+
+        let user = scraper(AppConfig::default()).extract_user_data_from_json(&web_profile(json!({})), "jane").unwrap();
+        let json = serde_json::to_value(&user.posts.unwrap()[0]).unwrap();
+        assert_eq!(json["timestamp"], "1970-01-02T00:00:00Z");
+        assert_eq!(json["timestampUnix"], 86400);
+    }
+
+    #[test]
+    fn json_after_a_guard_prefix_is_salvaged() {
+        assert_eq!(parse_json_lenient("for (;;);{\"status\":\"ok\"}").unwrap(), json!({ "status": "ok" }));
+        assert_eq!(parse_json_lenient("  while(1);{\"a\":[1,2]}").unwrap(), json!({ "a": [1, 2] }));
+    }
+
+    #[test]
+    fn json_wrapped_in_html_is_salvaged() {
+        let body = r#"<html><head><style>body { margin: 0 }</style></head>
+            <body><pre>{"data": {"user": {"username": "jane", "bio": "likes {braces} and \"quotes\""}}}</pre></body></html>"#;
+        let json = parse_json_lenient(body).unwrap();
+        assert_eq!(json["data"]["user"]["username"], "jane");
+        assert_eq!(json["data"]["user"]["bio"], "likes {braces} and \"quotes\"");
+    }
+
+    #[test]
+    fn garbage_is_still_a_parsing_error() {
+        assert!(parse_json_lenient("<html><body>Please wait</body></html>").is_err());
+        assert!(parse_json_lenient("for (;;);{\"truncated\": ").is_err());
+    }
+
+    #[test]
+    fn posts_page_from_the_query_hash_layout() {
+        let page = json!({ "data": { "user": { "edge_owner_to_timeline_media": { "count": 2, "edges": [
+            { "node": { "id": "1", "shortcode": "abc", "display_url": "https://scontent.cdninstagram.com/abc.jpg", "taken_at_timestamp": 86400 } },
+            { "node": { "id": "2", "shortcode": "def", "display_url": "https://scontent.cdninstagram.com/def.jpg", "is_video": true } }
+        ] } } } });
+        let posts = scraper(AppConfig::default()).extract_posts_page(&page).unwrap();
+        let shortcodes: Vec<&str> = posts.iter().map(|post| post.shortcode.as_str()).collect();
+        assert_eq!(shortcodes, ["abc", "def"]);
+        assert_eq!(posts[0].display_url.as_deref(), Some("https://scontent.cdninstagram.com/abc.jpg"));
+        assert_eq!(posts[0].timestamp_unix, Some(86400));
+        assert!(posts[1].is_video);
+    }
+
+    #[test]
+    fn posts_page_from_the_doc_id_layout() {
+        let page = json!({ "data": { "xdt_api__v1__feed__user_timeline_graphql_connection": { "edges": [
+            { "node": {
+                "pk": "1",
+                "code": "abc",
+                "taken_at": 86400,
+                "image_versions2": { "candidates": [{ "url": "https://scontent.cdninstagram.com/abc.jpg", "width": 1080, "height": 1080 }] }
+            } }
+        ] } } });
+        let posts = scraper(AppConfig::default()).extract_posts_page(&page).unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].shortcode, "abc");
+        assert_eq!(posts[0].display_url.as_deref(), Some("https://scontent.cdninstagram.com/abc.jpg"));
+        assert_eq!(posts[0].timestamp_unix, Some(86400));
+    }
+
+    #[test]
+    fn posts_page_urls_reflect_the_configured_queries() {
+        let config = AppConfig {
+            instagram_posts_query_hash: "myhash".to_string(),
+            instagram_posts_doc_id: Some("1234".to_string()),
+            ..AppConfig::default()
+        };
+        let urls = scraper(config).posts_page_urls("42");
+        assert_eq!(urls.len(), 2);
+        assert!(urls[0].starts_with("https://www.instagram.com/graphql/query/?doc_id=1234&variables="));
+        assert!(urls[1].starts_with("https://www.instagram.com/graphql/query/?query_hash=myhash&variables="));
+        assert!(urls.iter().all(|url| url.contains("%22id%22%3A%2242%22")));
+
+        // Only the query_hash endpoint without a doc_id
+        assert_eq!(scraper(AppConfig::default()).posts_page_urls("42").len(), 1);
+    }
+
+    const LOGIN_WALL_HTML: &str = r#"<!DOCTYPE html><html lang="en"><head><title>Login • Instagram</title></head>
+        <body><script type="application/json">{"require":[["PolarisRoute","LoginAndSignupPage"]]}</script>
+        <form id="loginForm" method="post"><input name="username"><input name="password" type="password"></form></body></html>"#;
+
+    #[tokio::test]
+    async fn login_wall_is_unauthorized_access() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "text/html")], LOGIN_WALL_HTML.as_bytes())]);
+        let result = scraper(AppConfig::default()).make_html_request(&format!("{}/jane/", server.url), "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::UnauthorizedAccess(message)) if message.contains("set instagram_cookies")));
+    }
+
+    #[tokio::test]
+    async fn redirect_to_the_login_page_is_unauthorized_access() {
+        let server = TestServer::start(vec![
+            response(302, &[("Location", "/accounts/login/?next=%2Fjane%2F")], b""),
+            response(200, &[("Content-Type", "text/html")], b"<html><body>Welcome back</body></html>"),
+        ]);
+        let result = scraper(AppConfig::default()).make_html_request(&format!("{}/jane/", server.url), "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::UnauthorizedAccess(_))));
+    }
+
+    #[tokio::test]
+    async fn successive_authenticated_requests_rotate_cookie_sets() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "application/json")], b"{}")]);
+        let config = AppConfig {
+            instagram_cookies_pool: Some(vec!["sessionid=first".to_string(), "sessionid=second".to_string()]),
+            ..AppConfig::default()
+        };
+        let scraper = scraper(config);
+        let url = format!("{}/api/v1/users/web_profile_info/?username=jane", server.url);
+        for _ in 0..2 {
+            let _ = scraper.make_mobile_api_request(&url, "jane", None).await;
+        }
+
+        let cookies: Vec<String> = server.requests().iter().map(|request| request.header("cookie").unwrap().to_string()).collect();
+        assert_eq!(cookies.len(), 2);
+        assert_ne!(cookies[0], cookies[1]);
+    }
+
+    const CHALLENGE_JSON: &str = r#"{"message": "checkpoint_required", "checkpoint_url": "https://www.instagram.com/challenge/?next=/api/v1/", "lock": true, "status": "fail"}"#;
+
+    #[tokio::test]
+    async fn challenge_response_cools_down_its_cookie_set() {
+        let server = TestServer::start(vec![response(400, &[("Content-Type", "application/json")], CHALLENGE_JSON.as_bytes())]);
+        let config = AppConfig {
+            instagram_cookies_pool: Some(vec!["sessionid=first".to_string(), "sessionid=second".to_string()]),
+            ..AppConfig::default()
+        };
+        let scraper = scraper(config);
+        let url = format!("{}/api/v1/users/web_profile_info/?username=jane", server.url);
+
+        let result = scraper.make_mobile_api_request(&url, "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::ChallengeRequired)));
+
+        // The challenged set is skipped by the next requests
+        let challenged = server.requests()[0].header("cookie").unwrap().to_string();
+        assert!((0..3).all(|_| scraper.pick_cookies(None).as_deref() != Some(challenged.as_str())));
+    }
+
+    #[tokio::test]
+    async fn challenge_in_a_successful_response_is_detected() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "application/json")], CHALLENGE_JSON.as_bytes())]);
+        let url = format!("{}/jane/?__a=1&__d=dis", server.url);
+        let result = scraper(AppConfig::default()).make_api_request(&url, "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::ChallengeRequired)));
+    }
+
+    #[tokio::test]
+    async fn delay_is_awaited_between_proxy_attempts() {
+        let servers: Vec<TestServer> = (0..2).map(|_| TestServer::start(vec![response(403, &[], b"")])).collect();
+        let proxies = servers.iter().map(|server| server.url.clone()).collect();
+        let config = AppConfig { proxy_attempt_delay_ms: 300, ..AppConfig::default() };
+        let scraper = InstagramScraper::new(config, ProxyManager::new(Some(proxies), 4));
+
+        let started = std::time::Instant::now();
+        assert!(scraper.try_mobile_api_endpoint("jane").await.is_err());
+        assert_eq!(servers.iter().map(|server| server.requests().len()).sum::<usize>(), 2);
+        // Paused between the two attempts
+        assert!(started.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn proxy_block_page_is_a_proxy_failure() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "text/html")], b"<html><body><h1>Access denied by proxy policy</h1></body></html>")]);
+        let proxy_manager = ProxyManager::new(Some(vec![server.url.clone()]), 4);
+        let scraper = InstagramScraper::new(AppConfig::default(), proxy_manager.clone());
+        let url = "http://i.instagram.com/api/v1/users/web_profile_info/?username=jane";
+
+        let result = scraper.make_mobile_api_json_request(url, Some(&server.url)).await;
+        assert!(matches!(result, Err(ScraperError::ProxyError(_))));
+
+        // The proxy is blamed and the next one tried, rather than failing on a parsing error
+        assert!(matches!(scraper.fetch_mobile_api_json(url, "jane").await, Err(ScraperError::AllProxiesFailed)));
+        assert_eq!(proxy_manager.snapshot()[0].consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn clients_are_bound_to_the_chosen_ip_family() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "application/json")], br#"{"status": "ok"}"#)]);
+        let url = format!("{}/api/v1/users/web_profile_info/?username=jane", server.url);
+
+        // The test server only listens on IPv4
+        let v4 = scraper(AppConfig { ip_family: "v4".to_string(), ..AppConfig::default() });
+        assert!(v4.make_mobile_api_json_request(&url, None).await.is_ok());
+
+        let v6 = scraper(AppConfig { ip_family: "v6".to_string(), ..AppConfig::default() });
+        assert!(v6.make_mobile_api_json_request(&url, None).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn web_api_query_params_are_configurable() {
+        assert_eq!(scraper(AppConfig::default()).web_api_url("jane"), "https://www.instagram.com/jane/?__a=1&__d=dis");
+
+        let config = AppConfig { web_api_query_params: "?__a=1&__comet_req=7&lsd=abc&user={username}".to_string(), ..AppConfig::default() };
+        assert_eq!(scraper(config).web_api_url("jane"), "https://www.instagram.com/jane/?__a=1&__comet_req=7&lsd=abc&user=jane");
+    }
+
+    #[tokio::test]
+    async fn rate_limit_keeps_the_retry_after_delay() {
+        let server = TestServer::start(vec![
+            response(429, &[("Retry-After", "30")], b""),
+            response(429, &[("x-ratelimit-reset", "12")], b""),
+        ]);
+        let scraper = scraper(AppConfig::default());
+        let url = format!("{}/api/v1/users/web_profile_info/?username=jane", server.url);
+
+        let result = scraper.make_mobile_api_json_request(&url, None).await;
+        assert!(matches!(result, Err(ScraperError::RateLimited { retry_after: Some(30) })));
+        let result = scraper.make_mobile_api_json_request(&url, None).await;
+        assert!(matches!(result, Err(ScraperError::RateLimited { retry_after: Some(12) })));
+    }
+
+    #[test]
+    fn accessibility_caption_becomes_the_alt_text() {
+        let items = [
+            json!({ "pk": "1", "code": "abc", "accessibility_caption": " Photo by Jane. May be an image of a cat. " }),
+            json!({ "pk": "2", "code": "def", "accessibility_caption": "" }),
+        ];
+        let posts = scraper(AppConfig::default()).extract_posts_from_items(&items).unwrap();
+        assert_eq!(posts[0].alt_text.as_deref(), Some("Photo by Jane. May be an image of a cat."));
+        assert_eq!(posts[1].alt_text, None);
+        assert_eq!(serde_json::to_value(&posts[0]).unwrap()["altText"], "Photo by Jane. May be an image of a cat.");
+
+        let timeline = json!({ "count": 1, "edges": [{ "node": {
+            "id": "1",
+            "shortcode": "abc",
+            "display_url": "https://scontent.cdninstagram.com/large.jpg",
+            "accessibility_caption": "May be an image of a dog"
+        } }] });
+        let post = scraper(AppConfig::default()).extract_posts_from_json(&timeline).unwrap().remove(0);
+        assert_eq!(post.alt_text.as_deref(), Some("May be an image of a dog"));
+    }
+
+    #[test]
+    fn post_without_image_has_no_display_url() {
+        let item = json!({ "pk": "1", "code": "abc", "caption": { "text": "No image" } });
+        let post = scraper(AppConfig::default()).extract_posts_from_items(&[item]).unwrap().remove(0);
+        assert_eq!(post.display_url, None);
+        assert!(serde_json::to_value(&post).unwrap()["displayUrl"].is_null());
+
+        // The thumbnail of the media is used when there is no other image
+        let item = json!({ "pk": "1", "code": "abc", "media": { "thumbnail_url": "https://scontent.cdninstagram.com/thumb.jpg" } });
+        let post = scraper(AppConfig::default()).extract_posts_from_items(&[item]).unwrap().remove(0);
+        assert_eq!(post.display_url.as_deref(), Some("https://scontent.cdninstagram.com/thumb.jpg"));
+    }
+
+    #[tokio::test]
+    async fn each_tried_endpoint_is_counted() {
+        let config = AppConfig { scrape_endpoint_order: Some(vec!["web".to_string(), "html".to_string()]), ..AppConfig::default() };
+        let scraper = scraper(config);
+        assert!(scraper.scrape_user("jane").await.is_err());
+
+        let endpoints = scraper.stats().endpoints;
+        assert_eq!(endpoints.keys().collect::<Vec<_>>(), vec!["html", "web"]);
+        assert!(endpoints.values().all(|counts| counts.succeeded == 0 && counts.failed == 1));
+    }
+
+    #[tokio::test]
+    async fn configured_accept_language_is_sent() {
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "application/json")], br#"{"status": "ok"}"#)]);
+        let config = AppConfig { scrape_accept_language: Some("fr-FR,fr;q=0.9".to_string()), ..AppConfig::default() };
+        let french = scraper(config);
+        let url = format!("{}/jane/", server.url);
+
+        let _ = french.make_api_request(&url, "jane", None).await;
+        let _ = french.make_mobile_api_request(&url, "jane", None).await;
+        let _ = french.make_html_request(&url, "jane", None).await;
+        let _ = french.make_mobile_api_json_request(&url, None).await;
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests.iter().all(|request| request.header("accept-language") == Some("fr-FR,fr;q=0.9")));
+
+        let _ = scraper(AppConfig::default()).make_mobile_api_json_request(&url, None).await;
+        assert_eq!(server.requests()[4].header("accept-language"), Some("en-US"));
+    }
+
+    // Profile page since the redesign: the profile query and the timeline query results in separate `data-sjs` scripts
+    const BBOX_PROFILE_HTML: &str = r#"<!DOCTYPE html><html><head><title>Jane (@jane) • Instagram photos and videos</title></head><body>
+<script type="application/json" data-content-len="12" data-sjs>{"require":[["ScheduledServerJS","handle",null,[{"__bbox":{"require":[["RelayPrefetchedStreamCache","next",[],["adp_PolarisProfilePageContentQueryRelayPreloader_1",{"__bbox":{"complete":true,"result":{"data":{"user":{"username":"jane","full_name":"Jane Doe","biography":"Hello","is_private":false,"is_verified":true,"follower_count":1250000,"following_count":321,"media_count":42,"profile_pic_url":"https://scontent.cdninstagram.com/v/t51/avatar.jpg"}},"extensions":{"is_final":true}}}}]]]}}]]]}</script>
+<script type="application/json" data-content-len="12" data-sjs>{"require":[["ScheduledServerJS","handle",null,[{"__bbox":{"require":[["RelayPrefetchedStreamCache","next",[],["adp_PolarisProfilePostsQueryRelayPreloader_1",{"__bbox":{"complete":true,"result":{"data":{"xdt_api__v1__feed__user_timeline_graphql_connection":{"edges":[{"node":{"pk":"1","code":"abc","taken_at":1700000000,"image_versions2":{"candidates":[{"url":"https://scontent.cdninstagram.com/v/t51/abc.jpg","width":1080,"height":1080}]}}},{"node":{"pk":"2","code":"def","taken_at":1690000000}}]}}}}}]]]}}]]]}</script>
+</body></html>"#;
+
+    #[test]
+    fn profile_from_embedded_query_results() {
+        let user = scraper(AppConfig::default()).extract_from_additional_data_sources(BBOX_PROFILE_HTML, "jane").unwrap();
+        assert_eq!(user.username, "jane");
+        assert_eq!(user.full_name.as_deref(), Some("Jane Doe"));
+        assert!(user.is_verified);
+        assert_eq!(user.stats.followers_count, Some(1_250_000));
+        assert_eq!(user.stats.posts_count, Some(42));
+
+        let posts = user.posts.unwrap();
+        assert_eq!(posts.iter().map(|post| post.shortcode.as_str()).collect::<Vec<_>>(), vec!["abc", "def"]);
+        assert_eq!(posts[0].display_url.as_deref(), Some("https://scontent.cdninstagram.com/v/t51/abc.jpg"));
+
+        // The embedded profile must be the requested one
+        assert!(scraper(AppConfig::default()).extract_from_additional_data_sources(BBOX_PROFILE_HTML, "john").is_none());
+    }
+
+    #[tokio::test]
+    async fn short_profile_page_is_not_a_block_page() {
+        let html = r#"<html><script>window.__additionalDataLoaded('/jane/', {"user": {"username": "jane", "is_private": true, "follower_count": 3}});</script></html>"#;
+        assert!(html.len() < AppConfig::default().html_min_length);
+        let server = TestServer::start(vec![response(200, &[("Content-Type", "text/html")], html.as_bytes())]);
+
+        let user = scraper(AppConfig::default()).make_html_request(&format!("{}/jane/", server.url), "jane", None).await.unwrap();
+        assert_eq!(user.stats.followers_count, Some(3));
+    }
+
+    #[tokio::test]
+    async fn block_page_is_detected_whatever_its_length() {
+        let block_page = format!(
+            "<html><body>jane<div class=\"g-recaptcha\"></div>{}</body></html>",
+            "<p>Please verify you are human</p>".repeat(100)
+        );
+        assert!(block_page.len() > AppConfig::default().html_min_length);
+        let server = TestServer::start(vec![
+            response(200, &[("Content-Type", "text/html")], block_page.as_bytes()),
+            response(200, &[("Content-Type", "text/html")], b"<html><body>Error</body></html>"),
+        ]);
+        let url = format!("{}/jane/", server.url);
+
+        let result = scraper(AppConfig::default()).make_html_request(&url, "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::ParsingError(message)) if message.contains("block page")));
+
+        // Short pages not mentioning the user are flagged under the configured length only
+        let result = scraper(AppConfig::default()).make_html_request(&url, "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::ParsingError(message)) if message.contains("too short")));
+        let config = AppConfig { html_min_length: 10, ..AppConfig::default() };
+        let result = scraper(config).make_html_request(&url, "jane", None).await;
+        assert!(matches!(result, Err(ScraperError::ParsingError(message)) if message.contains("Could not extract")));
+    }
+
+    #[tokio::test]
+    async fn misdetected_proxy_protocol_is_corrected() {
+        let json = response(200, &[("Content-Type", "application/json")], br#"{"status": "ok"}"#);
+        let server = TestServer::start(vec![json.clone(), json]);
+        // An HTTP proxy given without protocol, wrongly taken for a SOCKS5 one
+        let proxy = server.url.trim_start_matches("http://").to_string();
+        let proxy_manager = ProxyManager::new(Some(vec![proxy.clone()]), 4);
+        proxy_manager.set_proxy_protocol(&proxy, crate::proxy::ProxyProtocol::SOCKS5);
+        let config = AppConfig { connect_timeout: Some(1), ..AppConfig::default() };
+        let scraper = InstagramScraper::new(config, proxy_manager.clone());
+
+        let json = scraper.make_mobile_api_json_request("http://i.instagram.com/api/v1/users/web_profile_info/?username=jane", Some(&proxy)).await.unwrap();
+        assert_eq!(json["status"], "ok");
+        assert_eq!(proxy_manager.get_proxy_protocol(&proxy), crate::proxy::ProxyProtocol::HTTP);
+
+        // Retried as an HTTP proxy request after the failed SOCKS5 handshake
+        let request = server.requests().pop().unwrap();
+        assert!(request.line.starts_with("GET http://i.instagram.com/api/v1/users/web_profile_info/"));
+    }
+
+    #[test]
+    fn reels_cursor_advances_the_page() {
+        assert_eq!(clips_url("42", None, 12).unwrap(), "https://i.instagram.com/api/v1/clips/user/?target_user_id=42&page_size=12");
+        assert_eq!(
+            clips_url("42", Some("QVFB+x/y=="), 12).unwrap(),
+            "https://i.instagram.com/api/v1/clips/user/?target_user_id=42&page_size=12&max_id=QVFB%2Bx%2Fy%3D%3D"
+        );
+
+        let page = |items: Value, paging_info: Value| {
+            scraper(AppConfig::default()).extract_reels_from_clips(&json!({ "items": items, "paging_info": paging_info })).unwrap()
+        };
+        let first = page(
+            json!([{ "media": { "pk": "1", "code": "reel1" } }, { "media": { "pk": "2", "code": "reel2" } }]),
+            json!({ "more_available": true, "max_id": "cursor1" }),
+        );
+        assert_eq!(first.end_cursor.as_deref(), Some("cursor1"));
+
+        // Last page: a cursor without more reels isn't returned
+        let second = page(json!([{ "media": { "pk": "3", "code": "reel3" } }]), json!({ "more_available": false, "max_id": "cursor2" }));
+        let shortcodes: Vec<&str> = first.reels.iter().chain(&second.reels).map(|reel| reel.shortcode.as_str()).collect();
+        assert_eq!(shortcodes, vec!["reel1", "reel2", "reel3"]);
+        assert_eq!(second.end_cursor, None);
+    }
+}
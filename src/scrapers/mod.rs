@@ -1 +1,135 @@
-pub mod instagram; 
\ No newline at end of file
+pub mod instagram;
+pub mod stats;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::models::common::{Platform, Post, Profile};
+
+#[derive(Error, Debug)]
+pub enum ScraperError {
+    #[error("Network error: {0}")]
+    NetworkError(#[from] reqwest::Error),
+    
+    #[error("Parsing error: {0}")]
+    ParsingError(String),
+    
+    // Instagram answered 429, with the delay in seconds it asked to wait when given
+    #[error("Rate limited or blocked")]
+    RateLimited { retry_after: Option<u64> },
+    
+    #[error("Profile not found")]
+    ProfileNotFound,
+    
+    #[error("Post not found")]
+    PostNotFound,
+    
+    #[error("Private profile")]
+    PrivateProfile,
+    
+    #[error("Proxy error: {0}")]
+    ProxyError(String),
+    
+    #[error("All proxies failed")]
+    AllProxiesFailed,
+    
+    #[error("Unauthorized access: {0}")]
+    UnauthorizedAccess(String),
+    
+    // Instagram flagged the account (or IP) and requires a checkpoint challenge to be completed
+    #[error("Instagram challenge required")]
+    ChallengeRequired,
+    
+    // The whole scrape took longer than the configured deadline (in seconds)
+    #[error("Scrape deadline of {0}s exceeded")]
+    Timeout(u64),
+    
+    // No scrape slot freed up within the queue timeout (in seconds)
+    #[error("Too many concurrent scrapes, none finished within {0}s")]
+    Busy(u64),
+}
+
+impl ScraperError {
+    /// Stable machine-readable code of the error, returned in API error bodies
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScraperError::NetworkError(_) => "NETWORK_ERROR",
+            ScraperError::ParsingError(_) => "PARSING_ERROR",
+            ScraperError::RateLimited { .. } => "RATE_LIMITED",
+            ScraperError::ProfileNotFound => "PROFILE_NOT_FOUND",
+            ScraperError::PostNotFound => "POST_NOT_FOUND",
+            ScraperError::PrivateProfile => "PRIVATE_PROFILE",
+            ScraperError::ProxyError(_) => "PROXY_ERROR",
+            ScraperError::AllProxiesFailed => "ALL_PROXIES_FAILED",
+            ScraperError::UnauthorizedAccess(_) => "UNAUTHORIZED_ACCESS",
+            ScraperError::ChallengeRequired => "CHALLENGE_REQUIRED",
+            ScraperError::Timeout(_) => "SCRAPE_TIMEOUT",
+            ScraperError::Busy(_) => "SCRAPER_BUSY",
+        }
+    }
+}
+
+/// Common interface implemented by every platform scraper.
+///
+/// The trait is object-safe so scrapers can be stored as `Arc<dyn Scraper>`
+/// in a [`ScraperRegistry`] keyed by platform.
+#[rocket::async_trait]
+pub trait Scraper: Send + Sync {
+    /// Platform handled by this scraper
+    fn platform(&self) -> Platform;
+    
+    /// Scrape a user profile (without its media)
+    async fn scrape_user(&self, username: &str) -> Result<Profile, ScraperError>;
+    
+    /// Scrape the latest posts of a user
+    async fn scrape_posts(&self, username: &str) -> Result<Vec<Post>, ScraperError>;
+}
+
+// Scrapers available to the API, by platform
+#[derive(Clone, Default)]
+pub struct ScraperRegistry {
+    scrapers: HashMap<Platform, Arc<dyn Scraper>>,
+}
+
+impl ScraperRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn register(&mut self, scraper: Arc<dyn Scraper>) {
+        self.scrapers.insert(scraper.platform(), scraper);
+    }
+    
+    pub fn get(&self, platform: Platform) -> Option<Arc<dyn Scraper>> {
+        self.scrapers.get(&platform).cloned()
+    }
+    
+    pub fn platforms(&self) -> Vec<Platform> {
+        self.scrapers.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+    use crate::proxy::ProxyManager;
+    use crate::scrapers::instagram::InstagramScraper;
+
+    #[test]
+    fn instagram_scraper_is_a_registered_trait_object() {
+        let scraper: Arc<dyn Scraper> = Arc::new(InstagramScraper::new(AppConfig::default(), ProxyManager::new(None, 4)));
+        let mut registry = ScraperRegistry::new();
+        registry.register(scraper);
+
+        assert_eq!(registry.platforms(), vec![Platform::Instagram]);
+        assert_eq!(registry.get(Platform::Instagram).map(|scraper| scraper.platform()), Some(Platform::Instagram));
+    }
+
+    #[test]
+    fn platform_from_name() {
+        assert_eq!(Platform::from_name("instagram"), Some(Platform::Instagram));
+        assert_eq!(Platform::from_name("tiktok"), None);
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use serde::Serialize;
+
+use crate::scrapers::ScraperError;
+
+// Outcome counters of the scrapes since startup
+#[derive(Debug, Default)]
+pub struct ScrapeStats {
+    succeeded: AtomicU64,
+    // Error code -> number of failed scrapes
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    // Profile endpoint (mobile, web, html) -> outcomes of its attempts
+    endpoints: Mutex<BTreeMap<String, EndpointCounts>>,
+}
+
+// Outcomes of the attempts of a profile endpoint, to tune scrape_endpoint_order
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EndpointCounts {
+    pub succeeded: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrapeStatsSnapshot {
+    pub in_flight: usize,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub errors: BTreeMap<&'static str, u64>,
+    pub endpoints: BTreeMap<String, EndpointCounts>,
+}
+
+impl ScrapeStats {
+    pub fn record_success(&self) {
+        self.succeeded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self, err: &ScraperError) {
+        *self.errors.lock().unwrap().entry(err.code()).or_insert(0) += 1;
+    }
+
+    pub fn record_endpoint(&self, endpoint: &str, succeeded: bool) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let counts = endpoints.entry(endpoint.to_string()).or_default();
+        if succeeded {
+            counts.succeeded += 1;
+        } else {
+            counts.failed += 1;
+        }
+    }
+
+    pub fn snapshot(&self, in_flight: usize) -> ScrapeStatsSnapshot {
+        let errors = self.errors.lock().unwrap().clone();
+        ScrapeStatsSnapshot {
+            in_flight,
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            failed: errors.values().sum(),
+            errors,
+            endpoints: self.endpoints.lock().unwrap().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_outcomes_are_counted_separately() {
+        let stats = ScrapeStats::default();
+        stats.record_endpoint("mobile", false);
+        stats.record_endpoint("web", true);
+        stats.record_endpoint("web", true);
+        stats.record_endpoint("mobile", false);
+        stats.record_success();
+
+        let snapshot = stats.snapshot(0);
+        assert_eq!(snapshot.succeeded, 1);
+        assert_eq!((snapshot.endpoints["web"].succeeded, snapshot.endpoints["web"].failed), (2, 0));
+        assert_eq!((snapshot.endpoints["mobile"].succeeded, snapshot.endpoints["mobile"].failed), (0, 2));
+        assert!(!snapshot.endpoints.contains_key("html"));
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["endpoints"]["web"]["succeeded"], 2);
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::models::instagram::{InstagramPost, InstagramUser};
+
+// Header carrying the HMAC-SHA256 signature of the payload
+const SIGNATURE_HEADER: &str = "X-Scrapn-Signature";
+
+// Delivery attempts and base delay, doubled after each failed attempt
+const MAX_ATTEMPTS: u32 = 3;
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CountChange {
+    pub previous: Option<u64>,
+    pub current: Option<u64>,
+}
+
+// Changes between two scrapes of the same profile
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileDiff {
+    pub username: String,
+    pub new_posts: Vec<InstagramPost>,
+    pub followers_count: Option<CountChange>,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl ProfileDiff {
+    // Compare a freshly scraped profile with the previous one, returning None when nothing relevant changed
+    pub fn between(previous: &InstagramUser, current: &InstagramUser) -> Option<Self> {
+        let known_shortcodes: Vec<&str> = previous
+            .posts
+            .iter()
+            .flatten()
+            .map(|post| post.shortcode.as_str())
+            .collect();
+
+        let new_posts: Vec<InstagramPost> = current
+            .posts
+            .iter()
+            .flatten()
+            .filter(|post| !known_shortcodes.contains(&post.shortcode.as_str()))
+            .cloned()
+            .collect();
+
+        let followers_count = Some(CountChange {
+            previous: previous.stats.followers_count,
+            current: current.stats.followers_count,
+        })
+        .filter(|change| change.previous != change.current);
+
+        if new_posts.is_empty() && followers_count.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            username: current.username.clone(),
+            new_posts,
+            followers_count,
+            detected_at: Utc::now(),
+        })
+    }
+}
+
+// Sends profile changes to the configured webhook
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    url: String,
+    secret: Option<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String, secret: Option<String>, timeout: u64) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout))
+            .build()
+            .unwrap_or_else(|_| reqwest::Client::new());
+
+        Self { url, secret, client }
+    }
+
+    // Deliver the diff in the background
+    pub fn notify(&self, diff: ProfileDiff) {
+        let notifier = self.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move { notifier.deliver(&diff).await });
+            }
+            Err(_) => warn!("No async runtime available, webhook for '{}' not sent", diff.username),
+        }
+    }
+
+    async fn deliver(&self, diff: &ProfileDiff) {
+        let body = match serde_json::to_vec(diff) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook payload for '{}': {}", diff.username, e);
+                return;
+            }
+        };
+        let signature = self.secret.as_ref().map(|secret| sign(secret, &body));
+
+        let mut delay = BASE_RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(SIGNATURE_HEADER, signature);
+            }
+
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("Webhook delivered for '{}'", diff.username);
+                    return;
+                }
+                Ok(response) => warn!(
+                    "Webhook for '{}' failed with status {} (attempt {}/{})",
+                    diff.username, response.status(), attempt, MAX_ATTEMPTS
+                ),
+                Err(e) => warn!(
+                    "Webhook for '{}' failed: {} (attempt {}/{})",
+                    diff.username, e, attempt, MAX_ATTEMPTS
+                ),
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
+
+// HMAC-SHA256 signature of the payload, formatted as `sha256=<hex>`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}